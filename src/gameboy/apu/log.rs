@@ -0,0 +1,31 @@
+/// A write to one of the APU's registers or its wave RAM.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RegisterWrite {
+    /// When the write happened, in T-cycles from the start of the log.
+    pub cycle: u64,
+    /// The address written, from 0xFF10 to 0xFF3F.
+    pub address: u16,
+    pub value: u8,
+}
+
+/// The writes to the APU over a stretch of time, as taken by `GameBoy::take_register_log`.
+///
+/// The time is counted in T-cycles of the APU, i.e. at 4194304 Hz whatever the CPU's speed, so
+/// consecutive logs add up to the time that has passed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RegisterLog {
+    /// The writes in the order they happened, including those that the APU ignored.
+    pub writes: Vec<RegisterWrite>,
+    /// How long the stretch of time is.
+    pub cycles: u64,
+}
+
+impl RegisterLog {
+    pub(super) fn push(&mut self, address: u16, value: u8) {
+        self.writes.push(RegisterWrite {
+            cycle: self.cycles,
+            address,
+            value,
+        });
+    }
+}
@@ -0,0 +1,697 @@
+use std::mem;
+
+use super::mem::Bus;
+
+pub use self::log::{RegisterLog, RegisterWrite};
+use self::noise::Noise;
+use self::resample::Resampler;
+use self::square::Square;
+use self::wave::Wave;
+
+mod log;
+mod noise;
+mod resample;
+mod square;
+mod wave;
+
+/// The number of T-cycles per second, which the APU runs at regardless of the CPU's speed.
+pub(crate) const CLOCK_RATE: u32 = 4_194_304;
+/// The T-cycles between two steps of the frame sequencer, which makes it run at 512 Hz.
+const FRAME_SEQUENCER_PERIOD: u32 = 8192;
+
+const NR10: u16 = 0xFF10;
+const NR11: u16 = 0xFF11;
+const NR14: u16 = 0xFF14;
+const NR21: u16 = 0xFF16;
+const NR24: u16 = 0xFF19;
+const NR30: u16 = 0xFF1A;
+const NR31: u16 = 0xFF1B;
+const NR34: u16 = 0xFF1E;
+const NR41: u16 = 0xFF20;
+const NR44: u16 = 0xFF23;
+const NR50: u16 = 0xFF24;
+const NR51: u16 = 0xFF25;
+const NR52: u16 = 0xFF26;
+const WAVE_RAM_START: u16 = 0xFF30;
+const WAVE_RAM_END: u16 = 0xFF3F;
+
+/// The bits of each register from NR10 to NR52 that always read as set, because they're unused
+/// or write only.
+const READ_MASKS: [u8; 23] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, // NR10-NR14
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF, // NR20-NR24
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF, // NR30-NR34
+    0xFF, 0xFF, 0x00, 0x00, 0xBF, // NR40-NR44
+    0x00, 0x00, 0x70, // NR50-NR52
+];
+
+/// The four channels of the APU, in the order of their bits in NR51 and NR52.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Channel {
+    Square1,
+    Square2,
+    Wave,
+    Noise,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 4] = [
+        Channel::Square1,
+        Channel::Square2,
+        Channel::Wave,
+        Channel::Noise,
+    ];
+}
+
+/// The length counter of a channel, which turns the channel off when it runs out.
+#[derive(Debug)]
+struct Length {
+    /// The length that the counter starts from, 64 or 256 for the wave channel.
+    max: u16,
+    counter: u16,
+    enabled: bool,
+}
+
+impl Length {
+    fn new(max: u16) -> Length {
+        Length {
+            max,
+            counter: 0,
+            enabled: false,
+        }
+    }
+
+    /// Loads the length written to NRx1, which the counter counts up from towards the maximum.
+    fn load(&mut self, length: u8) {
+        self.counter = self.max - u16::from(length);
+    }
+
+    /// Clocked by the frame sequencer, returning whether the channel has to be turned off.
+    fn clock(&mut self) -> bool {
+        if !self.enabled || self.counter == 0 {
+            return false;
+        }
+        self.counter -= 1;
+        self.counter == 0
+    }
+
+    /// Handles the length enable and trigger bits of a write to NRx4, returning whether the
+    /// channel has to be turned off.
+    ///
+    /// `extra_clock` is set when the frame sequencer's next step doesn't clock the length. Enabling
+    /// the length then clocks it right away, and a trigger reloads it with one less.
+    fn write_control(&mut self, value: u8, extra_clock: bool) -> bool {
+        let enable = value & 0x40 != 0;
+        let trigger = value & 0x80 != 0;
+        let mut expired = false;
+        if extra_clock && enable && !self.enabled && self.counter > 0 {
+            self.counter -= 1;
+            expired = self.counter == 0 && !trigger;
+        }
+        self.enabled = enable;
+        if trigger && self.counter == 0 {
+            self.counter = if extra_clock && enable {
+                self.max - 1
+            } else {
+                self.max
+            };
+        }
+        expired
+    }
+}
+
+/// The volume envelope of the square and noise channels, controlled through NRx2.
+#[derive(Debug, Default)]
+struct Envelope {
+    register: u8,
+    volume: u8,
+    /// Envelope clocks left until the volume changes.
+    timer: u8,
+}
+
+impl Envelope {
+    fn write(&mut self, value: u8) {
+        self.register = value;
+    }
+
+    /// Whether the channel's DAC is on, which takes an initial volume or an increasing envelope.
+    fn dac_enabled(&self) -> bool {
+        self.register & 0xF8 != 0
+    }
+
+    fn period(&self) -> u8 {
+        self.register & 0x07
+    }
+
+    fn trigger(&mut self) {
+        self.volume = self.register >> 4;
+        self.timer = self.period();
+    }
+
+    /// Clocked by the frame sequencer at 64 Hz.
+    fn clock(&mut self) {
+        if self.period() == 0 {
+            return;
+        }
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return;
+        }
+        self.timer = self.period();
+        if self.register & 0x08 != 0 {
+            self.volume = (self.volume + 1).min(15);
+        } else {
+            self.volume = self.volume.saturating_sub(1);
+        }
+    }
+}
+
+/// The audio processing unit, which owns the sound registers at 0xFF10-0xFF3F.
+///
+/// Its four channels each produce a digital level from 0 to 15, which their DACs turn into an
+/// analog one. Those are mixed into the left and right outputs according to NR51 and NR50, and
+/// resampled to the host's rate.
+#[derive(Debug)]
+pub(crate) struct Apu {
+    square1: Square,
+    square2: Square,
+    wave: Wave,
+    noise: Noise,
+    nr50: u8,
+    nr51: u8,
+    powered: bool,
+    /// The step of the frame sequencer to run next.
+    frame_step: u8,
+    frame_timer: u32,
+    /// The channels left out of the mix, for debugging.
+    muted: [bool; 4],
+    /// The channels that the mix is limited to if there are any, for debugging.
+    solo: [bool; 4],
+    sample_rate: u32,
+    channel_sampling: bool,
+    /// Produces the samples at the host's rate, if any are wanted.
+    resampler: Option<Resampler>,
+    /// Produces the samples of each channel on its own, if they're wanted as well.
+    channel_resamplers: Option<Vec<Resampler>>,
+    /// The last value written to each register from NR10 to NR51 since the APU was powered on,
+    /// which a log starts out by writing again.
+    registers: [u8; 22],
+    /// The writes since the log was last taken, if they're being logged.
+    log: Option<RegisterLog>,
+}
+
+impl Apu {
+    pub(crate) fn new() -> Apu {
+        Apu {
+            square1: Square::new(true),
+            square2: Square::new(false),
+            wave: Wave::new(),
+            noise: Noise::new(),
+            nr50: 0,
+            nr51: 0,
+            powered: false,
+            frame_step: 0,
+            frame_timer: 0,
+            muted: [false; 4],
+            solo: [false; 4],
+            sample_rate: 0,
+            channel_sampling: false,
+            resampler: None,
+            channel_resamplers: None,
+            registers: [0; 22],
+            log: None,
+        }
+    }
+
+    /// Sets the rate of the samples in Hz, where 0 stops sampling altogether.
+    pub(crate) fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.reset_resamplers();
+    }
+
+    /// Sets whether each channel is sampled on its own as well, at the same rate as the mix.
+    pub(crate) fn set_channel_sampling(&mut self, enabled: bool) {
+        self.channel_sampling = enabled;
+        self.reset_resamplers();
+    }
+
+    fn reset_resamplers(&mut self) {
+        let sample_rate = self.sample_rate;
+        self.resampler = (sample_rate != 0).then(|| Resampler::new(sample_rate));
+        self.channel_resamplers = (sample_rate != 0 && self.channel_sampling).then(|| {
+            Channel::ALL
+                .iter()
+                .map(|_| Resampler::new(sample_rate))
+                .collect()
+        });
+    }
+
+    pub(crate) fn set_muted(&mut self, channel: Channel, muted: bool) {
+        self.muted[channel as usize] = muted;
+    }
+
+    pub(crate) fn set_solo(&mut self, channel: Channel, solo: bool) {
+        self.solo[channel as usize] = solo;
+    }
+
+    /// Takes the stereo samples produced since the last call, as left and right pairs.
+    pub(crate) fn take_samples(&mut self) -> Vec<[i16; 2]> {
+        self.resampler
+            .as_mut()
+            .map_or_else(Vec::new, Resampler::take_samples)
+    }
+
+    /// Takes the samples of the channel on its own produced since the last call, which are
+    /// panned and scaled like in the mix, but aren't affected by muting it.
+    pub(crate) fn take_channel_samples(&mut self, channel: Channel) -> Vec<[i16; 2]> {
+        self.channel_resamplers
+            .as_mut()
+            .map_or_else(Vec::new, |resamplers| {
+                resamplers[channel as usize].take_samples()
+            })
+    }
+
+    /// Starts logging the writes to the registers, or stops it. A log starts out with writes at
+    /// its very beginning that bring an APU fresh from reset to the current state, retriggering
+    /// the channels that are playing.
+    pub(crate) fn set_register_logging(&mut self, enabled: bool) {
+        if !enabled {
+            self.log = None;
+            return;
+        }
+        let mut log = RegisterLog::default();
+        log.push(NR52, (self.powered as u8) << 7);
+        for (index, &value) in self.wave.ram().iter().enumerate() {
+            log.push(WAVE_RAM_START + index as u16, value);
+        }
+        if self.powered {
+            let playing = self.status();
+            for (index, &value) in self.registers.iter().enumerate() {
+                let addr = NR10 + index as u16;
+                let value = match addr {
+                    NR14 | NR24 | NR34 | NR44 => {
+                        let channel = (addr - NR14) / 5;
+                        value & 0x7F | (playing >> channel & 1) << 7
+                    }
+                    _ => value,
+                };
+                log.push(addr, value);
+            }
+        }
+        self.log = Some(log);
+    }
+
+    /// Takes the writes logged since the last call.
+    pub(crate) fn take_register_log(&mut self) -> RegisterLog {
+        self.log.as_mut().map(mem::take).unwrap_or_default()
+    }
+
+    /// Advances the APU by the given number of T-cycles.
+    pub(crate) fn tick(&mut self, cycles: u8) {
+        if let Some(log) = &mut self.log {
+            log.cycles += u64::from(cycles);
+        }
+        if self.powered {
+            self.square1.tick(cycles);
+            self.square2.tick(cycles);
+            self.wave.tick(cycles);
+            self.noise.tick(cycles);
+            self.frame_timer += u32::from(cycles);
+            if self.frame_timer >= FRAME_SEQUENCER_PERIOD {
+                self.frame_timer -= FRAME_SEQUENCER_PERIOD;
+                self.step_frame_sequencer();
+            }
+        }
+        if self.resampler.is_none() {
+            return;
+        }
+        let outputs = self.outputs();
+        let mix = self.mix(&outputs);
+        if let Some(resampler) = &mut self.resampler {
+            resampler.push(u32::from(cycles), mix);
+        }
+        if let Some(resamplers) = &mut self.channel_resamplers {
+            for (resampler, &output) in resamplers.iter_mut().zip(&outputs) {
+                resampler.push(u32::from(cycles), output);
+            }
+        }
+    }
+
+    /// Clocks the lengths at 256 Hz, the sweep at 128 Hz and the envelopes at 64 Hz.
+    fn step_frame_sequencer(&mut self) {
+        if self.frame_step & 1 == 0 {
+            self.square1.clock_length();
+            self.square2.clock_length();
+            self.wave.clock_length();
+            self.noise.clock_length();
+        }
+        if self.frame_step == 2 || self.frame_step == 6 {
+            self.square1.clock_sweep();
+        }
+        if self.frame_step == 7 {
+            self.square1.clock_envelope();
+            self.square2.clock_envelope();
+            self.noise.clock_envelope();
+        }
+        self.frame_step = (self.frame_step + 1) % 8;
+    }
+
+    /// What each channel's DAC puts out, from -15 to 15, or 0 while it's off.
+    fn levels(&self) -> [i32; 4] {
+        let outputs = [
+            self.square1.output(),
+            self.square2.output(),
+            self.wave.output(),
+            self.noise.output(),
+        ];
+        let mut levels = [0; 4];
+        for (level, output) in levels.iter_mut().zip(&outputs) {
+            *level = output.map_or(0, |value| 2 * i32::from(value) - 15);
+        }
+        levels
+    }
+
+    /// What each channel adds to the left and right outputs, panned by NR51 and scaled by the
+    /// volumes in NR50.
+    fn outputs(&self) -> [[i32; 2]; 4] {
+        let left_volume = i32::from(self.nr50 >> 4 & 0x07) + 1;
+        let right_volume = i32::from(self.nr50 & 0x07) + 1;
+        let mut outputs = [[0; 2]; 4];
+        for (channel, (output, level)) in outputs.iter_mut().zip(&self.levels()).enumerate() {
+            // Four channels at full volume add up to 480, which is scaled to most of the i16
+            // range.
+            if self.nr51 & (0x10 << channel) != 0 {
+                output[0] = level * left_volume * 64;
+            }
+            if self.nr51 & (0x01 << channel) != 0 {
+                output[1] = level * right_volume * 64;
+            }
+        }
+        outputs
+    }
+
+    /// Mixes the channels that aren't muted into a stereo sample.
+    fn mix(&self, outputs: &[[i32; 2]; 4]) -> [i32; 2] {
+        let soloing = self.solo.contains(&true);
+        let mut mix = [0; 2];
+        for (channel, output) in outputs.iter().enumerate() {
+            if self.muted[channel] || soloing && !self.solo[channel] {
+                continue;
+            }
+            mix[0] += output[0];
+            mix[1] += output[1];
+        }
+        mix
+    }
+
+    /// Turns the APU off through NR52, which clears all of its registers except for the wave RAM
+    /// and the length counters.
+    fn power_off(&mut self) {
+        self.square1.power_off();
+        self.square2.power_off();
+        self.wave.power_off();
+        self.noise.power_off();
+        self.nr50 = 0;
+        self.nr51 = 0;
+        self.powered = false;
+        self.registers = [0; 22];
+    }
+
+    fn power_on(&mut self) {
+        self.powered = true;
+        self.frame_step = 0;
+        self.frame_timer = 0;
+    }
+
+    fn status(&self) -> u8 {
+        let channels = [
+            self.square1.enabled(),
+            self.square2.enabled(),
+            self.wave.enabled(),
+            self.noise.enabled(),
+        ];
+        let status = channels
+            .iter()
+            .enumerate()
+            .fold(0, |status, (channel, &enabled)| {
+                status | (enabled as u8) << channel
+            });
+        (self.powered as u8) << 7 | status
+    }
+}
+
+impl Bus for Apu {
+    fn read_u8(&self, addr: u16) -> u8 {
+        let value = match addr {
+            NR10..=NR14 => self.square1.read(addr - NR10),
+            // NR20 at 0xFF15 doesn't exist, but square 2 pretends to have it like square 1.
+            0xFF15..=NR24 => self.square2.read(addr - 0xFF15),
+            NR30..=NR34 => self.wave.read(addr - NR30),
+            // NR40 at 0xFF1F doesn't exist either.
+            0xFF1F..=NR44 => self.noise.read(addr - 0xFF1F),
+            NR50 => self.nr50,
+            NR51 => self.nr51,
+            NR52 => self.status(),
+            WAVE_RAM_START..=WAVE_RAM_END => return self.wave.read_ram(addr - WAVE_RAM_START),
+            _ => return 0xFF,
+        };
+        value | READ_MASKS[usize::from(addr - NR10)]
+    }
+
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        if let Some(log) = &mut self.log {
+            log.push(addr, value);
+        }
+        match addr {
+            WAVE_RAM_START..=WAVE_RAM_END => self.wave.write_ram(addr - WAVE_RAM_START, value),
+            NR52 if value & 0x80 == 0 => self.power_off(),
+            NR52 if !self.powered => self.power_on(),
+            NR52 => {}
+            // While off, the registers can't be written, except for the lengths on the DMG.
+            NR11 | NR21 if !self.powered => {
+                let square = if addr == NR11 {
+                    &mut self.square1
+                } else {
+                    &mut self.square2
+                };
+                square.write_length(value);
+            }
+            NR31 if !self.powered => self.wave.write_length(value),
+            NR41 if !self.powered => self.noise.write_length(value),
+            _ if !self.powered => {}
+            _ => {
+                // The length counters are clocked on even steps, so an odd next step means that
+                // the next one won't.
+                let extra_clock = self.frame_step & 1 != 0;
+                if let NR10..=NR51 = addr {
+                    self.registers[usize::from(addr - NR10)] = value;
+                }
+                match addr {
+                    NR10..=NR14 => self.square1.write(addr - NR10, value, extra_clock),
+                    0xFF15..=NR24 => self.square2.write(addr - 0xFF15, value, extra_clock),
+                    NR30..=NR34 => self.wave.write(addr - NR30, value, extra_clock),
+                    0xFF1F..=NR44 => self.noise.write(addr - 0xFF1F, value, extra_clock),
+                    NR50 => self.nr50 = value,
+                    NR51 => self.nr51 = value,
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+impl Default for Apu {
+    fn default() -> Apu {
+        Apu::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_masks() {
+        let mut apu = Apu::new();
+        assert_eq!(0x70, apu.read_u8(NR52));
+        apu.write_u8(NR52, 0x80);
+        assert_eq!(0xF0, apu.read_u8(NR52));
+        for addr in NR10..=NR51 {
+            apu.write_u8(addr, 0x00);
+        }
+        let values: Vec<u8> = (NR10..=NR51).map(|addr| apu.read_u8(addr)).collect();
+        assert_eq!(&READ_MASKS[..22], &values[..]);
+        assert_eq!(0xFF, apu.read_u8(0xFF27));
+    }
+
+    #[test]
+    fn test_power_off() {
+        let mut apu = Apu::new();
+        apu.write_u8(NR52, 0x80);
+        apu.write_u8(NR50, 0x77);
+        apu.write_u8(0xFF12, 0xF0);
+        apu.write_u8(NR14, 0x80);
+        apu.write_u8(WAVE_RAM_START, 0x12);
+        assert_eq!(0xF1, apu.read_u8(NR52));
+
+        apu.write_u8(NR52, 0x00);
+        assert_eq!(0x70, apu.read_u8(NR52));
+        assert_eq!(0x00, apu.read_u8(NR50));
+        assert_eq!(0x00, apu.read_u8(0xFF12));
+        assert_eq!(0x12, apu.read_u8(WAVE_RAM_START));
+        // Registers can't be written while the APU is off.
+        apu.write_u8(NR50, 0x77);
+        assert_eq!(0x00, apu.read_u8(NR50));
+    }
+
+    #[test]
+    fn test_length() {
+        let mut apu = Apu::new();
+        apu.write_u8(NR52, 0x80);
+        apu.write_u8(0xFF12, 0xF0);
+        // A length of 62 leaves 2 clocks at 256 Hz, i.e. two steps of 8192 T-cycles.
+        apu.write_u8(NR11, 62);
+        apu.write_u8(NR14, 0xC0);
+        for _ in 0..FRAME_SEQUENCER_PERIOD / 4 {
+            apu.tick(4);
+        }
+        assert_eq!(0xF1, apu.read_u8(NR52));
+        for _ in 0..FRAME_SEQUENCER_PERIOD / 4 * 2 {
+            apu.tick(4);
+        }
+        assert_eq!(0xF0, apu.read_u8(NR52));
+    }
+
+    #[test]
+    fn test_length_extra_clock() {
+        let mut apu = Apu::new();
+        apu.write_u8(NR52, 0x80);
+        apu.write_u8(0xFF12, 0xF0);
+        apu.write_u8(NR11, 63);
+        apu.write_u8(NR14, 0x80);
+        // After step 0 the next step doesn't clock the length, so enabling it clocks it once.
+        for _ in 0..FRAME_SEQUENCER_PERIOD / 4 {
+            apu.tick(4);
+        }
+        assert_eq!(0xF1, apu.read_u8(NR52));
+        apu.write_u8(NR14, 0x40);
+        assert_eq!(0xF0, apu.read_u8(NR52));
+    }
+
+    #[test]
+    fn test_samples() {
+        let mut apu = Apu::new();
+        assert!(apu.take_samples().is_empty());
+        apu.set_sample_rate(32768);
+        for _ in 0..1024 {
+            apu.tick(4);
+        }
+        assert_eq!(32, apu.take_samples().len());
+        assert!(apu.take_samples().is_empty());
+    }
+
+    #[test]
+    fn test_mute_and_solo() {
+        let mut apu = Apu::new();
+        apu.write_u8(NR52, 0x80);
+        apu.write_u8(NR51, 0xFF);
+        // Squares 1 and 2 with their DACs on, at their lowest level.
+        apu.write_u8(0xFF12, 0x08);
+        apu.write_u8(0xFF17, 0x08);
+        let outputs = apu.outputs();
+        assert_eq!([-15 * 64, -15 * 64], outputs[0]);
+        assert_eq!([0, 0], outputs[2]);
+        assert_eq!([-30 * 64, -30 * 64], apu.mix(&outputs));
+
+        apu.set_muted(Channel::Square1, true);
+        assert_eq!([-15 * 64, -15 * 64], apu.mix(&outputs));
+        apu.set_muted(Channel::Square1, false);
+        apu.set_solo(Channel::Wave, true);
+        assert_eq!([0, 0], apu.mix(&outputs));
+        apu.set_solo(Channel::Square2, true);
+        assert_eq!([-15 * 64, -15 * 64], apu.mix(&outputs));
+    }
+
+    #[test]
+    fn test_channel_samples() {
+        let mut apu = Apu::new();
+        apu.set_channel_sampling(true);
+        apu.set_sample_rate(32768);
+        apu.set_muted(Channel::Noise, true);
+        for _ in 0..1024 {
+            apu.tick(4);
+        }
+        assert_eq!(32, apu.take_channel_samples(Channel::Noise).len());
+        assert_eq!(32, apu.take_samples().len());
+        apu.set_channel_sampling(false);
+        apu.tick(255);
+        assert!(apu.take_channel_samples(Channel::Noise).is_empty());
+    }
+
+    #[test]
+    fn test_register_log() {
+        let mut apu = Apu::new();
+        assert_eq!(RegisterLog::default(), apu.take_register_log());
+        apu.write_u8(NR52, 0x80);
+        apu.write_u8(0xFF12, 0xF0);
+        apu.write_u8(0xFF13, 0x34);
+        apu.write_u8(NR14, 0x87);
+        apu.write_u8(WAVE_RAM_START, 0x12);
+
+        apu.set_register_logging(true);
+        apu.tick(4);
+        apu.write_u8(NR50, 0x77);
+        apu.tick(2);
+        let log = apu.take_register_log();
+        assert_eq!(6, log.cycles);
+        // The log starts out by powering on, filling the wave RAM and writing the registers,
+        // which retriggers square 1.
+        let write = |cycle, address, value| RegisterWrite {
+            cycle,
+            address,
+            value,
+        };
+        assert_eq!(write(0, NR52, 0x80), log.writes[0]);
+        assert_eq!(write(0, WAVE_RAM_START, 0x12), log.writes[1]);
+        assert_eq!(write(0, 0xFF13, 0x34), log.writes[17 + 3]);
+        assert_eq!(write(0, NR14, 0x87), log.writes[17 + 4]);
+        assert_eq!(write(0, 0xFF1E, 0x00), log.writes[17 + 14]);
+        assert_eq!(Some(&write(4, NR50, 0x77)), log.writes.last());
+        assert_eq!(17 + 22 + 1, log.writes.len());
+
+        // The next log picks up from there.
+        apu.tick(4);
+        apu.write_u8(NR52, 0x00);
+        let log = apu.take_register_log();
+        assert_eq!(vec![write(4, NR52, 0x00)], log.writes);
+        apu.set_register_logging(false);
+        apu.write_u8(NR52, 0x80);
+        assert_eq!(RegisterLog::default(), apu.take_register_log());
+    }
+
+    #[test]
+    fn test_no_aliasing() {
+        let mut apu = Apu::new();
+        apu.set_sample_rate(44_100);
+        apu.write_u8(NR52, 0x80);
+        apu.write_u8(NR50, 0x77);
+        apu.write_u8(NR51, 0x11);
+        // A 50% square wave at 131 kHz, which is inaudible, but aliases badly when sampled
+        // directly.
+        apu.write_u8(NR11, 0x80);
+        apu.write_u8(0xFF12, 0xF0);
+        apu.write_u8(0xFF13, 0xFF);
+        apu.write_u8(NR14, 0x87);
+        for _ in 0..CLOCK_RATE / 8 {
+            apu.tick(4);
+        }
+        // Once the high-pass filter has taken out the DC offset, there's next to nothing left.
+        let samples = apu.take_samples();
+        let loudest = samples[samples.len() - 1000..]
+            .iter()
+            .map(|sample| sample[0].abs())
+            .max()
+            .unwrap();
+        assert!(loudest < 16, "{}", loudest);
+    }
+}
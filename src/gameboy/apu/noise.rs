@@ -0,0 +1,187 @@
+use super::{Envelope, Length};
+
+/// The T-cycles between LFSR shifts for each divisor code of NR43, before the clock shift.
+const DIVISORS: [u32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+/// The noise channel, which puts out the lowest bit of a linear feedback shift register.
+#[derive(Debug)]
+pub(super) struct Noise {
+    enabled: bool,
+    length: Length,
+    envelope: Envelope,
+    /// NR43, which selects the clock shift, the width of the LFSR and the divisor.
+    register: u8,
+    /// T-cycles left until the next shift.
+    timer: u32,
+    lfsr: u16,
+}
+
+impl Noise {
+    pub(super) fn new() -> Noise {
+        let mut noise = Noise {
+            enabled: false,
+            length: Length::new(64),
+            envelope: Envelope::default(),
+            register: 0,
+            timer: 0,
+            lfsr: 0x7FFF,
+        };
+        noise.timer = noise.period();
+        noise
+    }
+
+    pub(super) fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn period(&self) -> u32 {
+        DIVISORS[usize::from(self.register & 0x07)] << (self.register >> 4)
+    }
+
+    pub(super) fn tick(&mut self, cycles: u8) {
+        // With a clock shift of 14 or 15, the LFSR isn't clocked at all.
+        if !self.enabled || self.register >> 4 >= 14 {
+            return;
+        }
+        let mut cycles = u32::from(cycles);
+        while cycles >= self.timer {
+            cycles -= self.timer;
+            self.timer = self.period();
+            self.shift();
+        }
+        self.timer -= cycles;
+    }
+
+    /// Shifts the LFSR right, feeding the XOR of its two lowest bits back into bit 14, and into
+    /// bit 6 as well in 7 bit mode.
+    fn shift(&mut self) {
+        let feedback = (self.lfsr ^ self.lfsr >> 1) & 1;
+        self.lfsr = self.lfsr >> 1 | feedback << 14;
+        if self.register & 0x08 != 0 {
+            self.lfsr = self.lfsr & !0x40 | feedback << 6;
+        }
+    }
+
+    /// The channel's digital output from 0 to 15, or `None` while its DAC is off.
+    pub(super) fn output(&self) -> Option<u8> {
+        if !self.envelope.dac_enabled() {
+            return None;
+        }
+        Some(if self.enabled && self.lfsr & 1 == 0 {
+            self.envelope.volume
+        } else {
+            0
+        })
+    }
+
+    pub(super) fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub(super) fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.timer = self.period();
+        self.envelope.trigger();
+        self.lfsr = 0x7FFF;
+    }
+
+    /// Reads one of NR40-NR44, without the bits that always read as set.
+    pub(super) fn read(&self, register: u16) -> u8 {
+        match register {
+            2 => self.envelope.register,
+            3 => self.register,
+            4 => (self.length.enabled as u8) << 6,
+            _ => 0,
+        }
+    }
+
+    pub(super) fn write(&mut self, register: u16, value: u8, extra_clock: bool) {
+        match register {
+            1 => self.write_length(value),
+            2 => {
+                self.envelope.write(value);
+                if !self.envelope.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => self.register = value,
+            4 => {
+                if self.length.write_control(value, extra_clock) {
+                    self.enabled = false;
+                }
+                if value & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub(super) fn write_length(&mut self, value: u8) {
+        self.length.load(value & 0x3F);
+    }
+
+    pub(super) fn power_off(&mut self) {
+        let counter = self.length.counter;
+        *self = Noise::new();
+        self.length.counter = counter;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The number of shifts until the LFSR is back where it started.
+    fn lfsr_period(register: u8) -> usize {
+        let mut noise = Noise::new();
+        noise.write(3, register, false);
+        let start = noise.lfsr;
+        (1..)
+            .find(|_| {
+                noise.shift();
+                noise.lfsr == start
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_lfsr_periods() {
+        assert_eq!(0x7FFF, lfsr_period(0x00));
+        // 7 bit mode ends up in a loop of 127 states, which doesn't include the initial one.
+        let mut noise = Noise::new();
+        noise.write(3, 0x08, false);
+        for _ in 0..0x100 {
+            noise.shift();
+        }
+        let start = noise.lfsr & 0x7F;
+        let period = (1..)
+            .find(|_| {
+                noise.shift();
+                noise.lfsr & 0x7F == start
+            })
+            .unwrap();
+        assert_eq!(127, period);
+    }
+
+    #[test]
+    fn test_output() {
+        let mut noise = Noise::new();
+        noise.write(2, 0xA0, false);
+        noise.write(4, 0x80, false);
+        // The LFSR starts out all ones, which is output inverted.
+        assert_eq!(Some(0), noise.output());
+        // With divisor code 0 and no shift, it shifts every 8 T-cycles, shifting in zeros first.
+        noise.tick(8);
+        noise.tick(8);
+        assert_eq!(0x1FFF, noise.lfsr);
+        noise.tick(8 * 13);
+        assert_eq!(Some(0x0A), noise.output());
+    }
+}
@@ -0,0 +1,193 @@
+use std::f64::consts::PI;
+
+use super::CLOCK_RATE;
+
+/// The length of the band-limited steps, in output samples.
+const TAPS: usize = 16;
+/// The number of positions between two output samples that steps are precomputed for.
+const PHASES: usize = 64;
+/// The cutoff of the low-pass filter, relative to the Nyquist frequency of the output, which
+/// leaves some room for the transition band.
+const CUTOFF: f64 = 0.9;
+/// How much charge the output capacitors keep per T-cycle.
+const CAPACITOR_CHARGE: f64 = 0.999_958;
+
+/// The capacitors that the Game Boy's outputs go through, which take out the DC offset of the
+/// DACs.
+#[derive(Debug)]
+struct HighPass {
+    /// How much charge is kept per output sample.
+    factor: f32,
+    charge: [f32; 2],
+}
+
+impl HighPass {
+    fn filter(&mut self, input: [f32; 2]) -> [f32; 2] {
+        let mut output = [0.0; 2];
+        for side in 0..2 {
+            output[side] = input[side] - self.charge[side];
+            self.charge[side] = input[side] - output[side] * self.factor;
+        }
+        output
+    }
+}
+
+/// Turns the APU's output, which changes in steps at T-cycle resolution, into samples at the
+/// host's rate.
+///
+/// Sampling the output directly would alias everything above the host's Nyquist frequency back
+/// into the audible range. Instead, each step is replaced by a band-limited one: its change is
+/// spread over the surrounding samples by a windowed sinc, and the samples are the running sum
+/// of those changes.
+#[derive(Debug)]
+pub(super) struct Resampler {
+    /// Output samples per T-cycle.
+    ratio: f64,
+    /// Where the current T-cycle falls, in output samples from the first unfinished one.
+    position: f64,
+    /// The changes to the output at each unfinished sample, as left and right.
+    deltas: Vec<[f32; 2]>,
+    /// The windowed sinc for each phase, i.e. each fraction of a sample that a step can fall on.
+    kernels: Vec<[f32; TAPS]>,
+    /// The input as of the last step.
+    input: [i32; 2],
+    /// The sum of the changes up to the last finished sample.
+    output: [f32; 2],
+    high_pass: HighPass,
+    samples: Vec<[i16; 2]>,
+}
+
+impl Resampler {
+    pub(super) fn new(sample_rate: u32) -> Resampler {
+        let ratio = f64::from(sample_rate) / f64::from(CLOCK_RATE);
+        Resampler {
+            ratio,
+            position: 0.0,
+            deltas: Vec::with_capacity(2 * TAPS),
+            kernels: kernels(),
+            input: [0; 2],
+            output: [0.0; 2],
+            high_pass: HighPass {
+                factor: CAPACITOR_CHARGE.powf(1.0 / ratio) as f32,
+                charge: [0.0; 2],
+            },
+            samples: Vec::new(),
+        }
+    }
+
+    /// Advances by the given number of T-cycles, during which the input is at `level`.
+    pub(super) fn push(&mut self, cycles: u32, level: [i32; 2]) {
+        let index = self.position as usize;
+        if level != self.input {
+            // Steps falling between two phases get a mix of their kernels.
+            let phase = (self.position - index as f64) * PHASES as f64;
+            let (before, after) = (
+                &self.kernels[phase as usize],
+                &self.kernels[phase as usize + 1],
+            );
+            let mix = phase.fract() as f32;
+            if self.deltas.len() < index + TAPS {
+                self.deltas.resize(index + TAPS, [0.0; 2]);
+            }
+            let changes = [
+                (level[0] - self.input[0]) as f32,
+                (level[1] - self.input[1]) as f32,
+            ];
+            for (tap, delta) in self.deltas[index..index + TAPS].iter_mut().enumerate() {
+                let weight = before[tap] + (after[tap] - before[tap]) * mix;
+                delta[0] += changes[0] * weight;
+                delta[1] += changes[1] * weight;
+            }
+            self.input = level;
+        }
+        self.position += f64::from(cycles) * self.ratio;
+
+        // Steps from here on can only change the samples from the current one on.
+        let finished = self.position as usize;
+        if finished == 0 {
+            return;
+        }
+        if self.deltas.len() < finished {
+            self.deltas.resize(finished, [0.0; 2]);
+        }
+        for delta in self.deltas.drain(..finished) {
+            self.output[0] += delta[0];
+            self.output[1] += delta[1];
+            let [left, right] = self.high_pass.filter(self.output);
+            self.samples.push([clamp(left), clamp(right)]);
+        }
+        self.position -= finished as f64;
+    }
+
+    pub(super) fn take_samples(&mut self) -> Vec<[i16; 2]> {
+        std::mem::take(&mut self.samples)
+    }
+}
+
+fn clamp(sample: f32) -> i16 {
+    sample
+        .round()
+        .max(f32::from(i16::MIN))
+        .min(f32::from(i16::MAX)) as i16
+}
+
+/// Computes the Blackman windowed sinc for each phase, normalized so that a step of 1 adds up to
+/// exactly 1.
+fn kernels() -> Vec<[f32; TAPS]> {
+    let half = (TAPS / 2) as f64;
+    (0..=PHASES)
+        .map(|phase| {
+            let fraction = phase as f64 / PHASES as f64;
+            let mut kernel = [0.0; TAPS];
+            for (tap, weight) in kernel.iter_mut().enumerate() {
+                // The distance of the tap from the step, which lies between taps 7 and 8.
+                let t = tap as f64 - (half - 1.0) - fraction;
+                let x = PI * CUTOFF * t;
+                let sinc = if x == 0.0 { 1.0 } else { x.sin() / x };
+                let window =
+                    0.42 + 0.5 * (PI * t / half).cos() + 0.08 * (2.0 * PI * t / half).cos();
+                *weight = sinc * window;
+            }
+            let sum: f64 = kernel.iter().sum();
+            let mut normalized = [0.0; TAPS];
+            for (normalized, weight) in normalized.iter_mut().zip(&kernel) {
+                *normalized = (weight / sum) as f32;
+            }
+            normalized
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_count() {
+        let mut resampler = Resampler::new(48_000);
+        for _ in 0..CLOCK_RATE / 4 {
+            resampler.push(4, [0, 0]);
+        }
+        let samples = resampler.take_samples();
+        assert!((47_999..=48_000).contains(&samples.len()));
+    }
+
+    #[test]
+    fn test_step() {
+        let mut resampler = Resampler::new(44_100);
+        resampler.push(1000, [0, 0]);
+        for _ in 0..1000 {
+            resampler.push(4, [10_000, -10_000]);
+        }
+        let samples = resampler.take_samples();
+        // The step rises smoothly, overshooting a bit as band-limited steps do, and settles. Only
+        // the high-pass filter slowly pulls it back towards 0.
+        let start = samples.iter().position(|sample| sample[0] != 0).unwrap();
+        assert!(samples[start][0] < 500);
+        let peak = samples.iter().map(|sample| sample[0]).max().unwrap();
+        assert!((10_000..12_000).contains(&peak));
+        let settled = samples[start + TAPS];
+        assert!((9_500..10_000).contains(&settled[0]));
+        assert_eq!(-settled[0], settled[1]);
+    }
+}
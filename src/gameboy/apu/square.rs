@@ -0,0 +1,287 @@
+use super::{Envelope, Length};
+
+/// The waveforms of the four duty cycles, 12.5%, 25%, 50% and 75%, one bit per step.
+const DUTY_PATTERNS: [u8; 4] = [0b0000_0001, 0b1000_0001, 0b1000_0111, 0b0111_1110];
+
+/// The frequency sweep of channel 1, controlled through NR10.
+#[derive(Debug, Default)]
+struct Sweep {
+    register: u8,
+    enabled: bool,
+    /// The frequency that the sweep calculates from, copied from the channel on trigger.
+    shadow: u16,
+    /// Sweep clocks left until the next calculation.
+    timer: u8,
+    /// Whether a calculation has subtracted since the trigger. Switching to addition after that
+    /// turns the channel off.
+    negated: bool,
+}
+
+impl Sweep {
+    fn period(&self) -> u8 {
+        self.register >> 4 & 0x07
+    }
+
+    fn shift(&self) -> u8 {
+        self.register & 0x07
+    }
+
+    /// The timer counts down from 8 rather than 0.
+    fn reload_timer(&mut self) {
+        self.timer = if self.period() == 0 { 8 } else { self.period() };
+    }
+
+    /// The next frequency, or `None` if it overflows, which turns the channel off.
+    fn calculate(&mut self) -> Option<u16> {
+        let delta = self.shadow >> self.shift();
+        let frequency = if self.register & 0x08 != 0 {
+            self.negated = true;
+            self.shadow - delta
+        } else {
+            self.shadow + delta
+        };
+        if frequency > 0x7FF {
+            None
+        } else {
+            Some(frequency)
+        }
+    }
+}
+
+/// One of the two square wave channels, of which only channel 1 has a sweep.
+#[derive(Debug)]
+pub(super) struct Square {
+    enabled: bool,
+    duty: u8,
+    length: Length,
+    envelope: Envelope,
+    sweep: Option<Sweep>,
+    frequency: u16,
+    /// T-cycles left until the next step of the duty cycle.
+    timer: u32,
+    position: u8,
+}
+
+impl Square {
+    pub(super) fn new(sweep: bool) -> Square {
+        let mut square = Square {
+            enabled: false,
+            duty: 0,
+            length: Length::new(64),
+            envelope: Envelope::default(),
+            sweep: if sweep { Some(Sweep::default()) } else { None },
+            frequency: 0,
+            timer: 0,
+            position: 0,
+        };
+        square.timer = square.period();
+        square
+    }
+
+    pub(super) fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The T-cycles per step of the duty cycle, of which there are 8 per period of the wave.
+    fn period(&self) -> u32 {
+        (2048 - u32::from(self.frequency)) * 4
+    }
+
+    pub(super) fn tick(&mut self, cycles: u8) {
+        let mut cycles = u32::from(cycles);
+        while cycles >= self.timer {
+            cycles -= self.timer;
+            self.timer = self.period();
+            self.position = (self.position + 1) % 8;
+        }
+        self.timer -= cycles;
+    }
+
+    /// The channel's digital output from 0 to 15, or `None` while its DAC is off.
+    pub(super) fn output(&self) -> Option<u8> {
+        if !self.envelope.dac_enabled() {
+            return None;
+        }
+        let high = DUTY_PATTERNS[usize::from(self.duty)] >> (7 - self.position) & 1 != 0;
+        Some(if self.enabled && high {
+            self.envelope.volume
+        } else {
+            0
+        })
+    }
+
+    pub(super) fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub(super) fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    /// Clocked by the frame sequencer at 128 Hz.
+    pub(super) fn clock_sweep(&mut self) {
+        let sweep = match &mut self.sweep {
+            Some(sweep) => sweep,
+            None => return,
+        };
+        sweep.timer = sweep.timer.saturating_sub(1);
+        if sweep.timer > 0 {
+            return;
+        }
+        sweep.reload_timer();
+        if !sweep.enabled || sweep.period() == 0 {
+            return;
+        }
+        match sweep.calculate() {
+            Some(frequency) if sweep.shift() != 0 => {
+                sweep.shadow = frequency;
+                self.frequency = frequency;
+                // The new frequency is checked for overflow once more, but not used.
+                if sweep.calculate().is_none() {
+                    self.enabled = false;
+                }
+            }
+            Some(_) => {}
+            None => self.enabled = false,
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.timer = self.period();
+        self.envelope.trigger();
+        if let Some(sweep) = &mut self.sweep {
+            sweep.shadow = self.frequency;
+            sweep.reload_timer();
+            sweep.enabled = sweep.period() != 0 || sweep.shift() != 0;
+            sweep.negated = false;
+            if sweep.shift() != 0 && sweep.calculate().is_none() {
+                self.enabled = false;
+            }
+        }
+    }
+
+    /// Reads one of NRx0-NRx4, without the bits that always read as set.
+    pub(super) fn read(&self, register: u16) -> u8 {
+        match register {
+            0 => self.sweep.as_ref().map_or(0, |sweep| sweep.register),
+            1 => self.duty << 6,
+            2 => self.envelope.register,
+            4 => (self.length.enabled as u8) << 6,
+            _ => 0,
+        }
+    }
+
+    pub(super) fn write(&mut self, register: u16, value: u8, extra_clock: bool) {
+        match register {
+            0 => {
+                if let Some(sweep) = &mut self.sweep {
+                    let negate = sweep.register & 0x08 != 0;
+                    sweep.register = value;
+                    if negate && value & 0x08 == 0 && sweep.negated {
+                        self.enabled = false;
+                    }
+                }
+            }
+            1 => {
+                self.duty = value >> 6;
+                self.write_length(value);
+            }
+            2 => {
+                self.envelope.write(value);
+                if !self.envelope.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => self.frequency = self.frequency & 0x700 | u16::from(value),
+            _ => {
+                self.frequency = self.frequency & 0xFF | u16::from(value & 0x07) << 8;
+                if self.length.write_control(value, extra_clock) {
+                    self.enabled = false;
+                }
+                if value & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+        }
+    }
+
+    /// Writes the length part of NRx1, which is all that can be written while the APU is off.
+    pub(super) fn write_length(&mut self, value: u8) {
+        self.length.load(value & 0x3F);
+    }
+
+    pub(super) fn power_off(&mut self) {
+        let counter = self.length.counter;
+        *self = Square::new(self.sweep.is_some());
+        self.length.counter = counter;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triggered(sweep: u8, frequency: u16) -> Square {
+        let mut square = Square::new(true);
+        square.write(0, sweep, false);
+        square.write(2, 0xF0, false);
+        square.write(3, frequency as u8, false);
+        square.write(4, 0x80 | (frequency >> 8) as u8, false);
+        square
+    }
+
+    #[test]
+    fn test_duty() {
+        let mut square = triggered(0, 0x7FF);
+        square.write(1, 0x80, false);
+        // At the highest frequency, each step of the 50% duty cycle takes 4 T-cycles.
+        let mut outputs = Vec::new();
+        for _ in 0..8 {
+            square.tick(4);
+            outputs.push(square.output().unwrap());
+        }
+        assert_eq!(vec![0, 0, 0, 0, 15, 15, 15, 15], outputs);
+    }
+
+    #[test]
+    fn test_sweep() {
+        // A period of 1 with a shift of 1 adds half the frequency on every sweep clock.
+        let mut square = triggered(0x11, 0x100);
+        square.clock_sweep();
+        assert_eq!(0x180, square.frequency);
+        square.clock_sweep();
+        assert_eq!(0x240, square.frequency);
+        square.clock_sweep();
+        assert_eq!(0x360, square.frequency);
+        square.clock_sweep();
+        assert_eq!(0x510, square.frequency);
+        assert!(square.enabled());
+        // 0x798 is used, but adding another 0x3CC overflows on the second check.
+        square.clock_sweep();
+        assert_eq!(0x798, square.frequency);
+        assert!(!square.enabled());
+    }
+
+    #[test]
+    fn test_sweep_negate_quirk() {
+        let mut square = triggered(0x19, 0x400);
+        square.clock_sweep();
+        assert_eq!(0x200, square.frequency);
+        assert!(square.enabled());
+        // Clearing the negate bit after a subtraction turns the channel off.
+        square.write(0, 0x11, false);
+        assert!(!square.enabled());
+    }
+
+    #[test]
+    fn test_dac_off() {
+        let mut square = triggered(0, 0);
+        assert_eq!(Some(0), square.output());
+        square.write(2, 0x00, false);
+        assert!(!square.enabled());
+        assert_eq!(None, square.output());
+    }
+}
@@ -0,0 +1,203 @@
+use super::Length;
+
+/// The right shifts applied to the samples for each volume code of NR32: mute, 100%, 50% and 25%.
+const VOLUME_SHIFTS: [u8; 4] = [4, 0, 1, 2];
+
+/// The wave channel, which plays the 32 4-bit samples stored in the wave RAM at 0xFF30-0xFF3F.
+#[derive(Debug)]
+pub(super) struct Wave {
+    enabled: bool,
+    dac_enabled: bool,
+    length: Length,
+    volume: u8,
+    frequency: u16,
+    /// T-cycles left until the next sample is read.
+    timer: u32,
+    /// The index of the sample being played, of which the high nibble of each byte comes first.
+    position: u8,
+    sample: u8,
+    ram: [u8; 16],
+}
+
+impl Wave {
+    pub(super) fn new() -> Wave {
+        let mut wave = Wave {
+            enabled: false,
+            dac_enabled: false,
+            length: Length::new(256),
+            volume: 0,
+            frequency: 0,
+            timer: 0,
+            position: 0,
+            sample: 0,
+            ram: [0; 16],
+        };
+        wave.timer = wave.period();
+        wave
+    }
+
+    pub(super) fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The T-cycles per sample, of which there are 32 per period of the wave.
+    fn period(&self) -> u32 {
+        (2048 - u32::from(self.frequency)) * 2
+    }
+
+    pub(super) fn tick(&mut self, cycles: u8) {
+        if !self.enabled {
+            return;
+        }
+        let mut cycles = u32::from(cycles);
+        while cycles >= self.timer {
+            cycles -= self.timer;
+            self.timer = self.period();
+            self.position = (self.position + 1) % 32;
+            let byte = self.ram[usize::from(self.position / 2)];
+            self.sample = if self.position & 1 == 0 {
+                byte >> 4
+            } else {
+                byte & 0x0F
+            };
+        }
+        self.timer -= cycles;
+    }
+
+    /// The channel's digital output from 0 to 15, or `None` while its DAC is off.
+    pub(super) fn output(&self) -> Option<u8> {
+        if !self.dac_enabled {
+            return None;
+        }
+        Some(if self.enabled {
+            self.sample >> VOLUME_SHIFTS[usize::from(self.volume)]
+        } else {
+            0
+        })
+    }
+
+    pub(super) fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    /// Starts over from the first sample, though the last sample read keeps playing until the
+    /// next one is.
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled;
+        self.timer = self.period();
+        self.position = 0;
+    }
+
+    /// Reads one of NR30-NR34, without the bits that always read as set.
+    pub(super) fn read(&self, register: u16) -> u8 {
+        match register {
+            0 => (self.dac_enabled as u8) << 7,
+            2 => self.volume << 5,
+            4 => (self.length.enabled as u8) << 6,
+            _ => 0,
+        }
+    }
+
+    pub(super) fn write(&mut self, register: u16, value: u8, extra_clock: bool) {
+        match register {
+            0 => {
+                self.dac_enabled = value & 0x80 != 0;
+                if !self.dac_enabled {
+                    self.enabled = false;
+                }
+            }
+            1 => self.write_length(value),
+            2 => self.volume = value >> 5 & 0x03,
+            3 => self.frequency = self.frequency & 0x700 | u16::from(value),
+            _ => {
+                self.frequency = self.frequency & 0xFF | u16::from(value & 0x07) << 8;
+                if self.length.write_control(value, extra_clock) {
+                    self.enabled = false;
+                }
+                if value & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+        }
+    }
+
+    pub(super) fn write_length(&mut self, value: u8) {
+        self.length.load(value);
+    }
+
+    /// While the channel plays, the CPU can only get at the byte that the channel is reading,
+    /// whatever the address. This is how the CGB behaves, the DMG is even more restrictive.
+    fn ram_index(&self, index: u16) -> usize {
+        if self.enabled {
+            usize::from(self.position / 2)
+        } else {
+            usize::from(index)
+        }
+    }
+
+    /// The wave RAM as it is, regardless of whether the channel is playing.
+    pub(super) fn ram(&self) -> &[u8; 16] {
+        &self.ram
+    }
+
+    pub(super) fn read_ram(&self, index: u16) -> u8 {
+        self.ram[self.ram_index(index)]
+    }
+
+    pub(super) fn write_ram(&mut self, index: u16, value: u8) {
+        self.ram[self.ram_index(index)] = value;
+    }
+
+    /// Clears the registers, but leaves the wave RAM and the length counter alone.
+    pub(super) fn power_off(&mut self) {
+        let ram = self.ram;
+        let counter = self.length.counter;
+        *self = Wave::new();
+        self.ram = ram;
+        self.length.counter = counter;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_samples() {
+        let mut wave = Wave::new();
+        for i in 0..16 {
+            wave.write_ram(i, 0x10 * i as u8 + 0x0F - i as u8);
+        }
+        wave.write(0, 0x80, false);
+        wave.write(2, 0x20, false);
+        wave.write(3, 0xFF, false);
+        wave.write(4, 0x87, false);
+        // At the highest frequency, a sample takes 2 T-cycles.
+        let mut outputs = Vec::new();
+        for _ in 0..4 {
+            wave.tick(2);
+            outputs.push(wave.output().unwrap());
+        }
+        assert_eq!(vec![0x0F, 0x01, 0x0E, 0x02], outputs);
+
+        // At 50%, the samples are shifted right by one.
+        wave.write(2, 0x40, false);
+        wave.tick(2);
+        assert_eq!(Some(0x0D / 2), wave.output());
+    }
+
+    #[test]
+    fn test_ram_while_playing() {
+        let mut wave = Wave::new();
+        wave.write_ram(0x05, 0x42);
+        wave.write(0, 0x80, false);
+        wave.write(3, 0xFF, false);
+        wave.write(4, 0x87, false);
+        wave.tick(2 * 10);
+        assert_eq!(0x42, wave.read_ram(0x00));
+        wave.write(0, 0x00, false);
+        assert_eq!(0x00, wave.read_ram(0x00));
+    }
+}
@@ -0,0 +1,123 @@
+use std::fmt;
+
+use failure::Fail;
+
+/// The Game Boy models, which differ in their boot ROMs and the state those leave behind.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Model {
+    #[default]
+    Dmg,
+    /// The Game Boy Pocket.
+    Mgb,
+    /// The Super Game Boy.
+    Sgb,
+    /// The Game Boy Color.
+    Cgb,
+}
+
+impl Model {
+    /// The size of the model's boot ROM in bytes.
+    pub fn boot_rom_size(self) -> usize {
+        match self {
+            Model::Cgb => 0x900,
+            _ => 0x100,
+        }
+    }
+
+    /// AF, BC, DE and HL as the model's boot ROM leaves them, for a cartridge with the given
+    /// header checksum.
+    pub(crate) fn post_boot_registers(self, header_checksum: u8) -> [u16; 4] {
+        // The DMG boot ROM leaves the flags of its checksum comparison behind.
+        let flags = if header_checksum == 0 { 0x80 } else { 0xB0 };
+        match self {
+            Model::Dmg => [0x0100 | flags, 0x0013, 0x00D8, 0x014D],
+            Model::Mgb => [0xFF00 | flags, 0x0013, 0x00D8, 0x014D],
+            Model::Sgb => [0x0100, 0x0014, 0x0000, 0xC060],
+            Model::Cgb => [0x1180, 0x0000, 0xFF56, 0x000D],
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BootError {
+    WrongSize {
+        model: Model,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl fmt::Display for BootError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BootError::WrongSize {
+                model,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "boot ROM is {} bytes, but the {:?} boot ROM is {}",
+                actual, model, expected
+            ),
+        }
+    }
+}
+
+impl Fail for BootError {}
+
+/// A boot ROM, which is overlaid on the cartridge ROM from 0x0000 until a write to 0xFF50.
+#[derive(Debug)]
+pub(crate) struct BootRom {
+    data: Box<[u8]>,
+}
+
+impl BootRom {
+    pub(crate) fn new(model: Model, data: Vec<u8>) -> Result<BootRom, BootError> {
+        if data.len() != model.boot_rom_size() {
+            return Err(BootError::WrongSize {
+                model,
+                expected: model.boot_rom_size(),
+                actual: data.len(),
+            });
+        }
+        Ok(BootRom {
+            data: data.into_boxed_slice(),
+        })
+    }
+
+    /// Reads the boot ROM, if it covers the address. The CGB boot ROM is split in two around the
+    /// cartridge header at 0x0100-0x01FF, which it reads while running.
+    pub(crate) fn read(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x0100..=0x01FF => None,
+            _ => self.data.get(usize::from(addr)).cloned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_boot_rom() {
+        assert_eq!(
+            BootError::WrongSize {
+                model: Model::Cgb,
+                expected: 0x900,
+                actual: 0x100,
+            },
+            BootRom::new(Model::Cgb, vec![0; 0x100]).unwrap_err()
+        );
+
+        let mut data = vec![0; 0x900];
+        data[0x00FF] = 0x12;
+        data[0x0100] = 0x34;
+        data[0x0200] = 0x56;
+        let boot_rom = BootRom::new(Model::Cgb, data).unwrap();
+        assert_eq!(Some(0x12), boot_rom.read(0x00FF));
+        assert_eq!(None, boot_rom.read(0x0100));
+        assert_eq!(Some(0x56), boot_rom.read(0x0200));
+        assert_eq!(None, boot_rom.read(0x0900));
+    }
+}
@@ -161,6 +161,7 @@ fn test_toggle() {
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_as_bool() {
     let mut rf: RegisterF = Default::default();
     assert_eq!(false, rf[Z].into());
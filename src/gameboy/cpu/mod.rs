@@ -1,7 +1,27 @@
 pub(crate) mod flag;
 pub(crate) mod register;
 
+/// The CPU's power state, changed by HALT and STOP.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub(crate) enum Mode {
+    #[default]
+    Running,
+    /// Halted until an enabled interrupt is requested.
+    Halted,
+    /// Stopped until a button is pressed.
+    Stopped,
+}
+
 #[derive(Debug, Default)]
 pub struct CPU {
     pub(crate) register: register::Register,
+    /// The interrupt master enable flag. Interrupts are only serviced while it's set.
+    pub(crate) ime: bool,
+    /// EI doesn't take effect until after the following instruction; this counts down the
+    /// instructions left until IME is set.
+    pub(crate) ime_delay: u8,
+    pub(crate) mode: Mode,
+    /// Set when HALT is executed with IME unset and an interrupt already pending, in which case
+    /// the CPU fails to increment PC after fetching the next opcode.
+    pub(crate) halt_bug: bool,
 }
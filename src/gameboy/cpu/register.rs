@@ -155,6 +155,7 @@ impl Register {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::field_reassign_with_default)]
 mod tests {
     use super::super::flag;
     use super::*;
@@ -0,0 +1,104 @@
+/// The number of bytes copied into OAM, one per M-cycle.
+const TRANSFER_SIZE: u8 = 0xA0;
+/// The M-cycles between the write to 0xFF46 and the first byte being copied.
+const START_DELAY: u8 = 1;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Transfer {
+    source: u16,
+    /// The index in OAM of the byte to copy next.
+    index: u8,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Start {
+    source: u16,
+    delay: u8,
+}
+
+/// The OAM DMA controller, which copies 160 bytes from `XX00` to OAM after `XX` is written to
+/// 0xFF46.
+///
+/// Writing to it while a transfer is running restarts it, though the old transfer keeps going
+/// until the new one has started.
+#[derive(Debug, Default)]
+pub(crate) struct Dma {
+    register: u8,
+    transfer: Option<Transfer>,
+    start: Option<Start>,
+}
+
+impl Dma {
+    pub(crate) fn register(&self) -> u8 {
+        self.register
+    }
+
+    pub(crate) fn write_register(&mut self, value: u8) {
+        self.register = value;
+        let mut source = u16::from(value) << 8;
+        // There's nothing past the echo RAM on the bus, so 0xE000-0xFFFF all map to work RAM.
+        if source >= 0xE000 {
+            source -= 0x2000;
+        }
+        self.start = Some(Start {
+            source,
+            delay: START_DELAY,
+        });
+    }
+
+    /// The address the transfer is reading from, if one is running.
+    pub(crate) fn source(&self) -> Option<u16> {
+        self.transfer
+            .map(|transfer| transfer.source + u16::from(transfer.index))
+    }
+
+    /// Advances by an M-cycle, returning the source address and OAM index of the byte to copy,
+    /// if any.
+    pub(crate) fn tick(&mut self) -> Option<(u16, u8)> {
+        let copy = self
+            .transfer
+            .map(|transfer| (transfer.source + u16::from(transfer.index), transfer.index));
+        if let Some(transfer) = &mut self.transfer {
+            transfer.index += 1;
+            if transfer.index == TRANSFER_SIZE {
+                self.transfer = None;
+            }
+        }
+        if let Some(start) = &mut self.start {
+            start.delay -= 1;
+            if start.delay == 0 {
+                self.transfer = Some(Transfer {
+                    source: start.source,
+                    index: 0,
+                });
+                self.start = None;
+            }
+        }
+        copy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restart() {
+        let mut dma = Dma::default();
+        dma.write_register(0xC0);
+        assert_eq!(None, dma.tick());
+        assert_eq!(Some((0xC000, 0x00)), dma.tick());
+        assert_eq!(Some((0xC001, 0x01)), dma.tick());
+
+        // The old transfer goes on until the new one starts.
+        dma.write_register(0xFE);
+        assert_eq!(Some(0xC002), dma.source());
+        assert_eq!(Some((0xC002, 0x02)), dma.tick());
+        assert_eq!(Some((0xDE00, 0x00)), dma.tick());
+        for index in 1..TRANSFER_SIZE {
+            assert_eq!(Some((0xDE00 + u16::from(index), index)), dma.tick());
+        }
+        assert_eq!(None, dma.source());
+        assert_eq!(0xFE, dma.register());
+    }
+}
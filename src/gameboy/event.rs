@@ -0,0 +1,31 @@
+use std::{collections::VecDeque, mem};
+
+/// Something that happened in the emulated system which a frontend might want to act on, but
+/// which isn't visible in the picture or the sound.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Event {
+    /// The cartridge's rumble motor was switched on or off.
+    Rumble(bool),
+    /// The cartridge's infrared LED was switched on or off.
+    InfraredLed(bool),
+}
+
+/// The events that haven't been handled yet, oldest first. Only the latest event of each kind is
+/// kept, so that the queue stays short even if nobody polls it.
+#[derive(Debug, Default)]
+pub(crate) struct EventQueue {
+    events: VecDeque<Event>,
+}
+
+impl EventQueue {
+    /// Queues the event, replacing any older event of the same kind that's still queued.
+    pub(crate) fn push(&mut self, event: Event) {
+        self.events
+            .retain(|queued| mem::discriminant(queued) != mem::discriminant(&event));
+        self.events.push_back(event);
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+}
@@ -0,0 +1,335 @@
+use std::{fmt, fs, path::Path};
+
+use failure::{Error, Fail, ResultExt};
+
+use super::mem::Bus;
+use super::rom::{ascii, ROM_BANK_SIZE};
+
+const MAGIC: &[u8; 3] = b"GBS";
+const VERSION: usize = 0x03;
+const SONGS: usize = 0x04;
+const FIRST_SONG: usize = 0x05;
+const LOAD_ADDRESS: usize = 0x06;
+const INIT_ADDRESS: usize = 0x08;
+const PLAY_ADDRESS: usize = 0x0A;
+const STACK_POINTER: usize = 0x0C;
+const TIMER_MODULO: usize = 0x0E;
+const TIMER_CONTROL: usize = 0x0F;
+const TITLE_START: usize = 0x10;
+const AUTHOR_START: usize = 0x30;
+const COPYRIGHT_START: usize = 0x50;
+const HEADER_END: usize = 0x70;
+
+/// The lowest load address, which leaves the space below it for the RST and interrupt vectors
+/// and the driver.
+const MIN_LOAD_ADDRESS: u16 = 0x0400;
+/// Where the driver starts, which calls init with the song number in A and then halts, waiting
+/// for the interrupts that call play.
+pub(crate) const DRIVER: u16 = 0x0100;
+
+const CALL: u8 = 0xCD;
+const JP: u8 = 0xC3;
+const RETI: u8 = 0xD9;
+const EI: u8 = 0xFB;
+const HALT: u8 = 0x76;
+const JR: u8 = 0x18;
+
+#[derive(Debug, PartialEq)]
+pub enum GbsError {
+    MissingHeader(usize),
+    BadMagic,
+    UnsupportedVersion(u8),
+    NoSongs,
+    InvalidLoadAddress(u16),
+    /// The song asked for, counting from 1, isn't one of the file's.
+    NoSuchSong {
+        song: u8,
+        songs: u8,
+    },
+}
+
+impl fmt::Display for GbsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::GbsError::*;
+        match self {
+            MissingHeader(len) => write!(
+                f,
+                "the file is too short to contain a GBS header ({} bytes)",
+                len
+            ),
+            BadMagic => write!(f, "not a GBS file"),
+            UnsupportedVersion(version) => write!(f, "unsupported GBS version {}", version),
+            NoSongs => write!(f, "the file contains no songs"),
+            InvalidLoadAddress(addr) => write!(f, "invalid load address 0x{:04X}", addr),
+            NoSuchSong { song, songs } => {
+                write!(f, "there's no song {}, the file has {}", song, songs)
+            }
+        }
+    }
+}
+
+impl Fail for GbsError {}
+
+/// The header of a GBS file, which describes how to play the music driver it contains.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GbsHeader {
+    pub songs: u8,
+    /// The song to play by default, counting from 1.
+    pub first_song: u8,
+    /// Where the data following the header is loaded to.
+    pub load_address: u16,
+    /// The routine that starts a song, which gets the song number counting from 0 in A.
+    pub init_address: u16,
+    /// The routine that advances the song, called on every VBlank or timer interrupt.
+    pub play_address: u16,
+    pub stack_pointer: u16,
+    /// TMA, which is used along with TAC when play is called on the timer interrupt.
+    pub timer_modulo: u8,
+    /// TAC, where bit 2 selects the timer interrupt rather than VBlank for calling play, and bit 7
+    /// selects the CGB's double speed mode.
+    pub timer_control: u8,
+    pub title: String,
+    pub author: String,
+    pub copyright: String,
+}
+
+impl GbsHeader {
+    pub fn parse(file: &[u8]) -> Result<GbsHeader, GbsError> {
+        if file.len() < HEADER_END {
+            return Err(GbsError::MissingHeader(file.len()));
+        }
+        if &file[..VERSION] != MAGIC {
+            return Err(GbsError::BadMagic);
+        }
+        if file[VERSION] != 1 {
+            return Err(GbsError::UnsupportedVersion(file[VERSION]));
+        }
+        if file[SONGS] == 0 {
+            return Err(GbsError::NoSongs);
+        }
+        let word = |offset: usize| u16::from(file[offset]) | u16::from(file[offset + 1]) << 8;
+        let load_address = word(LOAD_ADDRESS);
+        if !(MIN_LOAD_ADDRESS..0x8000).contains(&load_address) {
+            return Err(GbsError::InvalidLoadAddress(load_address));
+        }
+        Ok(GbsHeader {
+            songs: file[SONGS],
+            first_song: file[FIRST_SONG],
+            load_address,
+            init_address: word(INIT_ADDRESS),
+            play_address: word(PLAY_ADDRESS),
+            stack_pointer: word(STACK_POINTER),
+            timer_modulo: file[TIMER_MODULO],
+            timer_control: file[TIMER_CONTROL],
+            title: ascii(&file[TITLE_START..AUTHOR_START]),
+            author: ascii(&file[AUTHOR_START..COPYRIGHT_START]),
+            copyright: ascii(&file[COPYRIGHT_START..HEADER_END]),
+        })
+    }
+
+    /// Whether play is called on the timer interrupt, rather than on VBlank.
+    pub fn timer_interrupt(&self) -> bool {
+        self.timer_control & 0x04 != 0
+    }
+
+    pub fn double_speed(&self) -> bool {
+        self.timer_control & 0x80 != 0
+    }
+}
+
+/// A GBS file, i.e. a Game Boy music driver along with its songs, ripped out of a game.
+#[derive(Debug)]
+pub struct GbsFile {
+    pub header: GbsHeader,
+    /// Everything following the header, which is loaded from the load address onwards.
+    data: Box<[u8]>,
+}
+
+impl GbsFile {
+    pub fn from_bytes(mut file: Vec<u8>) -> Result<GbsFile, GbsError> {
+        let header = GbsHeader::parse(&file)?;
+        Ok(GbsFile {
+            header,
+            data: file.split_off(HEADER_END).into_boxed_slice(),
+        })
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<GbsFile, Error> {
+        let path = path.as_ref();
+        let file = fs::read(path).with_context(|_| format!("couldn't read {}", path.display()))?;
+        let gbs = GbsFile::from_bytes(file)
+            .with_context(|_| format!("couldn't load {}", path.display()))?;
+        Ok(gbs)
+    }
+
+    /// Checks that `song`, counting from 1, is one of the file's.
+    pub(crate) fn check_song(&self, song: u8) -> Result<(), GbsError> {
+        if (1..=self.header.songs).contains(&song) {
+            Ok(())
+        } else {
+            Err(GbsError::NoSuchSong {
+                song,
+                songs: self.header.songs,
+            })
+        }
+    }
+}
+
+/// What a GBS file is played from in place of a cartridge. The data is laid out as a ROM with
+/// the first bank fixed and the second one switched through writes to 0x2000-0x3FFF, like most
+/// memory bank controllers do it, and there's 8 kB of RAM. Below the load address, the ROM holds
+/// the vectors and the driver.
+#[derive(Debug)]
+pub(crate) struct GbsRom {
+    rom: Box<[u8]>,
+    bank: u8,
+    ram: Box<[u8]>,
+}
+
+impl GbsRom {
+    pub(crate) fn new(gbs: &GbsFile) -> GbsRom {
+        let header = &gbs.header;
+        let load_address = usize::from(header.load_address);
+        let size = (load_address + gbs.data.len()).max(2 * ROM_BANK_SIZE);
+        let mut rom = vec![0u8; size.div_ceil(ROM_BANK_SIZE) * ROM_BANK_SIZE];
+        rom[load_address..load_address + gbs.data.len()].copy_from_slice(&gbs.data);
+
+        // The RST vectors are relocated to the load address.
+        for vector in (0x00..0x40).step_by(8) {
+            let [lo, hi] = (header.load_address + vector).to_le_bytes();
+            rom[usize::from(vector)..usize::from(vector) + 3].copy_from_slice(&[JP, lo, hi]);
+        }
+        // Only VBlank and the timer are ever enabled, but there's no harm in having the other
+        // interrupts return as well.
+        let [lo, hi] = header.play_address.to_le_bytes();
+        for vector in (0x40..=0x60).step_by(8) {
+            rom[vector] = RETI;
+        }
+        rom[0x40..0x44].copy_from_slice(&[CALL, lo, hi, RETI]);
+        rom[0x50..0x54].copy_from_slice(&[CALL, lo, hi, RETI]);
+
+        let driver = usize::from(DRIVER);
+        let [lo, hi] = header.init_address.to_le_bytes();
+        // CALL init; EI; HALT; JR -3
+        rom[driver..driver + 7].copy_from_slice(&[CALL, lo, hi, EI, HALT, JR, 0xFD]);
+
+        GbsRom {
+            rom: rom.into_boxed_slice(),
+            bank: 1,
+            ram: vec![0u8; 0x2000].into_boxed_slice(),
+        }
+    }
+}
+
+impl Bus for GbsRom {
+    fn read_u8(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => self.rom[usize::from(addr)],
+            0x4000..=0x7FFF => {
+                // Bank 0 can't be selected, it selects bank 1 instead.
+                let banks = self.rom.len() / ROM_BANK_SIZE;
+                let bank = match usize::from(self.bank) % banks {
+                    0 => 1,
+                    bank => bank,
+                };
+                self.rom[bank * ROM_BANK_SIZE + usize::from(addr - 0x4000)]
+            }
+            0xA000..=0xBFFF => self.ram[usize::from(addr - 0xA000)],
+            _ => 0xFF,
+        }
+    }
+
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x2000..=0x3FFF => self.bank = value,
+            0xA000..=0xBFFF => self.ram[usize::from(addr - 0xA000)] = value,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A GBS file whose init stores the song number at 0xC000, and whose play counts its calls
+    /// at 0xC001.
+    pub(crate) fn gbs(timer_modulo: u8, timer_control: u8) -> Vec<u8> {
+        let mut file = vec![0u8; HEADER_END];
+        file[..3].copy_from_slice(MAGIC);
+        file[VERSION] = 1;
+        file[SONGS] = 3;
+        file[FIRST_SONG] = 1;
+        file[LOAD_ADDRESS..LOAD_ADDRESS + 2].copy_from_slice(&[0x00, 0x04]);
+        file[INIT_ADDRESS..INIT_ADDRESS + 2].copy_from_slice(&[0x00, 0x04]);
+        file[PLAY_ADDRESS..PLAY_ADDRESS + 2].copy_from_slice(&[0x04, 0x04]);
+        file[STACK_POINTER..STACK_POINTER + 2].copy_from_slice(&[0xFE, 0xFF]);
+        file[TIMER_MODULO] = timer_modulo;
+        file[TIMER_CONTROL] = timer_control;
+        file[TITLE_START..TITLE_START + 4].copy_from_slice(b"Test");
+        // LD (0xC000), A; RET
+        file.extend_from_slice(&[0xEA, 0x00, 0xC0, 0xC9]);
+        // LD HL, 0xC001; INC (HL); RET
+        file.extend_from_slice(&[0x21, 0x01, 0xC0, 0x34, 0xC9]);
+        file
+    }
+
+    #[test]
+    fn test_header() {
+        let gbs = GbsFile::from_bytes(gbs(0xC0, 0x84)).unwrap();
+        let header = &gbs.header;
+        assert_eq!(3, header.songs);
+        assert_eq!(0x0400, header.load_address);
+        assert_eq!(0x0404, header.play_address);
+        assert_eq!(0xFFFE, header.stack_pointer);
+        assert_eq!("Test", header.title);
+        assert_eq!("", header.author);
+        assert!(header.timer_interrupt());
+        assert!(header.double_speed());
+        assert_eq!(Ok(()), gbs.check_song(3));
+        assert_eq!(
+            Err(GbsError::NoSuchSong { song: 4, songs: 3 }),
+            gbs.check_song(4)
+        );
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        assert_eq!(
+            GbsError::MissingHeader(3),
+            GbsFile::from_bytes(b"GBS".to_vec()).unwrap_err()
+        );
+        let mut file = gbs(0, 0);
+        file[0] = b'X';
+        assert_eq!(GbsError::BadMagic, GbsFile::from_bytes(file).unwrap_err());
+        let mut file = gbs(0, 0);
+        file[LOAD_ADDRESS + 1] = 0x00;
+        assert_eq!(
+            GbsError::InvalidLoadAddress(0x0000),
+            GbsFile::from_bytes(file).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_rom() {
+        let mut file = gbs(0, 0);
+        file.resize(HEADER_END + 0x8000, 0);
+        // The first byte of banks 1 and 2.
+        file[HEADER_END + 0x3C00] = 0x11;
+        file[HEADER_END + 0x7C00] = 0x22;
+        let mut rom = GbsRom::new(&GbsFile::from_bytes(file).unwrap());
+        assert_eq!(0xEA, rom.read_u8(0x0400));
+        // RST 0x38 jumps to 0x0438.
+        assert_eq!(
+            [JP, 0x38, 0x04],
+            [rom.read_u8(0x38), rom.read_u8(0x39), rom.read_u8(0x3A)]
+        );
+        assert_eq!(0x11, rom.read_u8(0x4000));
+        rom.write_u8(0x2000, 2);
+        assert_eq!(0x22, rom.read_u8(0x4000));
+        rom.write_u8(0x2000, 0);
+        assert_eq!(0x11, rom.read_u8(0x4000));
+        rom.write_u8(0xA123, 0x42);
+        assert_eq!(0x42, rom.read_u8(0xA123));
+    }
+}
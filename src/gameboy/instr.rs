@@ -1,12 +1,15 @@
-use failure::Error;
+use std::fmt;
+
+use failure::{Error, Fail};
 
 use num_derive::{FromPrimitive, ToPrimitive};
 use num_traits::ToPrimitive;
 
 use crate::gameboy::cpu::flag;
 use crate::gameboy::cpu::register::*;
-use crate::gameboy::mem;
-use crate::GameBoy;
+use crate::gameboy::cpu::Mode;
+use crate::gameboy::mem::{self, Read};
+use crate::gameboy::GameBoy;
 
 pub(crate) fn execute(opcode: u8, gameboy: &mut GameBoy) -> Result<u8, Error> {
     match opcode {
@@ -17,19 +20,16 @@ pub(crate) fn execute(opcode: u8, gameboy: &mut GameBoy) -> Result<u8, Error> {
         0x04 => gameboy.inc(R8::B),
         0x05 => gameboy.dec(R8::B),
         0x06 => gameboy.load(R8::B, Immediate8),
-        0x07 => unimplemented!(),
-        0x08 => unimplemented!(),
-        // Storing into a deref u16 pointer seems to be only this instruction,
-        // so TBD if it should be special cased or if there are other similar instructions
-        // but not necessarily LD -- e.g. PUSH or POP?
-        // 0x08 => gameboy.load(AddrOf(Immediate16), R16::SP),
+        0x07 => gameboy.rlca(),
+        // Storing into a deref u16 pointer is unique to this instruction.
+        0x08 => gameboy.load(WideAddrOf(Immediate16), R16::SP),
         0x09 => gameboy.add(R16::HL, R16::BC, Carry::Without),
         0x0A => gameboy.load(R8::A, AddrOf(R16::BC)),
         0x0B => gameboy.dec16(R16::BC),
         0x0C => gameboy.inc(R8::C),
         0x0D => gameboy.dec(R8::C),
         0x0E => gameboy.load(R8::C, Immediate8),
-        0x0F => unimplemented!(),
+        0x0F => gameboy.rrca(),
         0x10 => gameboy.stop(),
         0x11 => gameboy.load(R16::DE, Immediate16),
         0x12 => gameboy.load(AddrOf(R16::DE), R8::A),
@@ -37,47 +37,47 @@ pub(crate) fn execute(opcode: u8, gameboy: &mut GameBoy) -> Result<u8, Error> {
         0x14 => gameboy.inc(R8::D),
         0x15 => gameboy.dec(R8::D),
         0x16 => gameboy.load(R8::D, Immediate8),
-        0x17 => unimplemented!(),
-        0x18 => gameboy.jump(Flags::Always, Immediate8),
+        0x17 => gameboy.rla(),
+        0x18 => gameboy.jump_relative(Flags::Always, SignedImmediate8),
         0x19 => gameboy.add(R16::HL, R16::DE, Carry::Without),
         0x1A => gameboy.load(R8::A, AddrOf(R16::DE)),
         0x1B => gameboy.dec16(R16::DE),
-        0x1C => gameboy.inc(R8::C),
-        0x1D => gameboy.dec(R8::C),
+        0x1C => gameboy.inc(R8::E),
+        0x1D => gameboy.dec(R8::E),
         0x1E => gameboy.load(R8::E, Immediate8),
-        0x1F => unimplemented!(),
-        0x20 => gameboy.jump(Flags::NZ, Immediate8),
+        0x1F => gameboy.rra(),
+        0x20 => gameboy.jump_relative(Flags::NZ, SignedImmediate8),
         0x21 => gameboy.load(R16::HL, Immediate16),
         0x22 => gameboy.load(AddrOf(PostInc(R16::HL)), R8::A),
         0x23 => gameboy.inc16(R16::HL),
         0x24 => gameboy.inc(R8::H),
         0x25 => gameboy.dec(R8::H),
         0x26 => gameboy.load(R8::H, Immediate8),
-        0x27 => unimplemented!(),
-        0x28 => gameboy.jump(Flags::Z, Immediate8),
+        0x27 => gameboy.daa(),
+        0x28 => gameboy.jump_relative(Flags::Z, SignedImmediate8),
         0x29 => gameboy.add(R16::HL, R16::HL, Carry::Without),
         0x2A => gameboy.load(R8::A, AddrOf(PostInc(R16::HL))),
         0x2B => gameboy.dec16(R16::HL),
         0x2C => gameboy.inc(R8::L),
         0x2D => gameboy.dec(R8::L),
         0x2E => gameboy.load(R8::L, Immediate8),
-        0x2F => unimplemented!(),
-        0x30 => gameboy.jump(Flags::NC, Immediate8),
+        0x2F => gameboy.cpl(),
+        0x30 => gameboy.jump_relative(Flags::NC, SignedImmediate8),
         0x31 => gameboy.load(R16::SP, Immediate16),
         0x32 => gameboy.load(AddrOf(PostDec(R16::HL)), R8::A),
         0x33 => gameboy.inc16(R16::SP),
         0x34 => gameboy.inc(AddrOf(R16::HL)),
         0x35 => gameboy.dec(AddrOf(R16::HL)),
         0x36 => gameboy.load(AddrOf(R16::HL), Immediate8),
-        0x37 => unimplemented!(),
-        0x38 => gameboy.jump(Flags::C, Immediate8),
+        0x37 => gameboy.scf(),
+        0x38 => gameboy.jump_relative(Flags::C, SignedImmediate8),
         0x39 => gameboy.add(R16::HL, R16::SP, Carry::Without),
         0x3A => gameboy.load(R8::A, AddrOf(PostDec(R16::HL))),
         0x3B => gameboy.dec16(R16::SP),
         0x3C => gameboy.inc(R8::A),
         0x3D => gameboy.dec(R8::A),
         0x3E => gameboy.load(R8::A, Immediate8),
-        0x3F => unimplemented!(),
+        0x3F => gameboy.ccf(),
         0x40 => gameboy.load(R8::B, R8::B),
         0x41 => gameboy.load(R8::B, R8::C),
         0x42 => gameboy.load(R8::B, R8::D),
@@ -206,76 +206,366 @@ pub(crate) fn execute(opcode: u8, gameboy: &mut GameBoy) -> Result<u8, Error> {
         0xBD => gameboy.cmp(R8::A, R8::L),
         0xBE => gameboy.cmp(R8::A, AddrOf(R16::HL)),
         0xBF => gameboy.cmp(R8::A, R8::A),
-        0xC0 => unimplemented!(),
-        0xC1 => unimplemented!(),
+        0xC0 => gameboy.ret(Flags::NZ),
+        0xC1 => gameboy.pop(R16::BC),
         0xC2 => gameboy.jump(Flags::NZ, Immediate16),
         0xC3 => gameboy.jump(Flags::Always, Immediate16),
-        0xC4 => unimplemented!(),
+        0xC4 => gameboy.call(Flags::NZ, Immediate16),
         0xC5 => gameboy.push(R16::BC),
         0xC6 => gameboy.add(R8::A, Immediate8, Carry::Without),
-        0xC7 => unimplemented!(),
-        0xC8 => unimplemented!(),
-        0xC9 => unimplemented!(),
+        0xC7 => gameboy.rst(0x00),
+        0xC8 => gameboy.ret(Flags::Z),
+        0xC9 => gameboy.ret(Flags::Always),
         0xCA => gameboy.jump(Flags::Z, Immediate16),
-        0xCB => unimplemented!(),
-        0xCC => unimplemented!(),
-        0xCD => unimplemented!(),
+        0xCB => {
+            let opcode = Immediate8.read(gameboy);
+            // The prefix byte itself costs one cycle to fetch.
+            execute_cb(opcode, gameboy).map(|cycles| cycles + 1)
+        }
+        0xCC => gameboy.call(Flags::Z, Immediate16),
+        0xCD => gameboy.call(Flags::Always, Immediate16),
         0xCE => gameboy.add(R8::A, Immediate8, Carry::With),
-        0xCF => unimplemented!(),
-        0xD0 => unimplemented!(),
-        0xD1 => unimplemented!(),
+        0xCF => gameboy.rst(0x08),
+        0xD0 => gameboy.ret(Flags::NC),
+        0xD1 => gameboy.pop(R16::DE),
         0xD2 => gameboy.jump(Flags::NC, Immediate16),
-        0xD3 => unimplemented!(),
-        0xD4 => unimplemented!(),
+        0xD3 => illegal(opcode, gameboy),
+        0xD4 => gameboy.call(Flags::NC, Immediate16),
         0xD5 => gameboy.push(R16::DE),
         0xD6 => gameboy.sub(R8::A, Immediate8, Carry::Without),
-        0xD7 => unimplemented!(),
-        0xD8 => unimplemented!(),
-        0xD9 => unimplemented!(),
+        0xD7 => gameboy.rst(0x10),
+        0xD8 => gameboy.ret(Flags::C),
+        0xD9 => gameboy.reti(),
         0xDA => gameboy.jump(Flags::C, Immediate16),
-        0xDB => unimplemented!(),
-        0xDC => unimplemented!(),
-        0xDD => unimplemented!(),
+        0xDB => illegal(opcode, gameboy),
+        0xDC => gameboy.call(Flags::C, Immediate16),
+        0xDD => illegal(opcode, gameboy),
         0xDE => gameboy.sub(R8::A, Immediate8, Carry::With),
-        0xDF => unimplemented!(),
+        0xDF => gameboy.rst(0x18),
         0xE0 => gameboy.load(AddrOf(Immediate8), R8::A),
-        0xE1 => unimplemented!(),
+        0xE1 => gameboy.pop(R16::HL),
         0xE2 => gameboy.load(AddrOf(R8::C), R8::A),
-        0xE3 => unimplemented!(),
-        0xE4 => unimplemented!(),
+        0xE3 => illegal(opcode, gameboy),
+        0xE4 => illegal(opcode, gameboy),
         0xE5 => gameboy.push(R16::HL),
         0xE6 => gameboy.and(R8::A, Immediate8),
-        0xE7 => unimplemented!(),
-        0xE8 => unimplemented!(),
-        // ^TODO: Create separate method for this, since its behavior is unique. I.e., not:
-        // gameboy.add(R16::SP, Immediate8, Carry::Without),
-        0xE9 => unimplemented!(),
+        0xE7 => gameboy.rst(0x20),
+        // Writing the result back to SP takes an extra cycle compared to 0xF8.
+        0xE8 => gameboy
+            .offset_sp(R16::SP, SignedImmediate8)
+            .map(|cycles| cycles + 1),
+        0xE9 => gameboy.load(R16::PC, R16::HL),
         0xEA => gameboy.load(AddrOf(Immediate16), R8::A),
-        0xEB => unimplemented!(),
-        0xEC => unimplemented!(),
-        0xED => unimplemented!(),
+        0xEB => illegal(opcode, gameboy),
+        0xEC => illegal(opcode, gameboy),
+        0xED => illegal(opcode, gameboy),
         0xEE => gameboy.xor(R8::A, Immediate8),
-        0xEF => unimplemented!(),
+        0xEF => gameboy.rst(0x28),
         0xF0 => gameboy.load(R8::A, AddrOf(Immediate8)),
-        0xF1 => unimplemented!(),
+        0xF1 => gameboy.pop(R16::AF),
         0xF2 => gameboy.load(R8::A, AddrOf(R8::C)),
         0xF3 => gameboy.set_interrupt(Interrupt::Disable),
-        0xF4 => unimplemented!(),
+        0xF4 => illegal(opcode, gameboy),
         0xF5 => gameboy.push(R16::AF),
         0xF6 => gameboy.or(R8::A, Immediate8),
-        0xF7 => unimplemented!(),
-        0xF8 => gameboy.load(R16::HL, Plus(R16::SP, Immediate8)),
-        0xF9 => gameboy.load(R16::SP, R16::HL),
+        0xF7 => gameboy.rst(0x30),
+        0xF8 => gameboy.offset_sp(R16::HL, SignedImmediate8),
+        // Moving a 16 bit register takes an extra cycle.
+        0xF9 => gameboy.load(R16::SP, R16::HL).map(|cycles| cycles + 1),
         0xFA => gameboy.load(R8::A, AddrOf(Immediate16)),
         0xFB => gameboy.set_interrupt(Interrupt::Enable),
-        0xFC => unimplemented!(),
-        0xFD => unimplemented!(),
+        0xFC => illegal(opcode, gameboy),
+        0xFD => illegal(opcode, gameboy),
         0xFE => gameboy.cmp(R8::A, Immediate8),
-        0xFF => unimplemented!(),
+        0xFF => gameboy.rst(0x38),
     }
 }
 
-struct Plus<T, U>(T, U);
+#[derive(Debug, PartialEq)]
+pub(crate) enum InstrError {
+    /// One of the opcodes which don't map to an instruction, and lock up the CPU.
+    IllegalOpcode { opcode: u8, addr: u16 },
+}
+
+impl fmt::Display for InstrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InstrError::IllegalOpcode { opcode, addr } => {
+                write!(f, "illegal opcode 0x{:02X} at 0x{:04X}", opcode, addr)
+            }
+        }
+    }
+}
+
+impl Fail for InstrError {}
+
+fn illegal(opcode: u8, gameboy: &GameBoy) -> Result<u8, Error> {
+    // The opcode has already been fetched, so PC has moved past it.
+    let addr = gameboy.cpu.register.pc.wrapping_sub(1);
+    Err(InstrError::IllegalOpcode { opcode, addr }.into())
+}
+
+/// Executes an instruction from the second page of opcodes, i.e. the ones prefixed by 0xCB.
+fn execute_cb(opcode: u8, gameboy: &mut GameBoy) -> Result<u8, Error> {
+    match opcode {
+        0x00 => gameboy.rlc(R8::B),
+        0x01 => gameboy.rlc(R8::C),
+        0x02 => gameboy.rlc(R8::D),
+        0x03 => gameboy.rlc(R8::E),
+        0x04 => gameboy.rlc(R8::H),
+        0x05 => gameboy.rlc(R8::L),
+        0x06 => gameboy.rlc(AddrOf(R16::HL)),
+        0x07 => gameboy.rlc(R8::A),
+        0x08 => gameboy.rrc(R8::B),
+        0x09 => gameboy.rrc(R8::C),
+        0x0A => gameboy.rrc(R8::D),
+        0x0B => gameboy.rrc(R8::E),
+        0x0C => gameboy.rrc(R8::H),
+        0x0D => gameboy.rrc(R8::L),
+        0x0E => gameboy.rrc(AddrOf(R16::HL)),
+        0x0F => gameboy.rrc(R8::A),
+        0x10 => gameboy.rl(R8::B),
+        0x11 => gameboy.rl(R8::C),
+        0x12 => gameboy.rl(R8::D),
+        0x13 => gameboy.rl(R8::E),
+        0x14 => gameboy.rl(R8::H),
+        0x15 => gameboy.rl(R8::L),
+        0x16 => gameboy.rl(AddrOf(R16::HL)),
+        0x17 => gameboy.rl(R8::A),
+        0x18 => gameboy.rr(R8::B),
+        0x19 => gameboy.rr(R8::C),
+        0x1A => gameboy.rr(R8::D),
+        0x1B => gameboy.rr(R8::E),
+        0x1C => gameboy.rr(R8::H),
+        0x1D => gameboy.rr(R8::L),
+        0x1E => gameboy.rr(AddrOf(R16::HL)),
+        0x1F => gameboy.rr(R8::A),
+        0x20 => gameboy.sla(R8::B),
+        0x21 => gameboy.sla(R8::C),
+        0x22 => gameboy.sla(R8::D),
+        0x23 => gameboy.sla(R8::E),
+        0x24 => gameboy.sla(R8::H),
+        0x25 => gameboy.sla(R8::L),
+        0x26 => gameboy.sla(AddrOf(R16::HL)),
+        0x27 => gameboy.sla(R8::A),
+        0x28 => gameboy.sra(R8::B),
+        0x29 => gameboy.sra(R8::C),
+        0x2A => gameboy.sra(R8::D),
+        0x2B => gameboy.sra(R8::E),
+        0x2C => gameboy.sra(R8::H),
+        0x2D => gameboy.sra(R8::L),
+        0x2E => gameboy.sra(AddrOf(R16::HL)),
+        0x2F => gameboy.sra(R8::A),
+        0x30 => gameboy.swap(R8::B),
+        0x31 => gameboy.swap(R8::C),
+        0x32 => gameboy.swap(R8::D),
+        0x33 => gameboy.swap(R8::E),
+        0x34 => gameboy.swap(R8::H),
+        0x35 => gameboy.swap(R8::L),
+        0x36 => gameboy.swap(AddrOf(R16::HL)),
+        0x37 => gameboy.swap(R8::A),
+        0x38 => gameboy.srl(R8::B),
+        0x39 => gameboy.srl(R8::C),
+        0x3A => gameboy.srl(R8::D),
+        0x3B => gameboy.srl(R8::E),
+        0x3C => gameboy.srl(R8::H),
+        0x3D => gameboy.srl(R8::L),
+        0x3E => gameboy.srl(AddrOf(R16::HL)),
+        0x3F => gameboy.srl(R8::A),
+        0x40 => gameboy.bit(0, R8::B),
+        0x41 => gameboy.bit(0, R8::C),
+        0x42 => gameboy.bit(0, R8::D),
+        0x43 => gameboy.bit(0, R8::E),
+        0x44 => gameboy.bit(0, R8::H),
+        0x45 => gameboy.bit(0, R8::L),
+        0x46 => gameboy.bit(0, AddrOf(R16::HL)),
+        0x47 => gameboy.bit(0, R8::A),
+        0x48 => gameboy.bit(1, R8::B),
+        0x49 => gameboy.bit(1, R8::C),
+        0x4A => gameboy.bit(1, R8::D),
+        0x4B => gameboy.bit(1, R8::E),
+        0x4C => gameboy.bit(1, R8::H),
+        0x4D => gameboy.bit(1, R8::L),
+        0x4E => gameboy.bit(1, AddrOf(R16::HL)),
+        0x4F => gameboy.bit(1, R8::A),
+        0x50 => gameboy.bit(2, R8::B),
+        0x51 => gameboy.bit(2, R8::C),
+        0x52 => gameboy.bit(2, R8::D),
+        0x53 => gameboy.bit(2, R8::E),
+        0x54 => gameboy.bit(2, R8::H),
+        0x55 => gameboy.bit(2, R8::L),
+        0x56 => gameboy.bit(2, AddrOf(R16::HL)),
+        0x57 => gameboy.bit(2, R8::A),
+        0x58 => gameboy.bit(3, R8::B),
+        0x59 => gameboy.bit(3, R8::C),
+        0x5A => gameboy.bit(3, R8::D),
+        0x5B => gameboy.bit(3, R8::E),
+        0x5C => gameboy.bit(3, R8::H),
+        0x5D => gameboy.bit(3, R8::L),
+        0x5E => gameboy.bit(3, AddrOf(R16::HL)),
+        0x5F => gameboy.bit(3, R8::A),
+        0x60 => gameboy.bit(4, R8::B),
+        0x61 => gameboy.bit(4, R8::C),
+        0x62 => gameboy.bit(4, R8::D),
+        0x63 => gameboy.bit(4, R8::E),
+        0x64 => gameboy.bit(4, R8::H),
+        0x65 => gameboy.bit(4, R8::L),
+        0x66 => gameboy.bit(4, AddrOf(R16::HL)),
+        0x67 => gameboy.bit(4, R8::A),
+        0x68 => gameboy.bit(5, R8::B),
+        0x69 => gameboy.bit(5, R8::C),
+        0x6A => gameboy.bit(5, R8::D),
+        0x6B => gameboy.bit(5, R8::E),
+        0x6C => gameboy.bit(5, R8::H),
+        0x6D => gameboy.bit(5, R8::L),
+        0x6E => gameboy.bit(5, AddrOf(R16::HL)),
+        0x6F => gameboy.bit(5, R8::A),
+        0x70 => gameboy.bit(6, R8::B),
+        0x71 => gameboy.bit(6, R8::C),
+        0x72 => gameboy.bit(6, R8::D),
+        0x73 => gameboy.bit(6, R8::E),
+        0x74 => gameboy.bit(6, R8::H),
+        0x75 => gameboy.bit(6, R8::L),
+        0x76 => gameboy.bit(6, AddrOf(R16::HL)),
+        0x77 => gameboy.bit(6, R8::A),
+        0x78 => gameboy.bit(7, R8::B),
+        0x79 => gameboy.bit(7, R8::C),
+        0x7A => gameboy.bit(7, R8::D),
+        0x7B => gameboy.bit(7, R8::E),
+        0x7C => gameboy.bit(7, R8::H),
+        0x7D => gameboy.bit(7, R8::L),
+        0x7E => gameboy.bit(7, AddrOf(R16::HL)),
+        0x7F => gameboy.bit(7, R8::A),
+        0x80 => gameboy.res(0, R8::B),
+        0x81 => gameboy.res(0, R8::C),
+        0x82 => gameboy.res(0, R8::D),
+        0x83 => gameboy.res(0, R8::E),
+        0x84 => gameboy.res(0, R8::H),
+        0x85 => gameboy.res(0, R8::L),
+        0x86 => gameboy.res(0, AddrOf(R16::HL)),
+        0x87 => gameboy.res(0, R8::A),
+        0x88 => gameboy.res(1, R8::B),
+        0x89 => gameboy.res(1, R8::C),
+        0x8A => gameboy.res(1, R8::D),
+        0x8B => gameboy.res(1, R8::E),
+        0x8C => gameboy.res(1, R8::H),
+        0x8D => gameboy.res(1, R8::L),
+        0x8E => gameboy.res(1, AddrOf(R16::HL)),
+        0x8F => gameboy.res(1, R8::A),
+        0x90 => gameboy.res(2, R8::B),
+        0x91 => gameboy.res(2, R8::C),
+        0x92 => gameboy.res(2, R8::D),
+        0x93 => gameboy.res(2, R8::E),
+        0x94 => gameboy.res(2, R8::H),
+        0x95 => gameboy.res(2, R8::L),
+        0x96 => gameboy.res(2, AddrOf(R16::HL)),
+        0x97 => gameboy.res(2, R8::A),
+        0x98 => gameboy.res(3, R8::B),
+        0x99 => gameboy.res(3, R8::C),
+        0x9A => gameboy.res(3, R8::D),
+        0x9B => gameboy.res(3, R8::E),
+        0x9C => gameboy.res(3, R8::H),
+        0x9D => gameboy.res(3, R8::L),
+        0x9E => gameboy.res(3, AddrOf(R16::HL)),
+        0x9F => gameboy.res(3, R8::A),
+        0xA0 => gameboy.res(4, R8::B),
+        0xA1 => gameboy.res(4, R8::C),
+        0xA2 => gameboy.res(4, R8::D),
+        0xA3 => gameboy.res(4, R8::E),
+        0xA4 => gameboy.res(4, R8::H),
+        0xA5 => gameboy.res(4, R8::L),
+        0xA6 => gameboy.res(4, AddrOf(R16::HL)),
+        0xA7 => gameboy.res(4, R8::A),
+        0xA8 => gameboy.res(5, R8::B),
+        0xA9 => gameboy.res(5, R8::C),
+        0xAA => gameboy.res(5, R8::D),
+        0xAB => gameboy.res(5, R8::E),
+        0xAC => gameboy.res(5, R8::H),
+        0xAD => gameboy.res(5, R8::L),
+        0xAE => gameboy.res(5, AddrOf(R16::HL)),
+        0xAF => gameboy.res(5, R8::A),
+        0xB0 => gameboy.res(6, R8::B),
+        0xB1 => gameboy.res(6, R8::C),
+        0xB2 => gameboy.res(6, R8::D),
+        0xB3 => gameboy.res(6, R8::E),
+        0xB4 => gameboy.res(6, R8::H),
+        0xB5 => gameboy.res(6, R8::L),
+        0xB6 => gameboy.res(6, AddrOf(R16::HL)),
+        0xB7 => gameboy.res(6, R8::A),
+        0xB8 => gameboy.res(7, R8::B),
+        0xB9 => gameboy.res(7, R8::C),
+        0xBA => gameboy.res(7, R8::D),
+        0xBB => gameboy.res(7, R8::E),
+        0xBC => gameboy.res(7, R8::H),
+        0xBD => gameboy.res(7, R8::L),
+        0xBE => gameboy.res(7, AddrOf(R16::HL)),
+        0xBF => gameboy.res(7, R8::A),
+        0xC0 => gameboy.set(0, R8::B),
+        0xC1 => gameboy.set(0, R8::C),
+        0xC2 => gameboy.set(0, R8::D),
+        0xC3 => gameboy.set(0, R8::E),
+        0xC4 => gameboy.set(0, R8::H),
+        0xC5 => gameboy.set(0, R8::L),
+        0xC6 => gameboy.set(0, AddrOf(R16::HL)),
+        0xC7 => gameboy.set(0, R8::A),
+        0xC8 => gameboy.set(1, R8::B),
+        0xC9 => gameboy.set(1, R8::C),
+        0xCA => gameboy.set(1, R8::D),
+        0xCB => gameboy.set(1, R8::E),
+        0xCC => gameboy.set(1, R8::H),
+        0xCD => gameboy.set(1, R8::L),
+        0xCE => gameboy.set(1, AddrOf(R16::HL)),
+        0xCF => gameboy.set(1, R8::A),
+        0xD0 => gameboy.set(2, R8::B),
+        0xD1 => gameboy.set(2, R8::C),
+        0xD2 => gameboy.set(2, R8::D),
+        0xD3 => gameboy.set(2, R8::E),
+        0xD4 => gameboy.set(2, R8::H),
+        0xD5 => gameboy.set(2, R8::L),
+        0xD6 => gameboy.set(2, AddrOf(R16::HL)),
+        0xD7 => gameboy.set(2, R8::A),
+        0xD8 => gameboy.set(3, R8::B),
+        0xD9 => gameboy.set(3, R8::C),
+        0xDA => gameboy.set(3, R8::D),
+        0xDB => gameboy.set(3, R8::E),
+        0xDC => gameboy.set(3, R8::H),
+        0xDD => gameboy.set(3, R8::L),
+        0xDE => gameboy.set(3, AddrOf(R16::HL)),
+        0xDF => gameboy.set(3, R8::A),
+        0xE0 => gameboy.set(4, R8::B),
+        0xE1 => gameboy.set(4, R8::C),
+        0xE2 => gameboy.set(4, R8::D),
+        0xE3 => gameboy.set(4, R8::E),
+        0xE4 => gameboy.set(4, R8::H),
+        0xE5 => gameboy.set(4, R8::L),
+        0xE6 => gameboy.set(4, AddrOf(R16::HL)),
+        0xE7 => gameboy.set(4, R8::A),
+        0xE8 => gameboy.set(5, R8::B),
+        0xE9 => gameboy.set(5, R8::C),
+        0xEA => gameboy.set(5, R8::D),
+        0xEB => gameboy.set(5, R8::E),
+        0xEC => gameboy.set(5, R8::H),
+        0xED => gameboy.set(5, R8::L),
+        0xEE => gameboy.set(5, AddrOf(R16::HL)),
+        0xEF => gameboy.set(5, R8::A),
+        0xF0 => gameboy.set(6, R8::B),
+        0xF1 => gameboy.set(6, R8::C),
+        0xF2 => gameboy.set(6, R8::D),
+        0xF3 => gameboy.set(6, R8::E),
+        0xF4 => gameboy.set(6, R8::H),
+        0xF5 => gameboy.set(6, R8::L),
+        0xF6 => gameboy.set(6, AddrOf(R16::HL)),
+        0xF7 => gameboy.set(6, R8::A),
+        0xF8 => gameboy.set(7, R8::B),
+        0xF9 => gameboy.set(7, R8::C),
+        0xFA => gameboy.set(7, R8::D),
+        0xFB => gameboy.set(7, R8::E),
+        0xFC => gameboy.set(7, R8::H),
+        0xFD => gameboy.set(7, R8::L),
+        0xFE => gameboy.set(7, AddrOf(R16::HL)),
+        0xFF => gameboy.set(7, R8::A),
+    }
+}
 
 struct PostDec<T>(T);
 
@@ -287,8 +577,14 @@ struct Constant<T>(T);
 
 struct AddrOf<T>(T);
 
+/// Like `AddrOf`, but for 16 bit values stored in two consecutive bytes.
+struct WideAddrOf<T>(T);
+
 struct Immediate8;
 
+/// An immediate which is interpreted as a two's complement displacement, e.g. by `JR`.
+struct SignedImmediate8;
+
 struct Immediate16;
 
 trait AsAddr {
@@ -301,24 +597,80 @@ impl AsAddr for u16 {
     }
 }
 
+// 8 bit addresses are offsets into the high page, i.e. 0xFF00-0xFFFF, where the I/O registers
+// and HRAM live.
 impl AsAddr for u8 {
     fn into_addr(self) -> u16 {
-        u16::from(self) + 0xff
+        0xFF00 | u16::from(self)
     }
 }
 
-trait Integer: num_traits::PrimInt + num_traits::Unsigned {
+/// `Operand` describes the cost of accessing an operand, which is what separates e.g.
+/// `RLC B` from `RLC (HL)`.
+trait Operand {
+    /// The number of cycles spent on a single read or write of the operand.
+    const CYCLES: u8;
+}
+
+impl Operand for R8 {
+    const CYCLES: u8 = 0;
+}
+
+impl Operand for R16 {
+    const CYCLES: u8 = 0;
+}
+
+impl Operand for Immediate8 {
+    const CYCLES: u8 = 1;
+}
+
+impl Operand for SignedImmediate8 {
+    const CYCLES: u8 = 1;
+}
+
+impl Operand for Immediate16 {
+    const CYCLES: u8 = 2;
+}
+
+impl<T> Operand for Constant<T> {
+    const CYCLES: u8 = 0;
+}
+
+// The address has to be read before the memory behind it can be accessed.
+impl<T: Operand> Operand for AddrOf<T> {
+    const CYCLES: u8 = 1 + T::CYCLES;
+}
+
+impl<T: Operand> Operand for WideAddrOf<T> {
+    const CYCLES: u8 = 2 + T::CYCLES;
+}
+
+impl<T: Operand> Operand for PostInc<T> {
+    const CYCLES: u8 = T::CYCLES;
+}
+
+impl<T: Operand> Operand for PostDec<T> {
+    const CYCLES: u8 = T::CYCLES;
+}
+
+impl<T: Operand> Operand for NoWrite<T> {
+    const CYCLES: u8 = T::CYCLES;
+}
+
+trait Integer:
+    num_traits::PrimInt + num_traits::Unsigned + num_traits::WrappingAdd + num_traits::WrappingSub
+{
     const CYCLES: u8;
     const HALF_CARRY_FLAG: Self;
     fn from_carry(carry: Carry) -> Self;
     fn set_zero_flag(f: &mut RegisterF, res: Self);
     fn overflowing_add(self, rhs: Self) -> (Self, bool);
-    fn overflowing_sub(self, rhs: Self) -> (Self, bool);
 }
 
 impl Integer for u16 {
     const CYCLES: u8 = 2;
-    const HALF_CARRY_FLAG: Self = 0x100;
+    // 16 bit additions set the half carry flag on a carry from bit 11.
+    const HALF_CARRY_FLAG: Self = 0x1000;
 
     fn from_carry(carry: Carry) -> Self {
         carry.to_u16().unwrap()
@@ -331,10 +683,6 @@ impl Integer for u16 {
     fn overflowing_add(self, rhs: Self) -> (Self, bool) {
         u16::overflowing_add(self, rhs)
     }
-
-    fn overflowing_sub(self, rhs: Self) -> (Self, bool) {
-        u16::overflowing_sub(self, rhs)
-    }
 }
 
 impl Integer for u8 {
@@ -352,25 +700,6 @@ impl Integer for u8 {
     fn overflowing_add(self, rhs: Self) -> (Self, bool) {
         u8::overflowing_add(self, rhs)
     }
-
-    fn overflowing_sub(self, rhs: Self) -> (Self, bool) {
-        u8::overflowing_sub(self, rhs)
-    }
-}
-
-impl<T, U, UNum, Num> mem::Read for Plus<T, U>
-where
-    T: mem::Read<Out = Num>,
-    U: mem::Read<Out = UNum>,
-    UNum: Into<Num>,
-    Num: Integer,
-{
-    type Out = Num;
-    fn read(&self, gb: &mut GameBoy) -> Self::Out {
-        let lhs = self.0.read(gb);
-        let rhs = self.1.read(gb);
-        lhs + rhs.into()
-    }
 }
 
 impl<W, T> mem::Read for PostInc<W>
@@ -382,7 +711,7 @@ where
     fn read(&self, gb: &mut GameBoy) -> Self::Out {
         let value = self.0.read(gb);
         self.0
-            .write(gb, value + T::one())
+            .write(gb, value.wrapping_add(&T::one()))
             .expect("post inc write must not fail");
         value
     }
@@ -397,7 +726,7 @@ where
     fn read(&self, gb: &mut GameBoy) -> Self::Out {
         let value = self.0.read(gb);
         self.0
-            .write(gb, value - T::one())
+            .write(gb, value.wrapping_sub(&T::one()))
             .expect("post dec write must not fail");
         value
     }
@@ -407,7 +736,7 @@ impl<A: AsAddr, R: mem::Read<Out = A>> mem::Read for AddrOf<R> {
     type Out = u8;
     fn read(&self, gb: &mut GameBoy) -> Self::Out {
         let addr = self.0.read(gb).into_addr();
-        gb.mmu.read_u8(addr)
+        gb.read_cycle(addr)
     }
 }
 
@@ -415,7 +744,17 @@ impl<A: AsAddr, R: mem::Read<Out = A>> mem::Write for AddrOf<R> {
     type In = u8;
     fn write(&self, gb: &mut GameBoy, value: Self::In) -> Result<(), Error> {
         let addr = self.0.read(gb).into_addr();
-        gb.mmu.write_u8(addr, value);
+        gb.write_cycle(addr, value);
+        Ok(())
+    }
+}
+
+impl<A: AsAddr, R: mem::Read<Out = A>> mem::Write for WideAddrOf<R> {
+    type In = u16;
+    fn write(&self, gb: &mut GameBoy, value: Self::In) -> Result<(), Error> {
+        let addr = self.0.read(gb).into_addr();
+        gb.write_cycle(addr, value as u8);
+        gb.write_cycle(addr.wrapping_add(1), (value >> 8) as u8);
         Ok(())
     }
 }
@@ -442,32 +781,54 @@ impl<T: Copy> mem::Read for Constant<T> {
     }
 }
 
+// Reading an immediate consumes it, i.e. the program counter is moved past the operand.
 impl mem::Read for Immediate8 {
     type Out = u8;
     fn read(&self, gb: &mut GameBoy) -> Self::Out {
-        gb.mmu.read_u8(*gb.cpu.register.pc)
+        let value = gb.read_cycle(*gb.cpu.register.pc);
+        gb.advance_pc(1);
+        value
+    }
+}
+
+impl mem::Read for SignedImmediate8 {
+    type Out = i8;
+    fn read(&self, gb: &mut GameBoy) -> Self::Out {
+        Immediate8.read(gb) as i8
     }
 }
 
 impl mem::Read for Immediate16 {
     type Out = u16;
     fn read(&self, gb: &mut GameBoy) -> Self::Out {
-        gb.mmu.read_u16(*gb.cpu.register.pc)
+        let lo = Immediate8.read(gb);
+        let hi = Immediate8.read(gb);
+        u16::from(hi) << 8 | u16::from(lo)
     }
 }
 
 enum Flags {
     Always,
     Z,
-    N,
-    H,
     C,
     NZ,
-    NN,
-    NH,
     NC,
 }
 
+impl Flags {
+    /// Checks whether the condition holds for the given register F.
+    fn test(&self, f: RegisterF) -> bool {
+        use self::Flags::*;
+        match self {
+            Always => true,
+            Z => f[flag::Z].as_bool(),
+            C => f[flag::C].as_bool(),
+            NZ => !f[flag::Z].as_bool(),
+            NC => !f[flag::C].as_bool(),
+        }
+    }
+}
+
 enum R8 {
     A,
     B,
@@ -498,14 +859,16 @@ impl mem::Write for R8 {
     type In = u8;
     fn write(&self, gb: &mut GameBoy, value: Self::In) -> Result<(), Error> {
         use self::R8::*;
-        let reg: &mut u8 = &mut match self {
-            A => gb.cpu.register.a(),
-            B => gb.cpu.register.b(),
-            C => gb.cpu.register.c(),
-            D => gb.cpu.register.d(),
-            E => gb.cpu.register.e(),
-            H => gb.cpu.register.h(),
-            L => gb.cpu.register.l(),
+        // `Register` is `Copy`, so the mutable borrow must be explicit to not write to a copy.
+        let register = &mut gb.cpu.register;
+        let reg: &mut u8 = match self {
+            A => register.a(),
+            B => register.b(),
+            C => register.c(),
+            D => register.d(),
+            E => register.e(),
+            H => register.h(),
+            L => register.l(),
         };
         *reg = value;
         Ok(())
@@ -518,6 +881,7 @@ enum R16 {
     DE,
     HL,
     SP,
+    PC,
 }
 
 impl mem::Read for R16 {
@@ -530,6 +894,7 @@ impl mem::Read for R16 {
             DE => *gb.cpu.register.de,
             HL => *gb.cpu.register.hl,
             SP => *gb.cpu.register.sp,
+            PC => *gb.cpu.register.pc,
         }
     }
 }
@@ -538,12 +903,18 @@ impl mem::Write for R16 {
     type In = u16;
     fn write(&self, gb: &mut GameBoy, value: Self::In) -> Result<(), Error> {
         use self::R16::*;
-        let reg: &mut u16 = &mut match self {
-            AF => *gb.cpu.register.af,
-            BC => *gb.cpu.register.bc,
-            DE => *gb.cpu.register.de,
-            HL => *gb.cpu.register.hl,
-            SP => *gb.cpu.register.sp,
+        // The lower nibble of register F is hardwired to zero.
+        let value = match self {
+            AF => value & 0xFFF0,
+            _ => value,
+        };
+        let reg: &mut u16 = match self {
+            AF => &mut gb.cpu.register.af,
+            BC => &mut gb.cpu.register.bc,
+            DE => &mut gb.cpu.register.de,
+            HL => &mut gb.cpu.register.hl,
+            SP => &mut gb.cpu.register.sp,
+            PC => &mut gb.cpu.register.pc,
         };
         *reg = value;
         Ok(())
@@ -556,6 +927,11 @@ enum Carry {
     With,
 }
 
+enum Direction {
+    Left,
+    Right,
+}
+
 enum Interrupt {
     Enable,
     Disable,
@@ -566,33 +942,49 @@ trait Instructions {
 
     fn load<W, R, V>(&mut self, _: W, _: R) -> Self::Output
     where
-        W: mem::Write<In = V>,
-        R: mem::Read<Out = V>;
+        W: mem::Write<In = V> + Operand,
+        R: mem::Read<Out = V> + Operand;
 
     fn binary_op<LHS, RHS, F, Num>(&mut self, _: LHS, _: RHS, _: F) -> Self::Output
     where
-        LHS: mem::Read<Out = Num> + mem::Write<In = Num>,
-        RHS: mem::Read<Out = Num>,
+        LHS: mem::Read<Out = Num> + mem::Write<In = Num> + Operand,
+        RHS: mem::Read<Out = Num> + Operand,
         F: FnOnce(Num, Num, RegisterF) -> (Num, RegisterF),
         Num: Integer;
 
-    fn jump<Offset, V>(&mut self, _: Flags, _: Offset) -> Self::Output
+    fn unary_op<T, F>(&mut self, _: T, _: F) -> Self::Output
     where
-        V: Into<u16>,
-        Offset: mem::Read<Out = V>;
+        T: mem::Read<Out = u8> + mem::Write<In = u8> + Operand,
+        F: FnOnce(u8, RegisterF) -> (u8, RegisterF);
+
+    fn jump<R>(&mut self, _: Flags, _: R) -> Self::Output
+    where
+        R: mem::Read<Out = u16>;
+
+    fn jump_relative<R>(&mut self, _: Flags, _: R) -> Self::Output
+    where
+        R: mem::Read<Out = i8>;
+
+    /// Adds a signed displacement to SP and writes the result to `to`. The flags are computed from
+    /// the unsigned addition of the lower byte of SP and the displacement.
+    fn offset_sp<W, R>(&mut self, to: W, offset: R) -> Self::Output
+    where
+        W: mem::Write<In = u16>,
+        R: mem::Read<Out = i8>;
 
     fn set_interrupt(&mut self, _: Interrupt) -> Self::Output;
 
     fn add<LHS, RHS, Num>(&mut self, lhs: LHS, rhs: RHS, carry: Carry) -> Self::Output
     where
-        LHS: mem::Read<Out = Num> + mem::Write<In = Num>,
-        RHS: mem::Read<Out = Num>,
+        LHS: mem::Read<Out = Num> + mem::Write<In = Num> + Operand,
+        RHS: mem::Read<Out = Num> + Operand,
         Num: Integer,
     {
         self.binary_op(lhs, rhs, |x, y, mut f| {
             let (res, overflow1) = x.overflowing_add(y);
             let (res, overflow2) = res.overflowing_add(Num::from_carry(carry));
             Num::set_zero_flag(&mut f, res);
+            f[flag::N].reset();
             f[flag::H].set_bool((x ^ y ^ res) & Num::HALF_CARRY_FLAG != Num::zero());
             f[flag::C].set_bool(overflow1 || overflow2);
             (res, f)
@@ -601,8 +993,8 @@ trait Instructions {
 
     fn sub<LHS, RHS>(&mut self, lhs: LHS, rhs: RHS, carry: Carry) -> Self::Output
     where
-        LHS: mem::Read<Out = u8> + mem::Write<In = u8>,
-        RHS: mem::Read<Out = u8>,
+        LHS: mem::Read<Out = u8> + mem::Write<In = u8> + Operand,
+        RHS: mem::Read<Out = u8> + Operand,
     {
         let carry = carry.to_u8().unwrap();
         self.binary_op(lhs, rhs, |x, y, mut f| {
@@ -618,21 +1010,24 @@ trait Instructions {
 
     fn xor<LHS, RHS, Num>(&mut self, lhs: LHS, rhs: RHS) -> Self::Output
     where
-        LHS: mem::Read<Out = Num> + mem::Write<In = Num>,
-        RHS: mem::Read<Out = Num>,
+        LHS: mem::Read<Out = Num> + mem::Write<In = Num> + Operand,
+        RHS: mem::Read<Out = Num> + Operand,
         Num: Integer,
     {
         self.binary_op(lhs, rhs, |x, y, mut f| {
             let res = x ^ y;
             f[flag::Z].set_bool(res == Num::zero());
+            f[flag::N].reset();
+            f[flag::H].reset();
+            f[flag::C].reset();
             (res, f)
         })
     }
 
     fn and<LHS, RHS, Num>(&mut self, lhs: LHS, rhs: RHS) -> Self::Output
     where
-        LHS: mem::Read<Out = Num> + mem::Write<In = Num>,
-        RHS: mem::Read<Out = Num>,
+        LHS: mem::Read<Out = Num> + mem::Write<In = Num> + Operand,
+        RHS: mem::Read<Out = Num> + Operand,
         Num: Integer,
     {
         self.binary_op(lhs, rhs, |x, y, mut f| {
@@ -647,8 +1042,8 @@ trait Instructions {
 
     fn or<LHS, RHS, Num>(&mut self, lhs: LHS, rhs: RHS) -> Self::Output
     where
-        LHS: mem::Read<Out = Num> + mem::Write<In = Num>,
-        RHS: mem::Read<Out = Num>,
+        LHS: mem::Read<Out = Num> + mem::Write<In = Num> + Operand,
+        RHS: mem::Read<Out = Num> + Operand,
         Num: Integer,
     {
         self.binary_op(lhs, rhs, |x, y, mut f| {
@@ -663,8 +1058,8 @@ trait Instructions {
 
     fn cmp<LHS, RHS>(&mut self, lhs: LHS, rhs: RHS) -> Self::Output
     where
-        LHS: mem::Read<Out = u8> + mem::Write<In = u8>,
-        RHS: mem::Read<Out = u8>,
+        LHS: mem::Read<Out = u8> + mem::Write<In = u8> + Operand,
+        RHS: mem::Read<Out = u8> + Operand,
     {
         // Ensure that the result is not written to the LHS
         self.sub(NoWrite(lhs), rhs, Carry::Without)
@@ -672,7 +1067,7 @@ trait Instructions {
 
     fn inc<T>(&mut self, lhs: T) -> Self::Output
     where
-        T: mem::Read<Out = u8> + mem::Write<In = u8>,
+        T: mem::Read<Out = u8> + mem::Write<In = u8> + Operand,
     {
         self.binary_op(lhs, Constant(1), |x, y, mut f| {
             let res = x.wrapping_add(y);
@@ -685,14 +1080,14 @@ trait Instructions {
 
     fn inc16<T>(&mut self, lhs: T) -> Self::Output
     where
-        T: mem::Read<Out = u16> + mem::Write<In = u16>,
+        T: mem::Read<Out = u16> + mem::Write<In = u16> + Operand,
     {
-        self.binary_op(lhs, Constant(1), |x, y, f| (x + y, f))
+        self.binary_op(lhs, Constant(1), |x, y, f| (x.wrapping_add(y), f))
     }
 
     fn dec<T>(&mut self, lhs: T) -> Self::Output
     where
-        T: mem::Read<Out = u8> + mem::Write<In = u8>,
+        T: mem::Read<Out = u8> + mem::Write<In = u8> + Operand,
     {
         self.binary_op(lhs, Constant(1), |x, y, mut f| {
             let res = x.wrapping_sub(y);
@@ -705,14 +1100,208 @@ trait Instructions {
 
     fn dec16<T>(&mut self, lhs: T) -> Self::Output
     where
-        T: mem::Read<Out = u16> + mem::Write<In = u16>,
+        T: mem::Read<Out = u16> + mem::Write<In = u16> + Operand,
+    {
+        self.binary_op(lhs, Constant(1), |x, y, f| (x.wrapping_sub(y), f))
+    }
+
+    /// Shifts `value` one step in `direction`, and sets the flags accordingly. The vacated bit is
+    /// filled by `fill`, which gets the original value and the carry flag as input.
+    fn shift<T, F>(&mut self, value: T, direction: Direction, fill: F) -> Self::Output
+    where
+        T: mem::Read<Out = u8> + mem::Write<In = u8> + Operand,
+        F: FnOnce(u8, bool) -> bool,
+    {
+        self.unary_op(value, |x, mut f| {
+            let (res, carry) = match direction {
+                Direction::Left => (x << 1 | fill(x, f[flag::C].as_bool()) as u8, x & 0x80 != 0),
+                Direction::Right => (
+                    x >> 1 | (fill(x, f[flag::C].as_bool()) as u8) << 7,
+                    x & 0x01 != 0,
+                ),
+            };
+            f[flag::Z].set_bool(res == 0);
+            f[flag::N].reset();
+            f[flag::H].reset();
+            f[flag::C].set_bool(carry);
+            (res, f)
+        })
+    }
+
+    fn rlc<T>(&mut self, value: T) -> Self::Output
+    where
+        T: mem::Read<Out = u8> + mem::Write<In = u8> + Operand,
+    {
+        self.shift(value, Direction::Left, |x, _| x & 0x80 != 0)
+    }
+
+    fn rrc<T>(&mut self, value: T) -> Self::Output
+    where
+        T: mem::Read<Out = u8> + mem::Write<In = u8> + Operand,
+    {
+        self.shift(value, Direction::Right, |x, _| x & 0x01 != 0)
+    }
+
+    fn rl<T>(&mut self, value: T) -> Self::Output
+    where
+        T: mem::Read<Out = u8> + mem::Write<In = u8> + Operand,
+    {
+        self.shift(value, Direction::Left, |_, carry| carry)
+    }
+
+    fn rr<T>(&mut self, value: T) -> Self::Output
+    where
+        T: mem::Read<Out = u8> + mem::Write<In = u8> + Operand,
+    {
+        self.shift(value, Direction::Right, |_, carry| carry)
+    }
+
+    fn sla<T>(&mut self, value: T) -> Self::Output
+    where
+        T: mem::Read<Out = u8> + mem::Write<In = u8> + Operand,
+    {
+        self.shift(value, Direction::Left, |_, _| false)
+    }
+
+    fn sra<T>(&mut self, value: T) -> Self::Output
+    where
+        T: mem::Read<Out = u8> + mem::Write<In = u8> + Operand,
+    {
+        // The sign bit is kept intact, i.e. it's an arithmetic shift.
+        self.shift(value, Direction::Right, |x, _| x & 0x80 != 0)
+    }
+
+    fn srl<T>(&mut self, value: T) -> Self::Output
+    where
+        T: mem::Read<Out = u8> + mem::Write<In = u8> + Operand,
+    {
+        self.shift(value, Direction::Right, |_, _| false)
+    }
+
+    fn swap<T>(&mut self, value: T) -> Self::Output
+    where
+        T: mem::Read<Out = u8> + mem::Write<In = u8> + Operand,
+    {
+        self.unary_op(value, |x, mut f| {
+            let res = x.rotate_left(4);
+            f[flag::Z].set_bool(res == 0);
+            f[flag::N].reset();
+            f[flag::H].reset();
+            f[flag::C].reset();
+            (res, f)
+        })
+    }
+
+    fn bit<T>(&mut self, bit: u8, value: T) -> Self::Output
+    where
+        T: mem::Read<Out = u8> + Operand;
+
+    /// Performs a rotation of register A. These differ from their CB prefixed counterparts in that
+    /// the zero flag is always reset.
+    fn rotate_a<F>(&mut self, rotate: F) -> Self::Output
+    where
+        F: FnOnce(&mut Self) -> Self::Output;
+
+    fn rlca(&mut self) -> Self::Output {
+        self.rotate_a(|gb| gb.rlc(R8::A))
+    }
+
+    fn rrca(&mut self) -> Self::Output {
+        self.rotate_a(|gb| gb.rrc(R8::A))
+    }
+
+    fn rla(&mut self) -> Self::Output {
+        self.rotate_a(|gb| gb.rl(R8::A))
+    }
+
+    fn rra(&mut self) -> Self::Output {
+        self.rotate_a(|gb| gb.rr(R8::A))
+    }
+
+    /// Decimal adjusts register A, turning the result of the previous addition or subtraction of
+    /// two BCD numbers into a BCD number again.
+    fn daa(&mut self) -> Self::Output {
+        self.unary_op(R8::A, |x, mut f| {
+            let mut res = x;
+            let mut carry = f[flag::C].as_bool();
+            if f[flag::N].as_bool() {
+                if carry {
+                    res = res.wrapping_sub(0x60);
+                }
+                if f[flag::H].as_bool() {
+                    res = res.wrapping_sub(0x06);
+                }
+            } else {
+                if carry || x > 0x99 {
+                    res = res.wrapping_add(0x60);
+                    carry = true;
+                }
+                if f[flag::H].as_bool() || x & 0x0F > 0x09 {
+                    res = res.wrapping_add(0x06);
+                }
+            }
+            f[flag::Z].set_bool(res == 0);
+            f[flag::H].reset();
+            f[flag::C].set_bool(carry);
+            (res, f)
+        })
+    }
+
+    fn cpl(&mut self) -> Self::Output {
+        self.unary_op(R8::A, |x, mut f| {
+            f[flag::N].set();
+            f[flag::H].set();
+            (!x, f)
+        })
+    }
+
+    fn scf(&mut self) -> Self::Output {
+        self.unary_op(R8::A, |x, mut f| {
+            f[flag::N].reset();
+            f[flag::H].reset();
+            f[flag::C].set();
+            (x, f)
+        })
+    }
+
+    fn ccf(&mut self) -> Self::Output {
+        self.unary_op(R8::A, |x, mut f| {
+            f[flag::N].reset();
+            f[flag::H].reset();
+            f[flag::C].toggle();
+            (x, f)
+        })
+    }
+
+    fn res<T>(&mut self, bit: u8, value: T) -> Self::Output
+    where
+        T: mem::Read<Out = u8> + mem::Write<In = u8> + Operand,
+    {
+        self.unary_op(value, |x, f| (x & !(1 << bit), f))
+    }
+
+    fn set<T>(&mut self, bit: u8, value: T) -> Self::Output
+    where
+        T: mem::Read<Out = u8> + mem::Write<In = u8> + Operand,
     {
-        self.binary_op(lhs, Constant(1), |x, y, f| (x - y, f))
+        self.unary_op(value, |x, f| (x | 1 << bit, f))
     }
 
     fn push<R>(&mut self, from: R) -> Self::Output
     where
         R: mem::Read<Out = u16>;
+
+    fn pop<W>(&mut self, to: W) -> Self::Output
+    where
+        W: mem::Write<In = u16>;
+
+    fn call<R>(&mut self, _: Flags, _: R) -> Self::Output
+    where
+        R: mem::Read<Out = u16>;
+
+    fn ret(&mut self, _: Flags) -> Self::Output;
+    fn reti(&mut self) -> Self::Output;
+    fn rst(&mut self, vector: u8) -> Self::Output;
     fn nop(&mut self) -> Self::Output;
     fn halt(&mut self) -> Self::Output;
     fn stop(&mut self) -> Self::Output;
@@ -723,18 +1312,18 @@ impl Instructions for GameBoy {
 
     fn load<W, R, V>(&mut self, to: W, from: R) -> Self::Output
     where
-        W: mem::Write<In = V>,
-        R: mem::Read<Out = V>,
+        W: mem::Write<In = V> + Operand,
+        R: mem::Read<Out = V> + Operand,
     {
         let value: V = from.read(self);
         to.write(self, value)?;
-        Ok(1)
+        Ok(1 + W::CYCLES + R::CYCLES)
     }
 
     fn binary_op<LHS, RHS, F, Num>(&mut self, lhs: LHS, rhs: RHS, op: F) -> Self::Output
     where
-        LHS: mem::Read<Out = Num> + mem::Write<In = Num>,
-        RHS: mem::Read<Out = Num>,
+        LHS: mem::Read<Out = Num> + mem::Write<In = Num> + Operand,
+        RHS: mem::Read<Out = Num> + Operand,
         F: FnOnce(Num, Num, RegisterF) -> (Num, RegisterF),
         Num: Integer,
     {
@@ -743,37 +1332,100 @@ impl Instructions for GameBoy {
         let (result, f) = op(lhs_, rhs_, *(self.cpu.register.f()));
         *(self.cpu.register.f()) = f;
         lhs.write(self, result)?;
-        Ok(Num::CYCLES)
+        // An operand in memory is read and then written back, e.g. by `INC (HL)`.
+        Ok(Num::CYCLES + RHS::CYCLES + 2 * LHS::CYCLES)
     }
 
-    fn jump<Offset, V>(&mut self, flags: Flags, offset: Offset) -> Self::Output
+    fn unary_op<T, F>(&mut self, value: T, op: F) -> Self::Output
     where
-        V: Into<u16>,
-        Offset: mem::Read<Out = V>,
+        T: mem::Read<Out = u8> + mem::Write<In = u8> + Operand,
+        F: FnOnce(u8, RegisterF) -> (u8, RegisterF),
     {
-        use self::Flags::*;
-        let offset_ = offset.read(self);
-        let f = *(self.cpu.register.f());
-        if match flags {
-            Always => true,
-            Z => f[flag::Z].as_bool(),
-            N => f[flag::N].as_bool(),
-            H => f[flag::H].as_bool(),
-            C => f[flag::C].as_bool(),
-            NZ => !f[flag::Z].as_bool(),
-            NN => !f[flag::N].as_bool(),
-            NH => !f[flag::H].as_bool(),
-            NC => !f[flag::C].as_bool(),
-        } {
-            *self.cpu.register.pc += offset_.into();
+        let value_ = value.read(self);
+        let (result, f) = op(value_, *(self.cpu.register.f()));
+        *(self.cpu.register.f()) = f;
+        value.write(self, result)?;
+        Ok(1 + 2 * T::CYCLES)
+    }
+
+    fn jump<R>(&mut self, flags: Flags, addr: R) -> Self::Output
+    where
+        R: mem::Read<Out = u16>,
+    {
+        let addr = addr.read(self);
+        if flags.test(*(self.cpu.register.f())) {
+            *self.cpu.register.pc = addr;
+            Ok(4)
+        } else {
+            Ok(3)
+        }
+    }
+
+    fn jump_relative<R>(&mut self, flags: Flags, offset: R) -> Self::Output
+    where
+        R: mem::Read<Out = i8>,
+    {
+        // The displacement is relative to the address following the instruction.
+        let offset = offset.read(self);
+        if flags.test(*(self.cpu.register.f())) {
+            let pc: &mut u16 = &mut self.cpu.register.pc;
+            *pc = pc.wrapping_add(offset as u16);
             Ok(3)
         } else {
             Ok(2)
         }
     }
 
-    fn set_interrupt(&mut self, _: Interrupt) -> Self::Output {
-        unimplemented!()
+    fn offset_sp<W, R>(&mut self, to: W, offset: R) -> Self::Output
+    where
+        W: mem::Write<In = u16>,
+        R: mem::Read<Out = i8>,
+    {
+        let sp = *self.cpu.register.sp;
+        let offset = offset.read(self) as u16;
+        let res = sp.wrapping_add(offset);
+        let f = self.cpu.register.f();
+        f[flag::Z].reset();
+        f[flag::N].reset();
+        f[flag::H].set_bool((sp ^ offset ^ res) & 0x10 != 0);
+        f[flag::C].set_bool((sp ^ offset ^ res) & 0x100 != 0);
+        to.write(self, res)?;
+        Ok(3)
+    }
+
+    fn set_interrupt(&mut self, interrupt: Interrupt) -> Self::Output {
+        match interrupt {
+            // IME is set only after the instruction following EI has been executed.
+            Interrupt::Enable => self.cpu.ime_delay = 2,
+            Interrupt::Disable => {
+                self.cpu.ime = false;
+                self.cpu.ime_delay = 0;
+            }
+        }
+        Ok(1)
+    }
+
+    fn rotate_a<F>(&mut self, rotate: F) -> Self::Output
+    where
+        F: FnOnce(&mut Self) -> Self::Output,
+    {
+        let cycles = rotate(self)?;
+        // Unlike the CB rotations, these always reset Z, even if A ends up being 0.
+        self.cpu.register.f()[flag::Z].reset();
+        Ok(cycles)
+    }
+
+    fn bit<T>(&mut self, bit: u8, value: T) -> Self::Output
+    where
+        T: mem::Read<Out = u8> + Operand,
+    {
+        let value = value.read(self);
+        let f = self.cpu.register.f();
+        f[flag::Z].set_bool(value & (1 << bit) == 0);
+        f[flag::N].reset();
+        f[flag::H].set();
+        // BIT only reads its operand, so there's no write back to pay for.
+        Ok(1 + T::CYCLES)
     }
 
     fn push<R>(&mut self, from: R) -> Self::Output
@@ -781,9 +1433,58 @@ impl Instructions for GameBoy {
         R: mem::Read<Out = u16>,
     {
         let from = from.read(self);
-        let sp: &mut u16 = &mut self.cpu.register.sp;
-        self.mmu.write_u16(*sp, from);
-        *sp += 2;
+        self.push_u16(from);
+        Ok(4)
+    }
+
+    fn pop<W>(&mut self, to: W) -> Self::Output
+    where
+        W: mem::Write<In = u16>,
+    {
+        let value = self.pop_u16();
+        to.write(self, value)?;
+        Ok(3)
+    }
+
+    fn call<R>(&mut self, flags: Flags, addr: R) -> Self::Output
+    where
+        R: mem::Read<Out = u16>,
+    {
+        // The address is always read, regardless of whether the call is made or not.
+        let addr = addr.read(self);
+        if flags.test(*(self.cpu.register.f())) {
+            let pc = *self.cpu.register.pc;
+            self.push_u16(pc);
+            *self.cpu.register.pc = addr;
+            Ok(6)
+        } else {
+            Ok(3)
+        }
+    }
+
+    fn ret(&mut self, flags: Flags) -> Self::Output {
+        let taken = flags.test(*(self.cpu.register.f()));
+        if taken {
+            *self.cpu.register.pc = self.pop_u16();
+        }
+        // Conditional returns spend an extra cycle evaluating the condition.
+        Ok(match (flags, taken) {
+            (Flags::Always, _) => 4,
+            (_, true) => 5,
+            (_, false) => 2,
+        })
+    }
+
+    fn reti(&mut self) -> Self::Output {
+        // Unlike EI, RETI enables interrupts immediately.
+        self.cpu.ime = true;
+        self.ret(Flags::Always)
+    }
+
+    fn rst(&mut self, vector: u8) -> Self::Output {
+        let pc = *self.cpu.register.pc;
+        self.push_u16(pc);
+        *self.cpu.register.pc = u16::from(vector);
         Ok(4)
     }
 
@@ -792,10 +1493,574 @@ impl Instructions for GameBoy {
     }
 
     fn halt(&mut self) -> Self::Output {
-        unimplemented!()
+        if !self.cpu.ime && self.mmu.pending_interrupts() != 0 {
+            // The HALT bug: the CPU doesn't halt at all, but the next opcode is read twice.
+            self.cpu.halt_bug = true;
+        } else {
+            self.cpu.mode = Mode::Halted;
+        }
+        Ok(1)
     }
 
     fn stop(&mut self) -> Self::Output {
-        unimplemented!()
+        // STOP is followed by a byte which is skipped.
+        Immediate8.read(self);
+        if self.mmu.speed_switch_armed() {
+            // STOP is how the CGB performs a speed switch requested through KEY1.
+            self.mmu.switch_speed();
+        } else {
+            self.cpu.mode = Mode::Stopped;
+            self.mmu.stop();
+        }
+        Ok(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::boot::Model;
+    use crate::gameboy::mem::{Bus, BusExt, Write};
+
+    fn load_program(gb: &mut GameBoy, program: &[u8]) {
+        load_program_at(gb, 0, program);
+    }
+
+    fn load_program_at(gb: &mut GameBoy, start: u16, program: &[u8]) {
+        for (offset, byte) in program.iter().enumerate() {
+            gb.mmu.write_u8(start + offset as u16, *byte);
+        }
+    }
+
+    #[test]
+    fn test_cb_rotate_and_shift() {
+        let mut gb: GameBoy = Default::default();
+        // RLC B; RR B; SRA B; SWAP B
+        load_program(&mut gb, &[0xCB, 0x00, 0xCB, 0x18, 0xCB, 0x28, 0xCB, 0x30]);
+        R8::B.write(&mut gb, 0b1000_0001).unwrap();
+
+        assert_eq!(2, gb.step().unwrap());
+        assert_eq!(0b0000_0011, gb.cpu.register.b());
+        assert!(gb.cpu.register.f()[flag::C].as_bool());
+
+        gb.step().unwrap();
+        assert_eq!(0b1000_0001, gb.cpu.register.b());
+        assert!(gb.cpu.register.f()[flag::C].as_bool());
+
+        gb.step().unwrap();
+        assert_eq!(0b1100_0000, gb.cpu.register.b());
+        assert!(gb.cpu.register.f()[flag::C].as_bool());
+
+        gb.step().unwrap();
+        assert_eq!(0b0000_1100, gb.cpu.register.b());
+        assert!(!gb.cpu.register.f()[flag::C].as_bool());
+        assert_eq!(8, *gb.cpu.register.pc);
+    }
+
+    #[test]
+    fn test_cb_zero_flag() {
+        let mut gb: GameBoy = Default::default();
+        // SRL A
+        load_program(&mut gb, &[0xCB, 0x3F]);
+        R8::A.write(&mut gb, 0b0000_0001).unwrap();
+        gb.step().unwrap();
+        assert_eq!(0, gb.cpu.register.a());
+        assert!(gb.cpu.register.f()[flag::Z].as_bool());
+        assert!(gb.cpu.register.f()[flag::C].as_bool());
+    }
+
+    #[test]
+    fn test_cb_bit() {
+        let mut gb: GameBoy = Default::default();
+        // BIT 7,H; BIT 0,H
+        load_program(&mut gb, &[0xCB, 0x7C, 0xCB, 0x44]);
+        R8::H.write(&mut gb, 0b0000_0001).unwrap();
+        gb.cpu.register.f()[flag::C].set();
+
+        gb.step().unwrap();
+        assert!(gb.cpu.register.f()[flag::Z].as_bool());
+        assert!(gb.cpu.register.f()[flag::H].as_bool());
+        assert!(gb.cpu.register.f()[flag::C].as_bool());
+
+        gb.step().unwrap();
+        assert!(!gb.cpu.register.f()[flag::Z].as_bool());
+        assert_eq!(0b0000_0001, gb.cpu.register.h());
+    }
+
+    #[test]
+    fn test_cb_indirect_hl() {
+        let mut gb: GameBoy = Default::default();
+        // SET 3,(HL); RES 0,(HL); BIT 3,(HL)
+        load_program(&mut gb, &[0xCB, 0xDE, 0xCB, 0x86, 0xCB, 0x5E]);
+        *gb.cpu.register.hl = 0x1000;
+        gb.mmu.write_u8(0x1000, 0b0000_0001);
+        let f = *gb.cpu.register.f();
+
+        assert_eq!(4, gb.step().unwrap());
+        assert_eq!(0b0000_1001, gb.mmu.read_u8(0x1000));
+        assert_eq!(4, gb.step().unwrap());
+        assert_eq!(0b0000_1000, gb.mmu.read_u8(0x1000));
+        assert_eq!(f, *gb.cpu.register.f());
+        assert_eq!(3, gb.step().unwrap());
+        assert!(!gb.cpu.register.f()[flag::Z].as_bool());
+    }
+
+    #[test]
+    fn test_push_pop() {
+        let mut gb: GameBoy = Default::default();
+        // PUSH BC; POP DE
+        load_program(&mut gb, &[0xC5, 0xD1]);
+        *gb.cpu.register.sp = 0x2000;
+        *gb.cpu.register.bc = 0x1234;
+
+        assert_eq!(4, gb.step().unwrap());
+        assert_eq!(0x1FFE, *gb.cpu.register.sp);
+        assert_eq!(0x34, gb.mmu.read_u8(0x1FFE));
+        assert_eq!(0x12, gb.mmu.read_u8(0x1FFF));
+
+        assert_eq!(3, gb.step().unwrap());
+        assert_eq!(0x2000, *gb.cpu.register.sp);
+        assert_eq!(0x1234, *gb.cpu.register.de);
+    }
+
+    #[test]
+    fn test_pop_af_masks_f() {
+        let mut gb: GameBoy = Default::default();
+        // PUSH BC; POP AF
+        load_program(&mut gb, &[0xC5, 0xF1]);
+        *gb.cpu.register.sp = 0x2000;
+        *gb.cpu.register.bc = 0x12FF;
+        gb.step().unwrap();
+        gb.step().unwrap();
+        assert_eq!(0x12F0, *gb.cpu.register.af);
+    }
+
+    #[test]
+    fn test_call_ret() {
+        let mut gb: GameBoy = Default::default();
+        // CALL 0x0101; ...; 0x0101: RET NZ; RET Z
+        load_program(&mut gb, &[0xCD, 0x01, 0x01]);
+        gb.mmu.write_u8(0x0101, 0xC0);
+        gb.mmu.write_u8(0x0102, 0xC8);
+        *gb.cpu.register.sp = 0x2000;
+        gb.cpu.register.f()[flag::Z].set();
+
+        assert_eq!(6, gb.step().unwrap());
+        assert_eq!(0x0101, *gb.cpu.register.pc);
+        assert_eq!(0x1FFE, *gb.cpu.register.sp);
+
+        assert_eq!(2, gb.step().unwrap());
+        assert_eq!(0x0102, *gb.cpu.register.pc);
+
+        assert_eq!(5, gb.step().unwrap());
+        assert_eq!(0x0003, *gb.cpu.register.pc);
+        assert_eq!(0x2000, *gb.cpu.register.sp);
+    }
+
+    #[test]
+    fn test_call_not_taken() {
+        let mut gb: GameBoy = Default::default();
+        // CALL C,0x1234
+        load_program(&mut gb, &[0xDC, 0x34, 0x12]);
+        *gb.cpu.register.sp = 0x2000;
+        assert_eq!(3, gb.step().unwrap());
+        assert_eq!(0x0003, *gb.cpu.register.pc);
+        assert_eq!(0x2000, *gb.cpu.register.sp);
+    }
+
+    #[test]
+    fn test_rst() {
+        let mut gb: GameBoy = Default::default();
+        // NOP; RST 0x28
+        load_program(&mut gb, &[0x00, 0xEF]);
+        *gb.cpu.register.sp = 0x2000;
+        gb.step().unwrap();
+        assert_eq!(4, gb.step().unwrap());
+        assert_eq!(0x0028, *gb.cpu.register.pc);
+        assert_eq!(0x02, gb.mmu.read_u8(0x1FFE));
+        assert_eq!(0x00, gb.mmu.read_u8(0x1FFF));
+    }
+
+    #[test]
+    fn test_halt_until_interrupt() {
+        let mut gb: GameBoy = Default::default();
+        // HALT; NOP
+        load_program(&mut gb, &[0x76, 0x00]);
+        gb.mmu.write_u8(0xFFFF, 0x01);
+
+        gb.step().unwrap();
+        assert_eq!(Mode::Halted, gb.cpu.mode);
+        gb.step().unwrap();
+        assert_eq!(0x0001, *gb.cpu.register.pc);
+
+        // Without IME the CPU just resumes after HALT.
+        gb.mmu.write_u8(0xFF0F, 0x01);
+        gb.step().unwrap();
+        assert_eq!(Mode::Running, gb.cpu.mode);
+        assert_eq!(0x0002, *gb.cpu.register.pc);
+    }
+
+    #[test]
+    fn test_halt_services_interrupt_with_ime() {
+        let mut gb: GameBoy = Default::default();
+        // HALT
+        load_program(&mut gb, &[0x76]);
+        *gb.cpu.register.sp = 0x2000;
+        gb.cpu.ime = true;
+        gb.mmu.write_u8(0xFFFF, 0x04);
+
+        gb.step().unwrap();
+        gb.mmu.write_u8(0xFF0F, 0x04);
+        assert_eq!(5, gb.step().unwrap());
+        assert_eq!(0x50, *gb.cpu.register.pc);
+        assert_eq!(0x01, gb.mmu.read_u8(0x1FFE));
+    }
+
+    #[test]
+    fn test_halt_bug() {
+        let mut gb: GameBoy = Default::default();
+        // HALT; INC A
+        load_program(&mut gb, &[0x76, 0x3C]);
+        gb.mmu.write_u8(0xFFFF, 0x01);
+        gb.mmu.write_u8(0xFF0F, 0x01);
+
+        gb.step().unwrap();
+        assert_eq!(Mode::Running, gb.cpu.mode);
+        gb.step().unwrap();
+        gb.step().unwrap();
+        assert_eq!(2, gb.cpu.register.a());
+        assert_eq!(0x0002, *gb.cpu.register.pc);
+    }
+
+    #[test]
+    fn test_halt_bug_with_ei() {
+        let mut gb: GameBoy = Default::default();
+        // EI; HALT; NOP
+        load_program(&mut gb, &[0xFB, 0x76, 0x00]);
+        // INC B; RETI
+        load_program_at(&mut gb, 0x40, &[0x04, 0xD9]);
+        *gb.cpu.register.sp = 0x2000;
+        gb.mmu.write_u8(0xFFFF, 0x01);
+        gb.mmu.write_u8(0xFF0F, 0x01);
+
+        gb.step().unwrap();
+        gb.step().unwrap();
+        assert_eq!(5, gb.step().unwrap());
+        assert_eq!(0x0040, *gb.cpu.register.pc);
+        gb.step().unwrap();
+        gb.step().unwrap();
+        assert_eq!(1, gb.cpu.register.b());
+        // The HALT is executed again, and now actually halts.
+        assert_eq!(0x0001, *gb.cpu.register.pc);
+        gb.step().unwrap();
+        assert_eq!(Mode::Halted, gb.cpu.mode);
+        assert_eq!(0x0002, *gb.cpu.register.pc);
+    }
+
+    #[test]
+    fn test_stop() {
+        let mut gb: GameBoy = Default::default();
+        // STOP; NOP
+        load_program(&mut gb, &[0x10, 0x00, 0x00]);
+        gb.step().unwrap();
+        assert_eq!(Mode::Stopped, gb.cpu.mode);
+        gb.step().unwrap();
+        assert_eq!(0x0002, *gb.cpu.register.pc);
+
+        gb.joypad_input();
+        gb.step().unwrap();
+        assert_eq!(0x0003, *gb.cpu.register.pc);
+    }
+
+    #[test]
+    fn test_stop_timer() {
+        let mut gb: GameBoy = Default::default();
+        // STOP
+        load_program(&mut gb, &[0x10, 0x00]);
+        gb.mmu.write_u8(0xFF07, 0x05);
+        gb.mmu.tick(200);
+        assert_ne!(0, gb.mmu.read_u8(0xFF04));
+
+        gb.step().unwrap();
+        assert_eq!(0, gb.mmu.read_u8(0xFF04));
+        let tima = gb.mmu.read_u8(0xFF05);
+        for _ in 0..100 {
+            gb.step().unwrap();
+        }
+        assert_eq!(0, gb.mmu.read_u8(0xFF04));
+        assert_eq!(tima, gb.mmu.read_u8(0xFF05));
+
+        gb.joypad_input();
+        gb.mmu.tick(100);
+        assert_ne!(0, gb.mmu.read_u8(0xFF04));
+    }
+
+    #[test]
+    fn test_stop_speed_switch() {
+        let mut gb: GameBoy = Default::default();
+        gb.mmu.set_model(Model::Cgb);
+        // STOP
+        load_program(&mut gb, &[0x10, 0x00]);
+        gb.mmu.write_u8(0xFF4D, 0x01);
+        gb.step().unwrap();
+        assert_eq!(Mode::Running, gb.cpu.mode);
+        assert_eq!(0xFE, gb.mmu.read_u8(0xFF4D));
+    }
+
+    #[test]
+    fn test_stop_without_speed_switch() {
+        let mut gb: GameBoy = Default::default();
+        // STOP
+        load_program(&mut gb, &[0x10, 0x00]);
+        gb.mmu.write_u8(0xFF4D, 0x01);
+        assert_eq!(0xFF, gb.mmu.read_u8(0xFF4D));
+        gb.step().unwrap();
+        assert_eq!(Mode::Stopped, gb.cpu.mode);
+        assert!(!gb.mmu.double_speed());
+    }
+
+    #[test]
+    fn test_rotate_a_resets_zero_flag() {
+        let mut gb: GameBoy = Default::default();
+        // RLCA; RRA; RRCA; RLA
+        load_program(&mut gb, &[0x07, 0x1F, 0x0F, 0x17]);
+        R8::A.write(&mut gb, 0b1000_0000).unwrap();
+
+        assert_eq!(1, gb.step().unwrap());
+        assert_eq!(0b0000_0001, gb.cpu.register.a());
+        assert!(gb.cpu.register.f()[flag::C].as_bool());
+
+        gb.step().unwrap();
+        assert_eq!(0b1000_0000, gb.cpu.register.a());
+        assert!(gb.cpu.register.f()[flag::C].as_bool());
+
+        gb.step().unwrap();
+        assert_eq!(0b0100_0000, gb.cpu.register.a());
+        assert!(!gb.cpu.register.f()[flag::C].as_bool());
+
+        R8::A.write(&mut gb, 0b1000_0000).unwrap();
+        gb.step().unwrap();
+        assert_eq!(0, gb.cpu.register.a());
+        assert!(!gb.cpu.register.f()[flag::Z].as_bool());
+        assert!(gb.cpu.register.f()[flag::C].as_bool());
+    }
+
+    #[test]
+    fn test_daa() {
+        // (a, b, op) where op is an ADD (0x80) or a SUB (0x90) of B from A, followed by DAA.
+        let cases = [
+            (0x15, 0x27, 0x80, 0x42, false),
+            (0x99, 0x01, 0x80, 0x00, true),
+            (0x58, 0x46, 0x80, 0x04, true),
+            (0x09, 0x09, 0x80, 0x18, false),
+            (0x42, 0x15, 0x90, 0x27, false),
+            (0x10, 0x01, 0x90, 0x09, false),
+            (0x00, 0x01, 0x90, 0x99, true),
+        ];
+        for &(a, b, op, expected, carry) in cases.iter() {
+            let mut gb: GameBoy = Default::default();
+            load_program(&mut gb, &[op, 0x27]);
+            R8::A.write(&mut gb, a).unwrap();
+            R8::B.write(&mut gb, b).unwrap();
+            gb.step().unwrap();
+            gb.step().unwrap();
+            assert_eq!(expected, gb.cpu.register.a(), "{:02X} {:02X}", a, b);
+            assert_eq!(carry, gb.cpu.register.f()[flag::C].as_bool());
+            assert_eq!(expected == 0, gb.cpu.register.f()[flag::Z].as_bool());
+            assert!(!gb.cpu.register.f()[flag::H].as_bool());
+        }
+    }
+
+    #[test]
+    fn test_cpl_scf_ccf() {
+        let mut gb: GameBoy = Default::default();
+        // CPL; SCF; CCF
+        load_program(&mut gb, &[0x2F, 0x37, 0x3F]);
+        R8::A.write(&mut gb, 0b1010_0101).unwrap();
+
+        gb.step().unwrap();
+        assert_eq!(0b0101_1010, gb.cpu.register.a());
+        assert!(gb.cpu.register.f()[flag::N].as_bool());
+        assert!(gb.cpu.register.f()[flag::H].as_bool());
+
+        gb.step().unwrap();
+        assert!(gb.cpu.register.f()[flag::C].as_bool());
+        assert!(!gb.cpu.register.f()[flag::N].as_bool());
+        assert!(!gb.cpu.register.f()[flag::H].as_bool());
+
+        gb.step().unwrap();
+        assert!(!gb.cpu.register.f()[flag::C].as_bool());
+        assert_eq!(0b0101_1010, gb.cpu.register.a());
+    }
+
+    #[test]
+    fn test_jump_relative() {
+        let mut gb: GameBoy = Default::default();
+        // 0x0000: JR +2; ...; 0x0004: JR NZ,-6
+        load_program(&mut gb, &[0x18, 0x02, 0x00, 0x00, 0x20, 0xFA]);
+        assert_eq!(3, gb.step().unwrap());
+        assert_eq!(0x0004, *gb.cpu.register.pc);
+        assert_eq!(3, gb.step().unwrap());
+        assert_eq!(0x0000, *gb.cpu.register.pc);
+
+        gb.cpu.register.f()[flag::Z].set();
+        *gb.cpu.register.pc = 0x0004;
+        assert_eq!(2, gb.step().unwrap());
+        assert_eq!(0x0006, *gb.cpu.register.pc);
+    }
+
+    #[test]
+    fn test_jump_hl() {
+        let mut gb: GameBoy = Default::default();
+        // JP HL
+        load_program(&mut gb, &[0xE9]);
+        *gb.cpu.register.hl = 0x1234;
+        assert_eq!(1, gb.step().unwrap());
+        assert_eq!(0x1234, *gb.cpu.register.pc);
+    }
+
+    #[test]
+    fn test_add_sp() {
+        let mut gb: GameBoy = Default::default();
+        // ADD SP,-1; ADD SP,+1
+        load_program(&mut gb, &[0xE8, 0xFF, 0xE8, 0x01]);
+        *gb.cpu.register.sp = 0x1000;
+        *gb.cpu.register.af = 0x0F80;
+
+        assert_eq!(4, gb.step().unwrap());
+        assert_eq!(0x0FFF, *gb.cpu.register.sp);
+        assert_eq!(0x0F00, *gb.cpu.register.af);
+
+        gb.step().unwrap();
+        assert_eq!(0x1000, *gb.cpu.register.sp);
+        assert!(gb.cpu.register.f()[flag::H].as_bool());
+        assert!(gb.cpu.register.f()[flag::C].as_bool());
+    }
+
+    #[test]
+    fn test_load_hl_sp_offset() {
+        let mut gb: GameBoy = Default::default();
+        // LD HL,SP-2
+        load_program(&mut gb, &[0xF8, 0xFE]);
+        *gb.cpu.register.sp = 0x0001;
+
+        assert_eq!(3, gb.step().unwrap());
+        assert_eq!(0xFFFF, *gb.cpu.register.hl);
+        assert_eq!(0x0001, *gb.cpu.register.sp);
+        assert!(!gb.cpu.register.f()[flag::H].as_bool());
+        assert!(!gb.cpu.register.f()[flag::C].as_bool());
+    }
+
+    #[test]
+    fn test_load_immediate16() {
+        let mut gb: GameBoy = Default::default();
+        // LD BC,0x1234; LD SP,0xCFFF
+        load_program(&mut gb, &[0x01, 0x34, 0x12, 0x31, 0xFF, 0xCF]);
+        gb.step().unwrap();
+        assert_eq!(0x1234, *gb.cpu.register.bc);
+        gb.step().unwrap();
+        assert_eq!(0xCFFF, *gb.cpu.register.sp);
+        assert_eq!(0x0006, *gb.cpu.register.pc);
+    }
+
+    #[test]
+    fn test_inc_dec16_wrap() {
+        let mut gb: GameBoy = Default::default();
+        // INC BC; DEC DE
+        load_program(&mut gb, &[0x03, 0x1B]);
+        *gb.cpu.register.bc = 0xFFFF;
+        *gb.cpu.register.de = 0x0000;
+        gb.step().unwrap();
+        assert_eq!(0x0000, *gb.cpu.register.bc);
+        gb.step().unwrap();
+        assert_eq!(0xFFFF, *gb.cpu.register.de);
+    }
+
+    #[test]
+    fn test_load_post_inc_dec_wrap() {
+        let mut gb: GameBoy = Default::default();
+        // LD (HL+),A; LD (HL-),A
+        load_program(&mut gb, &[0x22, 0x32]);
+        *gb.cpu.register.hl = 0xFFFF;
+        gb.step().unwrap();
+        assert_eq!(0x0000, *gb.cpu.register.hl);
+        gb.step().unwrap();
+        assert_eq!(0xFFFF, *gb.cpu.register.hl);
+    }
+
+    #[test]
+    fn test_pc_wrap() {
+        let mut gb: GameBoy = Default::default();
+        // NOP, in IE
+        gb.mmu.write_u8(0xFFFF, 0x00);
+        *gb.cpu.register.pc = 0xFFFF;
+        gb.step().unwrap();
+        assert_eq!(0x0000, *gb.cpu.register.pc);
+    }
+
+    #[test]
+    fn test_store_sp() {
+        let mut gb: GameBoy = Default::default();
+        // LD (0xC123),SP
+        load_program(&mut gb, &[0x08, 0x23, 0xC1]);
+        *gb.cpu.register.sp = 0xBEEF;
+        gb.step().unwrap();
+        assert_eq!(0xEF, gb.mmu.read_u8(0xC123));
+        assert_eq!(0xBE, gb.mmu.read_u8(0xC124));
+    }
+
+    #[test]
+    fn test_push_pop_round_trip() {
+        let mut gb: GameBoy = Default::default();
+        // PUSH HL; POP BC; CALL 0x1234
+        load_program(&mut gb, &[0xE5, 0xC1, 0xCD, 0x34, 0x12]);
+        *gb.cpu.register.sp = 0xFFFE;
+        *gb.cpu.register.hl = 0xABCD;
+
+        gb.step().unwrap();
+        assert_eq!(0xABCD, gb.mmu.read_u16(0xFFFC));
+        gb.step().unwrap();
+        assert_eq!(0xABCD, *gb.cpu.register.bc);
+
+        gb.step().unwrap();
+        assert_eq!(0x1234, *gb.cpu.register.pc);
+        assert_eq!(0x0005, gb.mmu.read_u16(0xFFFC));
+    }
+
+    #[test]
+    fn test_high_page() {
+        let mut gb: GameBoy = Default::default();
+        // LDH (0x80),A; LD C,0x81; LD (C),A; LDH A,(0x80); LD A,(C)
+        load_program(&mut gb, &[0xE0, 0x80, 0x0E, 0x81, 0xE2, 0xF0, 0x80, 0xF2]);
+        R8::A.write(&mut gb, 0x42).unwrap();
+        gb.step().unwrap();
+        assert_eq!(0x42, gb.mmu.read_u8(0xFF80));
+
+        gb.step().unwrap();
+        gb.step().unwrap();
+        assert_eq!(0x42, gb.mmu.read_u8(0xFF81));
+
+        gb.mmu.write_u8(0xFF80, 0x24);
+        gb.step().unwrap();
+        assert_eq!(0x24, gb.cpu.register.a());
+
+        gb.mmu.write_u8(0xFF81, 0x18);
+        gb.step().unwrap();
+        assert_eq!(0x18, gb.cpu.register.a());
+    }
+
+    #[test]
+    fn test_illegal_opcode() {
+        let mut gb: GameBoy = Default::default();
+        load_program(&mut gb, &[0x00, 0xDD]);
+        gb.step().unwrap();
+        let err = gb.step().unwrap_err();
+        assert_eq!(
+            Some(&InstrError::IllegalOpcode {
+                opcode: 0xDD,
+                addr: 0x0001,
+            }),
+            err.downcast_ref::<InstrError>()
+        );
     }
 }
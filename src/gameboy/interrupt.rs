@@ -0,0 +1,152 @@
+/// The five interrupt sources of the Game Boy, in order of priority.
+///
+/// Each source has its own bit in the IE (0xFFFF) and IF (0xFF0F) registers, as well as its own
+/// vector which the CPU jumps to when the interrupt is serviced.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) enum Interrupt {
+    VBlank,
+    LcdStat,
+    Timer,
+    Serial,
+    Joypad,
+}
+
+impl Interrupt {
+    /// All interrupts, ordered from highest to lowest priority.
+    pub(crate) const ALL: [Interrupt; 5] = [
+        Interrupt::VBlank,
+        Interrupt::LcdStat,
+        Interrupt::Timer,
+        Interrupt::Serial,
+        Interrupt::Joypad,
+    ];
+
+    /// The interrupt's bit mask in the IE and IF registers.
+    pub(crate) fn mask(self) -> u8 {
+        1 << self as u8
+    }
+
+    /// The address that the CPU jumps to when servicing the interrupt.
+    pub(crate) fn vector(self) -> u16 {
+        0x40 + 8 * self as u16
+    }
+
+    /// Finds the pending interrupt with the highest priority, if any.
+    pub(crate) fn highest_priority(pending: u8) -> Option<Interrupt> {
+        Interrupt::ALL
+            .iter()
+            .cloned()
+            .find(|interrupt| pending & interrupt.mask() != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::mem::Bus;
+    use crate::gameboy::GameBoy;
+
+    #[test]
+    fn test_vectors() {
+        assert_eq!(0x40, Interrupt::VBlank.vector());
+        assert_eq!(0x48, Interrupt::LcdStat.vector());
+        assert_eq!(0x50, Interrupt::Timer.vector());
+        assert_eq!(0x58, Interrupt::Serial.vector());
+        assert_eq!(0x60, Interrupt::Joypad.vector());
+    }
+
+    #[test]
+    fn test_highest_priority() {
+        assert_eq!(None, Interrupt::highest_priority(0));
+        assert_eq!(None, Interrupt::highest_priority(0b1110_0000));
+        assert_eq!(
+            Some(Interrupt::Timer),
+            Interrupt::highest_priority(0b0001_1100)
+        );
+        assert_eq!(
+            Some(Interrupt::VBlank),
+            Interrupt::highest_priority(0b0001_1111)
+        );
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let mut gb: GameBoy = Default::default();
+        *gb.cpu.register.sp = 0x2000;
+        *gb.cpu.register.pc = 0x1234;
+        gb.cpu.ime = true;
+        gb.mmu
+            .write_u8(0xFFFF, Interrupt::Timer.mask() | Interrupt::Serial.mask());
+        gb.mmu.request_interrupt(Interrupt::Serial);
+        gb.mmu.request_interrupt(Interrupt::Timer);
+
+        assert_eq!(5, gb.step().unwrap());
+        assert_eq!(0x50, *gb.cpu.register.pc);
+        assert!(!gb.cpu.ime);
+        assert_eq!(0xE0 | Interrupt::Serial.mask(), gb.mmu.read_u8(0xFF0F));
+        assert_eq!(0x34, gb.mmu.read_u8(0x1FFE));
+        assert_eq!(0x12, gb.mmu.read_u8(0x1FFF));
+    }
+
+    #[test]
+    fn test_no_dispatch_without_ime() {
+        let mut gb: GameBoy = Default::default();
+        gb.mmu.write_u8(0xFFFF, Interrupt::VBlank.mask());
+        gb.mmu.request_interrupt(Interrupt::VBlank);
+        // NOP
+        assert_eq!(1, gb.step().unwrap());
+        assert_eq!(0x0001, *gb.cpu.register.pc);
+    }
+
+    #[test]
+    fn test_ei_delay() {
+        let mut gb: GameBoy = Default::default();
+        *gb.cpu.register.sp = 0x2000;
+        // EI; NOP; NOP
+        gb.mmu.write_u8(0x0000, 0xFB);
+        gb.mmu.write_u8(0xFFFF, Interrupt::Joypad.mask());
+        gb.mmu.request_interrupt(Interrupt::Joypad);
+
+        gb.step().unwrap();
+        assert!(!gb.cpu.ime);
+        gb.step().unwrap();
+        assert!(gb.cpu.ime);
+        assert_eq!(0x0002, *gb.cpu.register.pc);
+        gb.step().unwrap();
+        assert_eq!(0x60, *gb.cpu.register.pc);
+    }
+
+    #[test]
+    fn test_ei_with_ime_set() {
+        let mut gb: GameBoy = Default::default();
+        gb.cpu.ime = true;
+        // EI; NOP
+        gb.mmu.write_u8(0x0000, 0xFB);
+        gb.step().unwrap();
+        assert!(gb.cpu.ime);
+        gb.step().unwrap();
+        assert!(gb.cpu.ime);
+    }
+
+    #[test]
+    fn test_ei_di() {
+        let mut gb: GameBoy = Default::default();
+        // EI; DI; NOP
+        gb.mmu.write_u8(0x0000, 0xFB);
+        gb.mmu.write_u8(0x0001, 0xF3);
+        gb.step().unwrap();
+        gb.step().unwrap();
+        gb.step().unwrap();
+        assert!(!gb.cpu.ime);
+    }
+
+    #[test]
+    fn test_reti_enables_ime() {
+        let mut gb: GameBoy = Default::default();
+        *gb.cpu.register.sp = 0x1FFE;
+        // RETI
+        gb.mmu.write_u8(0x0000, 0xD9);
+        gb.step().unwrap();
+        assert!(gb.cpu.ime);
+    }
+}
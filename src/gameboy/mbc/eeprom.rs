@@ -0,0 +1,256 @@
+/// The number of 16 bit words in the 93LC56.
+const WORDS: usize = 128;
+
+/// The size of the contents in save files.
+pub(crate) const SAVE_SIZE: usize = 2 * WORDS;
+
+/// What the EEPROM is doing between clock pulses.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum State {
+    /// Waiting for a start bit.
+    Idle,
+    /// Shifting in the opcode and address, after the start bit.
+    Command { bits: u16, count: u8 },
+    /// Shifting out a word.
+    Reading { word: u16, count: u8 },
+    /// Shifting in a word to write to `addr`, or to every word.
+    Writing {
+        addr: Option<u8>,
+        word: u16,
+        count: u8,
+    },
+    /// Done with the command, until CS goes low.
+    Done,
+}
+
+/// The 93LC56, a serial EEPROM of 128 16 bit words, as found on MBC7 cartridges.
+///
+/// Commands are shifted in one bit at a time on DI, on the rising edge of CLK while CS is high.
+/// Each one is a start bit, a 2 bit opcode and an 8 bit address, of which the top bit is unused.
+/// Data is shifted out on DO, with the most significant bit first.
+#[derive(Debug)]
+pub(crate) struct Eeprom {
+    words: Box<[u16]>,
+    state: State,
+    write_enabled: bool,
+    cs: bool,
+    clk: bool,
+    di: bool,
+    do_: bool,
+}
+
+impl Eeprom {
+    pub(crate) fn new() -> Eeprom {
+        Eeprom {
+            // Erased memory reads as all ones.
+            words: vec![0xFFFF; WORDS].into_boxed_slice(),
+            state: State::Idle,
+            write_enabled: false,
+            cs: false,
+            clk: false,
+            di: false,
+            do_: true,
+        }
+    }
+
+    /// Reads the pins, with DO in bit 0, DI in bit 1, CLK in bit 6 and CS in bit 7.
+    pub(crate) fn read(&self) -> u8 {
+        (self.cs as u8) << 7 | (self.clk as u8) << 6 | (self.di as u8) << 1 | self.do_ as u8
+    }
+
+    /// Drives the pins, laid out as for `read`.
+    pub(crate) fn write(&mut self, value: u8) {
+        let cs = value & 0x80 != 0;
+        let clk = value & 0x40 != 0;
+        self.di = value & 0x02 != 0;
+        if !cs {
+            self.state = State::Idle;
+        } else if clk && !self.clk {
+            self.clock();
+        }
+        self.cs = cs;
+        self.clk = clk;
+    }
+
+    fn clock(&mut self) {
+        let di = self.di as u16;
+        self.state = match self.state {
+            State::Idle if self.di => State::Command { bits: 0, count: 0 },
+            State::Idle => State::Idle,
+            State::Command { bits, count } if count < 9 => State::Command {
+                bits: bits << 1 | di,
+                count: count + 1,
+            },
+            State::Command { bits, .. } => self.command(bits << 1 | di),
+            State::Reading { word, count } => {
+                self.do_ = word & 0x8000 != 0;
+                if count > 1 {
+                    State::Reading {
+                        word: word << 1,
+                        count: count - 1,
+                    }
+                } else {
+                    State::Done
+                }
+            }
+            State::Writing { addr, word, count } => {
+                let word = word << 1 | di;
+                if count < 15 {
+                    State::Writing {
+                        addr,
+                        word,
+                        count: count + 1,
+                    }
+                } else {
+                    self.store(addr, word);
+                    State::Done
+                }
+            }
+            State::Done => State::Done,
+        };
+    }
+
+    /// Decodes the opcode and the address, which take up the ten bits after the start bit.
+    fn command(&mut self, bits: u16) -> State {
+        let addr = (bits & 0x7F) as u8;
+        match bits >> 8 {
+            0b10 => {
+                // A dummy zero bit comes before the data.
+                self.do_ = false;
+                State::Reading {
+                    word: self.words[usize::from(addr)],
+                    count: 16,
+                }
+            }
+            0b01 => State::Writing {
+                addr: Some(addr),
+                word: 0,
+                count: 0,
+            },
+            0b11 => {
+                if self.write_enabled {
+                    self.words[usize::from(addr)] = 0xFFFF;
+                }
+                self.do_ = true;
+                State::Done
+            }
+            // The extended commands are told apart by the top two address bits.
+            _ => match bits >> 6 & 0b11 {
+                0b11 => {
+                    self.write_enabled = true;
+                    State::Done
+                }
+                0b00 => {
+                    self.write_enabled = false;
+                    State::Done
+                }
+                0b10 => {
+                    if self.write_enabled {
+                        self.words.iter_mut().for_each(|w| *w = 0xFFFF);
+                    }
+                    self.do_ = true;
+                    State::Done
+                }
+                _ => State::Writing {
+                    addr: None,
+                    word: 0,
+                    count: 0,
+                },
+            },
+        }
+    }
+
+    fn store(&mut self, addr: Option<u8>, word: u16) {
+        if self.write_enabled {
+            match addr {
+                Some(addr) => self.words[usize::from(addr)] = word,
+                None => self.words.iter_mut().for_each(|w| *w = word),
+            }
+        }
+        // DO signals that the write has finished, which is immediately.
+        self.do_ = true;
+    }
+
+    /// The contents as little-endian words, for save files.
+    pub(crate) fn save(&self) -> Vec<u8> {
+        self.words.iter().flat_map(|w| w.to_le_bytes()).collect()
+    }
+
+    pub(crate) fn load(&mut self, save: &[u8]) {
+        for (word, bytes) in self.words.iter_mut().zip(save.chunks(2)) {
+            *word = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Clocks in the bits of `value`, from the most significant of `count` bits.
+    fn send(eeprom: &mut Eeprom, value: u32, count: u8) {
+        for i in (0..count).rev() {
+            let di = ((value >> i) & 1) as u8;
+            eeprom.write(0x80 | di << 1);
+            eeprom.write(0xC0 | di << 1);
+        }
+    }
+
+    /// A start bit, followed by the opcode and the address.
+    fn command(opcode: u32, addr: u32) -> u32 {
+        1 << 10 | opcode << 8 | addr
+    }
+
+    fn receive(eeprom: &mut Eeprom) -> u16 {
+        (0..16).fold(0, |word, _| {
+            eeprom.write(0x80);
+            eeprom.write(0xC0);
+            word << 1 | u16::from(eeprom.read() & 0x01)
+        })
+    }
+
+    fn deselect(eeprom: &mut Eeprom) {
+        eeprom.write(0x00);
+    }
+
+    #[test]
+    fn test_write_and_read() {
+        let mut eeprom = Eeprom::new();
+        // Writes are ignored until they are enabled.
+        send(&mut eeprom, command(0b01, 0x03), 11);
+        send(&mut eeprom, 0x1234, 16);
+        deselect(&mut eeprom);
+        assert_eq!(0xFFFF, eeprom.words[3]);
+
+        send(&mut eeprom, command(0b00, 0xC0), 11);
+        deselect(&mut eeprom);
+        send(&mut eeprom, command(0b01, 0x03), 11);
+        send(&mut eeprom, 0x1234, 16);
+        deselect(&mut eeprom);
+        assert_eq!(0x1234, eeprom.words[3]);
+
+        send(&mut eeprom, command(0b10, 0x03), 11);
+        assert_eq!(0, eeprom.read() & 0x01);
+        assert_eq!(0x1234, receive(&mut eeprom));
+        deselect(&mut eeprom);
+
+        send(&mut eeprom, command(0b11, 0x03), 11);
+        deselect(&mut eeprom);
+        assert_eq!(0xFFFF, eeprom.words[3]);
+    }
+
+    #[test]
+    fn test_write_all() {
+        let mut eeprom = Eeprom::new();
+        send(&mut eeprom, command(0b00, 0xC0), 11);
+        deselect(&mut eeprom);
+        send(&mut eeprom, command(0b00, 0x40), 11);
+        send(&mut eeprom, 0xBEEF, 16);
+        deselect(&mut eeprom);
+        assert!(eeprom.words.iter().all(|&w| w == 0xBEEF));
+
+        send(&mut eeprom, command(0b00, 0x80), 11);
+        deselect(&mut eeprom);
+        assert!(eeprom.words.iter().all(|&w| w == 0xFFFF));
+    }
+}
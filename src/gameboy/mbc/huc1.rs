@@ -0,0 +1,100 @@
+use super::{load_ram, read_rom, Mbc, SaveError};
+use crate::gameboy::mem::Bus;
+use crate::gameboy::rom::Cartridge;
+
+const RAM_BANK_SIZE: usize = 0x2000;
+
+/// Hudson's HuC1, which is much like an MBC1 without the large ROM support, but with an infrared
+/// transceiver that can be mapped into 0xA000-0xBFFF in place of the RAM.
+#[derive(Debug)]
+pub(crate) struct HuC1 {
+    rom: Box<[u8]>,
+    ram: Box<[u8]>,
+    /// Whether the infrared transceiver is mapped in instead of the RAM, which is always enabled
+    /// otherwise.
+    infrared_mode: bool,
+    led: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+}
+
+impl HuC1 {
+    pub(crate) fn new(cartridge: Cartridge) -> HuC1 {
+        HuC1 {
+            ram: vec![0u8; cartridge.header.ram_size.min(4 * RAM_BANK_SIZE)].into_boxed_slice(),
+            rom: cartridge.rom,
+            infrared_mode: false,
+            led: false,
+            rom_bank: 1,
+            ram_bank: 0,
+        }
+    }
+
+    fn ram_offset(&self, addr: u16) -> usize {
+        (usize::from(self.ram_bank) * RAM_BANK_SIZE + usize::from(addr - 0xA000)) % self.ram.len()
+    }
+}
+
+impl Bus for HuC1 {
+    fn read_u8(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => read_rom(&self.rom, 0, addr),
+            0x4000..=0x7FFF => read_rom(&self.rom, usize::from(self.rom_bank), addr),
+            // Bit 0 is set when light is received, which it never is without another Game Boy.
+            0xA000..=0xBFFF if self.infrared_mode => 0xC0,
+            0xA000..=0xBFFF if !self.ram.is_empty() => self.ram[self.ram_offset(addr)],
+            _ => 0xFF,
+        }
+    }
+
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.infrared_mode = value & 0x0F == 0x0E,
+            0x2000..=0x3FFF => self.rom_bank = (value & 0x3F).max(1),
+            0x4000..=0x5FFF => self.ram_bank = value & 0x03,
+            0xA000..=0xBFFF if self.infrared_mode => self.led = value & 0x01 != 0,
+            0xA000..=0xBFFF if !self.ram.is_empty() => {
+                let offset = self.ram_offset(addr);
+                self.ram[offset] = value;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Mbc for HuC1 {
+    fn infrared_led(&self) -> bool {
+        self.led
+    }
+
+    fn save(&self) -> Vec<u8> {
+        self.ram.to_vec()
+    }
+
+    fn load_save(&mut self, save: &[u8]) -> Result<(), SaveError> {
+        load_ram(&mut self.ram, save)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::rom::tests::rom;
+
+    #[test]
+    fn test_huc1() {
+        let mut mbc = HuC1::new(Cartridge::from_bytes(rom(0xFF, 0x03, 0x03)).unwrap());
+        mbc.write_u8(0x4000, 0x02);
+        mbc.write_u8(0xA000, 0x42);
+        assert_eq!(0x42, mbc.ram[2 * RAM_BANK_SIZE]);
+
+        mbc.write_u8(0x0000, 0x0E);
+        assert_eq!(0xC0, mbc.read_u8(0xA000));
+        mbc.write_u8(0xA000, 0x01);
+        assert!(mbc.infrared_led());
+        assert_eq!(0x42, mbc.ram[2 * RAM_BANK_SIZE]);
+
+        mbc.write_u8(0x0000, 0x00);
+        assert_eq!(0x42, mbc.read_u8(0xA000));
+    }
+}
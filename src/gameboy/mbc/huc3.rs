@@ -0,0 +1,320 @@
+use std::time::SystemTime;
+
+use super::rtc::{self, RtcClock, Timebase};
+use super::{load_ram, read_rom, Mbc, SaveError};
+use crate::gameboy::mem::Bus;
+use crate::gameboy::rom::Cartridge;
+
+const RAM_BANK_SIZE: usize = 0x2000;
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// The size of the clock footer in save files: a 64 bit UNIX timestamp, followed by the minute of
+/// the day and the day counter as 16 bit values.
+const FOOTER_SIZE: usize = 12;
+
+/// What's mapped into 0xA000-0xBFFF, selected through 0x0000-0x1FFF.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Mode {
+    /// The RAM, read only.
+    Ram,
+    /// The RAM, read and write.
+    WritableRam,
+    /// Writes send a command to the clock.
+    Command,
+    /// Reads return the clock's response to the last command.
+    Response,
+    /// Reads tell whether the clock is ready for another command, which it always is.
+    Semaphore,
+    Infrared,
+}
+
+/// The clock's time, which only has a resolution of minutes as far as the game is concerned.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+struct Time {
+    seconds: u8,
+    /// The minute of the day.
+    minutes: u16,
+    days: u16,
+}
+
+impl Time {
+    fn advance(&mut self, seconds: u64) {
+        let seconds = seconds + u64::from(self.seconds);
+        let minutes = seconds / 60 + u64::from(self.minutes);
+        let days = minutes / u64::from(MINUTES_PER_DAY) + u64::from(self.days);
+        self.seconds = (seconds % 60) as u8;
+        self.minutes = (minutes % u64::from(MINUTES_PER_DAY)) as u16;
+        self.days = days as u16;
+    }
+}
+
+/// Hudson's HuC3, which has a clock and an infrared transceiver alongside the RAM.
+///
+/// The clock is accessed through a scratch memory of 256 4 bit values, with commands for reading
+/// and writing them, and for copying the time to and from the first seven of them.
+#[derive(Debug)]
+pub(crate) struct HuC3 {
+    rom: Box<[u8]>,
+    ram: Box<[u8]>,
+    mode: Mode,
+    rom_bank: u8,
+    ram_bank: u8,
+    led: bool,
+    timebase: Timebase,
+    time: Time,
+    memory: Box<[u8]>,
+    /// The scratch memory address used by the next read or write command.
+    address: u8,
+    response: u8,
+}
+
+impl HuC3 {
+    pub(crate) fn new(cartridge: Cartridge) -> HuC3 {
+        HuC3 {
+            ram: vec![0u8; cartridge.header.ram_size.min(4 * RAM_BANK_SIZE)].into_boxed_slice(),
+            rom: cartridge.rom,
+            mode: Mode::Ram,
+            rom_bank: 1,
+            ram_bank: 0,
+            led: false,
+            timebase: Timebase::new(),
+            time: Default::default(),
+            memory: vec![0u8; 0x100].into_boxed_slice(),
+            address: 0,
+            response: 0,
+        }
+    }
+
+    fn ram_offset(&self, addr: u16) -> Option<usize> {
+        if self.ram.is_empty() {
+            return None;
+        }
+        let offset = usize::from(self.ram_bank) * RAM_BANK_SIZE + usize::from(addr - 0xA000);
+        Some(offset % self.ram.len())
+    }
+
+    fn sync(&mut self) {
+        let seconds = self.timebase.sync();
+        self.time.advance(seconds);
+    }
+
+    /// Runs a command, which is in the upper four bits, with its argument in the lower four.
+    fn command(&mut self, value: u8) {
+        let argument = value & 0x0F;
+        match value >> 4 {
+            0x1 => {
+                self.response = 0x10 | self.memory[usize::from(self.address)];
+                self.address = self.address.wrapping_add(1);
+            }
+            command @ 0x2..=0x3 => {
+                self.memory[usize::from(self.address)] = argument;
+                if command == 0x3 {
+                    self.address = self.address.wrapping_add(1);
+                }
+            }
+            0x4 => self.address = self.address & 0xF0 | argument,
+            0x5 => self.address = self.address & 0x0F | argument << 4,
+            0x6 => match argument {
+                0x0 => {
+                    self.sync();
+                    let time = u32::from(self.time.minutes) | u32::from(self.time.days) << 12;
+                    for (i, nibble) in self.memory[..7].iter_mut().enumerate() {
+                        *nibble = (time >> (4 * i)) as u8 & 0x0F;
+                    }
+                }
+                0x1 => {
+                    let time = self.memory[..7]
+                        .iter()
+                        .rev()
+                        .fold(0u32, |time, &nibble| time << 4 | u32::from(nibble));
+                    self.time = Time {
+                        seconds: 0,
+                        minutes: (time & 0xFFF) as u16 % MINUTES_PER_DAY,
+                        days: (time >> 12) as u16,
+                    };
+                    self.timebase.reset();
+                }
+                // The status request, which is answered with 1.
+                0x2 => self.response = 0x61,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+impl Bus for HuC3 {
+    fn read_u8(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => read_rom(&self.rom, 0, addr),
+            0x4000..=0x7FFF => read_rom(&self.rom, usize::from(self.rom_bank), addr),
+            0xA000..=0xBFFF => match self.mode {
+                Mode::Ram | Mode::WritableRam => self
+                    .ram_offset(addr)
+                    .map(|offset| self.ram[offset])
+                    .unwrap_or(0xFF),
+                Mode::Response => self.response,
+                Mode::Semaphore => 0x01,
+                // Bit 0 is set when light is received, which it never is without another Game Boy.
+                Mode::Infrared => 0xC0,
+                Mode::Command => 0xFF,
+            },
+            _ => 0xFF,
+        }
+    }
+
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.mode = match value & 0x0F {
+                    0x0A => Mode::WritableRam,
+                    0x0B => Mode::Command,
+                    0x0C => Mode::Response,
+                    0x0D => Mode::Semaphore,
+                    0x0E => Mode::Infrared,
+                    _ => Mode::Ram,
+                }
+            }
+            0x2000..=0x3FFF => self.rom_bank = (value & 0x7F).max(1),
+            0x4000..=0x5FFF => self.ram_bank = value & 0x03,
+            0xA000..=0xBFFF => match self.mode {
+                Mode::WritableRam => {
+                    if let Some(offset) = self.ram_offset(addr) {
+                        self.ram[offset] = value;
+                    }
+                }
+                Mode::Command => self.command(value),
+                Mode::Infrared => self.led = value & 0x01 != 0,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+impl Mbc for HuC3 {
+    fn tick(&mut self, dots: u8) {
+        if self.timebase.tick(dots) {
+            self.time.advance(1);
+        }
+    }
+
+    fn set_rtc_clock(&mut self, clock: RtcClock) {
+        self.sync();
+        self.timebase.set_clock(clock);
+    }
+
+    fn infrared_led(&self) -> bool {
+        self.led
+    }
+
+    /// The RAM, followed by the clock.
+    fn save(&self) -> Vec<u8> {
+        let now = SystemTime::now();
+        let mut time = self.time;
+        time.advance(self.timebase.elapsed(now));
+        let mut save = self.ram.to_vec();
+        save.extend_from_slice(&rtc::timestamp(now).to_le_bytes());
+        save.extend_from_slice(&time.minutes.to_le_bytes());
+        save.extend_from_slice(&time.days.to_le_bytes());
+        save
+    }
+
+    fn load_save(&mut self, save: &[u8]) -> Result<(), SaveError> {
+        let len = self.ram.len();
+        if save.len() != len + FOOTER_SIZE {
+            return load_ram(&mut self.ram, save);
+        }
+        self.ram.copy_from_slice(&save[..len]);
+        let footer = &save[len..];
+        let mut timestamp = [0u8; 8];
+        timestamp.copy_from_slice(&footer[0..8]);
+        self.time = Time {
+            seconds: 0,
+            minutes: u16::from_le_bytes([footer[8], footer[9]]) % MINUTES_PER_DAY,
+            days: u16::from_le_bytes([footer[10], footer[11]]),
+        };
+        self.timebase.resume(u64::from_le_bytes(timestamp));
+        self.sync();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::rom::tests::rom;
+
+    fn huc3() -> HuC3 {
+        let mut mbc = HuC3::new(Cartridge::from_bytes(rom(0xFE, 0x03, 0x03)).unwrap());
+        mbc.set_rtc_clock(RtcClock::Emulated);
+        mbc
+    }
+
+    /// Sends a command, and returns the response.
+    fn command(mbc: &mut HuC3, value: u8) -> u8 {
+        mbc.write_u8(0x0000, 0x0B);
+        mbc.write_u8(0xA000, value);
+        mbc.write_u8(0x0000, 0x0C);
+        mbc.read_u8(0xA000)
+    }
+
+    #[test]
+    fn test_clock() {
+        let mut mbc = huc3();
+        // Set the time to day 0x123, minute 0x2CF, by writing it to 0x00-0x06 and copying it over.
+        command(&mut mbc, 0x40);
+        command(&mut mbc, 0x50);
+        for &nibble in &[0xF, 0xC, 0x2, 0x3, 0x2, 0x1, 0x0] {
+            command(&mut mbc, 0x30 | nibble);
+        }
+        command(&mut mbc, 0x61);
+        for _ in 0..60 * (1 << 22) / 16 {
+            mbc.tick(16);
+        }
+
+        command(&mut mbc, 0x60);
+        command(&mut mbc, 0x40);
+        let nibbles: Vec<u8> = (0..7).map(|_| command(&mut mbc, 0x10) & 0x0F).collect();
+        assert_eq!(vec![0x0, 0xD, 0x2, 0x3, 0x2, 0x1, 0x0], nibbles);
+
+        assert_eq!(0x61, command(&mut mbc, 0x62));
+    }
+
+    #[test]
+    fn test_modes() {
+        let mut mbc = huc3();
+        mbc.write_u8(0xA000, 0x42);
+        assert_eq!(0x00, mbc.read_u8(0xA000));
+        mbc.write_u8(0x0000, 0x0A);
+        mbc.write_u8(0xA000, 0x42);
+        mbc.write_u8(0x0000, 0x00);
+        assert_eq!(0x42, mbc.read_u8(0xA000));
+
+        mbc.write_u8(0x0000, 0x0D);
+        assert_eq!(0x01, mbc.read_u8(0xA000));
+        mbc.write_u8(0x0000, 0x0E);
+        mbc.write_u8(0xA000, 0x01);
+        assert!(mbc.infrared_led());
+    }
+
+    #[test]
+    fn test_save() {
+        let mut mbc = huc3();
+        command(&mut mbc, 0x40);
+        command(&mut mbc, 0x50);
+        for _ in 0..3 {
+            command(&mut mbc, 0x30);
+        }
+        for &nibble in &[0x5, 0x0, 0x0, 0x0] {
+            command(&mut mbc, 0x30 | nibble);
+        }
+        command(&mut mbc, 0x61);
+        let save = mbc.save();
+        assert_eq!(0x8000 + FOOTER_SIZE, save.len());
+
+        let mut restored = huc3();
+        restored.load_save(&save).unwrap();
+        assert_eq!(5, restored.time.days);
+    }
+}
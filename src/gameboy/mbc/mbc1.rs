@@ -0,0 +1,210 @@
+use super::{load_ram, read_rom, Mbc, SaveError};
+use crate::gameboy::mem::Bus;
+use crate::gameboy::rom::{self, Cartridge, ROM_BANK_SIZE};
+
+const RAM_BANK_SIZE: usize = 0x2000;
+
+/// The MBC1, which supports up to 2 MB of ROM and 32 kB of RAM.
+///
+/// The ROM bank is selected through two registers: a 5 bit register for the low bits, and a 2 bit
+/// register which is used either for the upper ROM bank bits, or for the RAM bank, depending on
+/// the banking mode. In the advanced banking mode, the 2 bit register also applies to the first
+/// ROM bank at 0x0000-0x3FFF, which is how 1 MB and larger cartridges reach banks 0x20, 0x40 and
+/// 0x60.
+#[derive(Debug)]
+pub(crate) struct Mbc1 {
+    rom: Box<[u8]>,
+    ram: Box<[u8]>,
+    ram_enabled: bool,
+    /// BANK1, the low bits of the ROM bank. Writing 0 selects 1 instead.
+    bank1: u8,
+    /// BANK2, the upper ROM bank bits, or the RAM bank.
+    bank2: u8,
+    advanced_banking: bool,
+    /// MBC1M multicarts only wire up four bits of BANK1, so that BANK2 selects one of four games.
+    multicart: bool,
+}
+
+impl Mbc1 {
+    pub(crate) fn new(cartridge: Cartridge) -> Mbc1 {
+        Mbc1 {
+            ram: vec![0u8; cartridge.header.ram_size.min(4 * RAM_BANK_SIZE)].into_boxed_slice(),
+            multicart: is_multicart(&cartridge.rom),
+            rom: cartridge.rom,
+            ram_enabled: false,
+            bank1: 1,
+            bank2: 0,
+            advanced_banking: false,
+        }
+    }
+
+    fn bank2_shift(&self) -> u8 {
+        if self.multicart {
+            4
+        } else {
+            5
+        }
+    }
+
+    fn rom_bank_0(&self) -> usize {
+        if self.advanced_banking {
+            usize::from(self.bank2 << self.bank2_shift())
+        } else {
+            0
+        }
+    }
+
+    fn rom_bank_n(&self) -> usize {
+        let bank1 = if self.multicart {
+            self.bank1 & 0x0F
+        } else {
+            self.bank1
+        };
+        usize::from(self.bank2 << self.bank2_shift() | bank1)
+    }
+
+    fn ram_offset(&self, addr: u16) -> usize {
+        let bank = if self.advanced_banking {
+            usize::from(self.bank2)
+        } else {
+            0
+        };
+        (bank * RAM_BANK_SIZE + usize::from(addr - 0xA000)) % self.ram.len()
+    }
+}
+
+/// MBC1M multicarts are 1 MB, with a game in each 256 kB quarter. There's nothing in the header
+/// to tell them apart, but there's a copy of the logo in the header of the second game.
+fn is_multicart(rom: &[u8]) -> bool {
+    rom.len() == 64 * ROM_BANK_SIZE && rom::has_logo(rom, 0x10 * ROM_BANK_SIZE)
+}
+
+impl Bus for Mbc1 {
+    fn read_u8(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => read_rom(&self.rom, self.rom_bank_0(), addr),
+            0x4000..=0x7FFF => read_rom(&self.rom, self.rom_bank_n(), addr),
+            0xA000..=0xBFFF if self.ram_enabled && !self.ram.is_empty() => {
+                self.ram[self.ram_offset(addr)]
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            // The zero check is done on all five bits, which is why e.g. bank 0x20 can't be
+            // selected in the switchable area; it becomes 0x21.
+            0x2000..=0x3FFF => self.bank1 = (value & 0x1F).max(1),
+            0x4000..=0x5FFF => self.bank2 = value & 0x03,
+            0x6000..=0x7FFF => self.advanced_banking = value & 0x01 != 0,
+            0xA000..=0xBFFF if self.ram_enabled && !self.ram.is_empty() => {
+                let offset = self.ram_offset(addr);
+                self.ram[offset] = value;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Mbc for Mbc1 {
+    fn save(&self) -> Vec<u8> {
+        self.ram.to_vec()
+    }
+
+    fn load_save(&mut self, save: &[u8]) -> Result<(), SaveError> {
+        load_ram(&mut self.ram, save)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::rom::tests::rom;
+
+    /// A cartridge where the first byte of each ROM bank is the bank number.
+    fn mbc1(rom_size: u8, ram_size: u8) -> Mbc1 {
+        let mut rom = rom(0x03, rom_size, ram_size);
+        for (bank, chunk) in rom.chunks_mut(ROM_BANK_SIZE).enumerate().skip(1) {
+            chunk[0] = bank as u8;
+        }
+        Mbc1::new(Cartridge::from_bytes(rom).unwrap())
+    }
+
+    #[test]
+    fn test_rom_banks() {
+        let mut mbc = mbc1(0x04, 0x00);
+        assert_eq!(0x01, mbc.read_u8(0x4000));
+        mbc.write_u8(0x2000, 0x00);
+        assert_eq!(0x01, mbc.read_u8(0x4000));
+        mbc.write_u8(0x2000, 0x13);
+        assert_eq!(0x13, mbc.read_u8(0x4000));
+        // Only five bits are used, and 0x20 is treated as 0.
+        mbc.write_u8(0x2000, 0xE0);
+        assert_eq!(0x01, mbc.read_u8(0x4000));
+        // Banks beyond the size of the ROM wrap around.
+        mbc.write_u8(0x2000, 0x1F);
+        mbc.write_u8(0x4000, 0x01);
+        assert_eq!(0x1F, mbc.read_u8(0x4000));
+    }
+
+    #[test]
+    fn test_large_rom() {
+        let mut mbc = mbc1(0x06, 0x00);
+        mbc.write_u8(0x2000, 0x00);
+        mbc.write_u8(0x4000, 0x01);
+        assert_eq!(0x21, mbc.read_u8(0x4000));
+        assert_eq!(0x00, mbc.read_u8(0x0000));
+
+        mbc.write_u8(0x4000, 0x03);
+        mbc.write_u8(0x2000, 0x05);
+        assert_eq!(0x65, mbc.read_u8(0x4000));
+
+        // The advanced banking mode maps BANK2 into the first bank as well.
+        mbc.write_u8(0x6000, 0x01);
+        assert_eq!(0x60, mbc.read_u8(0x0000));
+        assert_eq!(0x65, mbc.read_u8(0x4000));
+    }
+
+    #[test]
+    fn test_ram() {
+        let mut mbc = mbc1(0x00, 0x03);
+        mbc.write_u8(0xA000, 0x42);
+        assert_eq!(0xFF, mbc.read_u8(0xA000));
+
+        mbc.write_u8(0x0000, 0x0A);
+        mbc.write_u8(0xA000, 0x42);
+        assert_eq!(0x42, mbc.read_u8(0xA000));
+
+        // BANK2 only selects the RAM bank in the advanced banking mode.
+        mbc.write_u8(0x4000, 0x02);
+        assert_eq!(0x42, mbc.read_u8(0xA000));
+        mbc.write_u8(0x6000, 0x01);
+        assert_eq!(0x00, mbc.read_u8(0xA000));
+        mbc.write_u8(0xA000, 0x24);
+        assert_eq!(0x24, mbc.ram[2 * RAM_BANK_SIZE]);
+
+        mbc.write_u8(0x0000, 0x00);
+        assert_eq!(0xFF, mbc.read_u8(0xA000));
+    }
+
+    #[test]
+    fn test_multicart() {
+        let mut rom = rom(0x01, 0x05, 0x00);
+        for (bank, chunk) in rom.chunks_mut(ROM_BANK_SIZE).enumerate().skip(1) {
+            chunk[0] = bank as u8;
+        }
+        let logo = rom[0x0104..0x0134].to_vec();
+        rom[0x40104..0x40134].copy_from_slice(&logo);
+        let mut mbc = Mbc1::new(Cartridge::from_bytes(rom).unwrap());
+        assert!(mbc.multicart);
+
+        // BANK2 selects the game, and only the low four bits of BANK1 are used.
+        mbc.write_u8(0x4000, 0x02);
+        mbc.write_u8(0x2000, 0x13);
+        assert_eq!(0x23, mbc.read_u8(0x4000));
+        mbc.write_u8(0x6000, 0x01);
+        assert_eq!(0x20, mbc.read_u8(0x0000));
+    }
+}
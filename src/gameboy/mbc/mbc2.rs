@@ -0,0 +1,98 @@
+use super::{load_ram, read_rom, Mbc, SaveError};
+use crate::gameboy::mem::Bus;
+use crate::gameboy::rom::Cartridge;
+
+/// The size of the RAM built into the MBC2, in 4 bit values.
+const RAM_SIZE: usize = 512;
+
+/// The MBC2, which supports up to 256 kB of ROM, and has 512 4 bit values of RAM built in.
+///
+/// Both registers are at 0x0000-0x3FFF, where bit 8 of the address selects which one is written.
+#[derive(Debug)]
+pub(crate) struct Mbc2 {
+    rom: Box<[u8]>,
+    /// Only the lower four bits of each byte are used.
+    ram: Box<[u8]>,
+    ram_enabled: bool,
+    rom_bank: u8,
+}
+
+impl Mbc2 {
+    pub(crate) fn new(cartridge: Cartridge) -> Mbc2 {
+        Mbc2 {
+            rom: cartridge.rom,
+            ram: vec![0u8; RAM_SIZE].into_boxed_slice(),
+            ram_enabled: false,
+            rom_bank: 1,
+        }
+    }
+}
+
+impl Bus for Mbc2 {
+    fn read_u8(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => read_rom(&self.rom, 0, addr),
+            0x4000..=0x7FFF => read_rom(&self.rom, usize::from(self.rom_bank), addr),
+            // The RAM is repeated throughout 0xA000-0xBFFF, and the upper four bits are open bus.
+            0xA000..=0xBFFF if self.ram_enabled => 0xF0 | self.ram[usize::from(addr) % RAM_SIZE],
+            _ => 0xFF,
+        }
+    }
+
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x3FFF if addr & 0x0100 == 0 => self.ram_enabled = value & 0x0F == 0x0A,
+            0x0000..=0x3FFF => self.rom_bank = (value & 0x0F).max(1),
+            0xA000..=0xBFFF if self.ram_enabled => {
+                self.ram[usize::from(addr) % RAM_SIZE] = value & 0x0F;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Mbc for Mbc2 {
+    fn save(&self) -> Vec<u8> {
+        self.ram.to_vec()
+    }
+
+    fn load_save(&mut self, save: &[u8]) -> Result<(), SaveError> {
+        load_ram(&mut self.ram, save)?;
+        for value in self.ram.iter_mut() {
+            *value &= 0x0F;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::rom::tests::rom;
+    use crate::gameboy::rom::ROM_BANK_SIZE;
+
+    #[test]
+    fn test_mbc2() {
+        let mut rom = rom(0x06, 0x03, 0x00);
+        for (bank, chunk) in rom.chunks_mut(ROM_BANK_SIZE).enumerate().skip(1) {
+            chunk[0] = bank as u8;
+        }
+        let mut mbc = Mbc2::new(Cartridge::from_bytes(rom).unwrap());
+
+        // Bit 8 of the address is clear, so this enables the RAM instead of switching banks.
+        mbc.write_u8(0x2000, 0x0A);
+        assert_eq!(0x01, mbc.read_u8(0x4000));
+        mbc.write_u8(0x2100, 0x00);
+        assert_eq!(0x01, mbc.read_u8(0x4000));
+        mbc.write_u8(0x2100, 0x0F);
+        assert_eq!(0x0F, mbc.read_u8(0x4000));
+
+        mbc.write_u8(0xA000, 0x5A);
+        assert_eq!(0xFA, mbc.read_u8(0xA000));
+        assert_eq!(0xFA, mbc.read_u8(0xA200));
+        assert_eq!(0xFA, mbc.read_u8(0xBE00));
+
+        mbc.write_u8(0x0000, 0x00);
+        assert_eq!(0xFF, mbc.read_u8(0xA000));
+    }
+}
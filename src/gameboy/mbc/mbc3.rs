@@ -0,0 +1,200 @@
+use super::rtc::{self, Rtc, RtcClock};
+use super::{load_ram, read_rom, Mbc, SaveError};
+use crate::gameboy::mem::Bus;
+use crate::gameboy::rom::Cartridge;
+
+const RAM_BANK_SIZE: usize = 0x2000;
+
+/// The MBC3, which supports up to 2 MB of ROM, 32 kB of RAM, and optionally a real-time clock.
+///
+/// The clock registers are mapped into 0xA000-0xBFFF in place of a RAM bank, by selecting
+/// 0x08-0x0C instead of a RAM bank number.
+#[derive(Debug)]
+pub(crate) struct Mbc3 {
+    rom: Box<[u8]>,
+    ram: Box<[u8]>,
+    rtc: Option<Rtc>,
+    /// Enables both the RAM and the clock.
+    ram_enabled: bool,
+    rom_bank: u8,
+    /// The RAM bank, or the clock register.
+    ram_select: u8,
+}
+
+impl Mbc3 {
+    pub(crate) fn new(cartridge: Cartridge) -> Mbc3 {
+        let cartridge_type = cartridge.header.cartridge_type;
+        Mbc3 {
+            ram: vec![0u8; cartridge.header.ram_size.min(4 * RAM_BANK_SIZE)].into_boxed_slice(),
+            rtc: if cartridge_type.timer {
+                Some(Rtc::new())
+            } else {
+                None
+            },
+            rom: cartridge.rom,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_select: 0,
+        }
+    }
+
+    /// The offset into the RAM, if a RAM bank is selected and there's RAM to select from.
+    fn ram_offset(&self, addr: u16) -> Option<usize> {
+        if self.ram_select > 0x03 || self.ram.is_empty() {
+            return None;
+        }
+        let offset = usize::from(self.ram_select) * RAM_BANK_SIZE + usize::from(addr - 0xA000);
+        Some(offset % self.ram.len())
+    }
+}
+
+impl Bus for Mbc3 {
+    fn read_u8(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => read_rom(&self.rom, 0, addr),
+            0x4000..=0x7FFF => read_rom(&self.rom, usize::from(self.rom_bank), addr),
+            0xA000..=0xBFFF if self.ram_enabled => match (&self.rtc, self.ram_select) {
+                (Some(rtc), 0x08..=0x0C) => rtc.read(self.ram_select),
+                _ => self
+                    .ram_offset(addr)
+                    .map(|offset| self.ram[offset])
+                    .unwrap_or(0xFF),
+            },
+            _ => 0xFF,
+        }
+    }
+
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            // Unlike the MBC1, all seven bits are compared against zero.
+            0x2000..=0x3FFF => self.rom_bank = (value & 0x7F).max(1),
+            0x4000..=0x5FFF => self.ram_select = value & 0x0F,
+            0x6000..=0x7FFF => {
+                if let Some(rtc) = &mut self.rtc {
+                    rtc.write_latch(value);
+                }
+            }
+            0xA000..=0xBFFF if self.ram_enabled => match (&mut self.rtc, self.ram_select) {
+                (Some(rtc), 0x08..=0x0C) => rtc.write(self.ram_select, value),
+                _ => {
+                    if let Some(offset) = self.ram_offset(addr) {
+                        self.ram[offset] = value;
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+impl Mbc for Mbc3 {
+    fn tick(&mut self, dots: u8) {
+        if let Some(rtc) = &mut self.rtc {
+            rtc.tick(dots);
+        }
+    }
+
+    fn set_rtc_clock(&mut self, clock: RtcClock) {
+        if let Some(rtc) = &mut self.rtc {
+            rtc.set_clock(clock);
+        }
+    }
+
+    /// The RAM, followed by the clock in the footer format used by other emulators.
+    fn save(&self) -> Vec<u8> {
+        let mut save = self.ram.to_vec();
+        if let Some(rtc) = &self.rtc {
+            rtc.save(&mut save);
+        }
+        save
+    }
+
+    /// Restores the RAM and the clock. A save without the clock footer only restores the RAM,
+    /// which leaves the clock where it is.
+    fn load_save(&mut self, save: &[u8]) -> Result<(), SaveError> {
+        let len = self.ram.len();
+        match &mut self.rtc {
+            Some(rtc) if save.len() == len + rtc::FOOTER_SIZE => rtc.load(&save[len..]),
+            Some(rtc) if save.len() == len + rtc::LEGACY_FOOTER_SIZE => rtc.load(&save[len..]),
+            _ => return load_ram(&mut self.ram, save),
+        }
+        self.ram.copy_from_slice(&save[..len]);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::rom::tests::rom;
+    use crate::gameboy::rom::ROM_BANK_SIZE;
+
+    fn mbc3(cartridge_type: u8, rom_size: u8, ram_size: u8) -> Mbc3 {
+        let mut rom = rom(cartridge_type, rom_size, ram_size);
+        for (bank, chunk) in rom.chunks_mut(ROM_BANK_SIZE).enumerate().skip(1) {
+            chunk[0] = bank as u8;
+        }
+        Mbc3::new(Cartridge::from_bytes(rom).unwrap())
+    }
+
+    #[test]
+    fn test_rom_banks() {
+        let mut mbc = mbc3(0x11, 0x06, 0x00);
+        assert_eq!(0x01, mbc.read_u8(0x4000));
+        mbc.write_u8(0x2000, 0x00);
+        assert_eq!(0x01, mbc.read_u8(0x4000));
+        mbc.write_u8(0x2000, 0x20);
+        assert_eq!(0x20, mbc.read_u8(0x4000));
+        mbc.write_u8(0x2000, 0xFF);
+        assert_eq!(0x7F, mbc.read_u8(0x4000));
+    }
+
+    #[test]
+    fn test_ram_and_rtc() {
+        let mut mbc = mbc3(0x10, 0x00, 0x03);
+        mbc.set_rtc_clock(RtcClock::Emulated);
+        mbc.write_u8(0x0000, 0x0A);
+        mbc.write_u8(0x4000, 0x01);
+        mbc.write_u8(0xA000, 0x42);
+        assert_eq!(0x42, mbc.ram[RAM_BANK_SIZE]);
+
+        mbc.write_u8(0x4000, 0x0A);
+        mbc.write_u8(0xA000, 0x05);
+        assert_eq!(0x05, mbc.read_u8(0xA000));
+        mbc.write_u8(0x4000, 0x01);
+        assert_eq!(0x42, mbc.read_u8(0xA000));
+
+        mbc.write_u8(0x0000, 0x00);
+        mbc.write_u8(0x4000, 0x0A);
+        assert_eq!(0xFF, mbc.read_u8(0xA000));
+    }
+
+    #[test]
+    fn test_save() {
+        let mut mbc = mbc3(0x10, 0x00, 0x02);
+        mbc.set_rtc_clock(RtcClock::Emulated);
+        mbc.write_u8(0x0000, 0x0A);
+        mbc.write_u8(0xA000, 0x42);
+        mbc.write_u8(0x4000, 0x09);
+        mbc.write_u8(0xA000, 0x2A);
+        let save = mbc.save();
+        assert_eq!(0x2000 + rtc::FOOTER_SIZE, save.len());
+
+        let mut restored = mbc3(0x10, 0x00, 0x02);
+        restored.set_rtc_clock(RtcClock::Emulated);
+        restored.load_save(&save).unwrap();
+        restored.write_u8(0x0000, 0x0A);
+        assert_eq!(0x42, restored.read_u8(0xA000));
+        restored.write_u8(0x4000, 0x09);
+        assert_eq!(0x2A, restored.read_u8(0xA000));
+
+        assert_eq!(
+            Err(SaveError::WrongSize {
+                expected: 0x2000,
+                actual: 0x2001,
+            }),
+            restored.load_save(&save[..0x2001])
+        );
+    }
+}
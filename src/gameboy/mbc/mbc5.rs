@@ -0,0 +1,137 @@
+use super::{load_ram, read_rom, Mbc, SaveError};
+use crate::gameboy::mem::Bus;
+use crate::gameboy::rom::Cartridge;
+
+const RAM_BANK_SIZE: usize = 0x2000;
+
+/// The MBC5, which supports up to 8 MB of ROM through a 9 bit bank number, and 128 kB of RAM.
+///
+/// On rumble cartridges, bit 3 of the RAM bank register drives the motor instead, which leaves
+/// eight RAM banks.
+#[derive(Debug)]
+pub(crate) struct Mbc5 {
+    rom: Box<[u8]>,
+    ram: Box<[u8]>,
+    ram_enabled: bool,
+    /// Unlike the other MBCs, bank 0 can be mapped into 0x4000-0x7FFF as well.
+    rom_bank: u16,
+    ram_bank: u8,
+    has_rumble: bool,
+    rumble: bool,
+}
+
+impl Mbc5 {
+    pub(crate) fn new(cartridge: Cartridge) -> Mbc5 {
+        Mbc5 {
+            ram: vec![0u8; cartridge.header.ram_size.min(16 * RAM_BANK_SIZE)].into_boxed_slice(),
+            has_rumble: cartridge.header.cartridge_type.rumble,
+            rom: cartridge.rom,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            rumble: false,
+        }
+    }
+
+    fn ram_offset(&self, addr: u16) -> usize {
+        (usize::from(self.ram_bank) * RAM_BANK_SIZE + usize::from(addr - 0xA000)) % self.ram.len()
+    }
+}
+
+impl Bus for Mbc5 {
+    fn read_u8(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => read_rom(&self.rom, 0, addr),
+            0x4000..=0x7FFF => read_rom(&self.rom, usize::from(self.rom_bank), addr),
+            0xA000..=0xBFFF if self.ram_enabled && !self.ram.is_empty() => {
+                self.ram[self.ram_offset(addr)]
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = self.rom_bank & 0x100 | u16::from(value),
+            0x3000..=0x3FFF => self.rom_bank = self.rom_bank & 0xFF | u16::from(value & 0x01) << 8,
+            0x4000..=0x5FFF if self.has_rumble => {
+                self.rumble = value & 0x08 != 0;
+                self.ram_bank = value & 0x07;
+            }
+            0x4000..=0x5FFF => self.ram_bank = value & 0x0F,
+            0xA000..=0xBFFF if self.ram_enabled && !self.ram.is_empty() => {
+                let offset = self.ram_offset(addr);
+                self.ram[offset] = value;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Mbc for Mbc5 {
+    fn rumble(&self) -> bool {
+        self.rumble
+    }
+
+    fn save(&self) -> Vec<u8> {
+        self.ram.to_vec()
+    }
+
+    fn load_save(&mut self, save: &[u8]) -> Result<(), SaveError> {
+        load_ram(&mut self.ram, save)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::mem::BusExt;
+    use crate::gameboy::rom::tests::rom;
+    use crate::gameboy::rom::ROM_BANK_SIZE;
+
+    /// A cartridge where the first two bytes of each ROM bank are the bank number.
+    fn mbc5(cartridge_type: u8, rom_size: u8, ram_size: u8) -> Mbc5 {
+        let mut rom = rom(cartridge_type, rom_size, ram_size);
+        for (bank, chunk) in rom.chunks_mut(ROM_BANK_SIZE).enumerate().skip(1) {
+            chunk[0] = bank as u8;
+            chunk[1] = (bank >> 8) as u8;
+        }
+        Mbc5::new(Cartridge::from_bytes(rom).unwrap())
+    }
+
+    #[test]
+    fn test_rom_banks() {
+        let mut mbc = mbc5(0x19, 0x08, 0x00);
+        assert_eq!(0x0001, mbc.read_u16(0x4000));
+        mbc.write_u8(0x2000, 0x00);
+        assert_eq!(0x0000, mbc.read_u16(0x4000));
+        mbc.write_u8(0x2000, 0x23);
+        mbc.write_u8(0x3000, 0x01);
+        assert_eq!(0x0123, mbc.read_u16(0x4000));
+        mbc.write_u8(0x2000, 0xFF);
+        assert_eq!(0x01FF, mbc.read_u16(0x4000));
+    }
+
+    #[test]
+    fn test_ram_banks() {
+        let mut mbc = mbc5(0x1B, 0x00, 0x04);
+        mbc.write_u8(0x0000, 0x0A);
+        mbc.write_u8(0x4000, 0x0F);
+        mbc.write_u8(0xA000, 0x42);
+        assert_eq!(0x42, mbc.ram[15 * RAM_BANK_SIZE]);
+        assert!(!mbc.rumble());
+    }
+
+    #[test]
+    fn test_rumble() {
+        let mut mbc = mbc5(0x1E, 0x00, 0x03);
+        mbc.write_u8(0x0000, 0x0A);
+        mbc.write_u8(0x4000, 0x0B);
+        assert!(mbc.rumble());
+        mbc.write_u8(0xA000, 0x42);
+        assert_eq!(0x42, mbc.ram[3 * RAM_BANK_SIZE]);
+        mbc.write_u8(0x4000, 0x03);
+        assert!(!mbc.rumble());
+    }
+}
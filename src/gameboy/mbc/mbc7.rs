@@ -0,0 +1,187 @@
+use std::fmt;
+
+use super::eeprom::{self, Eeprom};
+use super::{read_rom, Mbc, SaveError};
+use crate::gameboy::mem::Bus;
+use crate::gameboy::rom::Cartridge;
+
+/// The value the accelerometer reads when level.
+const CENTER: u16 = 0x81D0;
+/// How much the value changes per g of acceleration.
+const PER_G: f32 = 112.0;
+
+/// Where the MBC7 gets the tilt of the cartridge from, e.g. a frontend's input handling or a
+/// scripted test.
+pub trait Accelerometer {
+    /// The acceleration along the x and y axes, in g. Tilting the cartridge all the way to one
+    /// side gives about 1.0 or -1.0.
+    fn acceleration(&mut self) -> (f32, f32);
+}
+
+impl<F: FnMut() -> (f32, f32)> Accelerometer for F {
+    fn acceleration(&mut self) -> (f32, f32) {
+        self()
+    }
+}
+
+/// The MBC7, which has an accelerometer and a 93LC56 EEPROM in place of RAM.
+///
+/// Both are accessed through registers at 0xA000-0xAFFF, where bits 4-7 of the address select
+/// the register, once both RAM enable registers have been written.
+pub(crate) struct Mbc7 {
+    rom: Box<[u8]>,
+    eeprom: Eeprom,
+    accelerometer: Option<Box<dyn Accelerometer>>,
+    /// The first RAM enable register, at 0x0000-0x1FFF.
+    ram_enabled: bool,
+    /// The second RAM enable register, at 0x4000-0x5FFF.
+    registers_enabled: bool,
+    rom_bank: u8,
+    /// The latched acceleration, as read by the game.
+    x: u16,
+    y: u16,
+    /// Whether the latched values have been erased, which they need to be before latching again.
+    erased: bool,
+}
+
+impl Mbc7 {
+    pub(crate) fn new(cartridge: Cartridge) -> Mbc7 {
+        Mbc7 {
+            rom: cartridge.rom,
+            eeprom: Eeprom::new(),
+            accelerometer: None,
+            ram_enabled: false,
+            registers_enabled: false,
+            rom_bank: 1,
+            x: 0x8000,
+            y: 0x8000,
+            erased: false,
+        }
+    }
+
+    fn latch(&mut self) {
+        let (x, y) = match &mut self.accelerometer {
+            Some(accelerometer) => accelerometer.acceleration(),
+            None => (0.0, 0.0),
+        };
+        let value = |g: f32| (f32::from(CENTER) + g * PER_G) as u16;
+        self.x = value(x);
+        self.y = value(y);
+        self.erased = false;
+    }
+}
+
+impl fmt::Debug for Mbc7 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Mbc7")
+            .field("eeprom", &self.eeprom)
+            .field("ram_enabled", &self.ram_enabled)
+            .field("registers_enabled", &self.registers_enabled)
+            .field("rom_bank", &self.rom_bank)
+            .field("x", &self.x)
+            .field("y", &self.y)
+            .field("erased", &self.erased)
+            .finish()
+    }
+}
+
+impl Bus for Mbc7 {
+    fn read_u8(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => read_rom(&self.rom, 0, addr),
+            0x4000..=0x7FFF => read_rom(&self.rom, usize::from(self.rom_bank), addr),
+            0xA000..=0xAFFF if self.ram_enabled && self.registers_enabled => {
+                match addr >> 4 & 0x0F {
+                    0x2 => self.x as u8,
+                    0x3 => (self.x >> 8) as u8,
+                    0x4 => self.y as u8,
+                    0x5 => (self.y >> 8) as u8,
+                    0x6 => 0x00,
+                    0x8 => self.eeprom.read(),
+                    _ => 0xFF,
+                }
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = value == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = value & 0x7F,
+            0x4000..=0x5FFF => self.registers_enabled = value == 0x40,
+            0xA000..=0xAFFF if self.ram_enabled && self.registers_enabled => {
+                match (addr >> 4 & 0x0F, value) {
+                    (0x0, 0x55) => {
+                        self.x = 0x8000;
+                        self.y = 0x8000;
+                        self.erased = true;
+                    }
+                    (0x1, 0xAA) if self.erased => self.latch(),
+                    (0x8, _) => self.eeprom.write(value),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Mbc for Mbc7 {
+    fn set_accelerometer(&mut self, accelerometer: Box<dyn Accelerometer>) {
+        self.accelerometer = Some(accelerometer);
+    }
+
+    fn save(&self) -> Vec<u8> {
+        self.eeprom.save()
+    }
+
+    fn load_save(&mut self, save: &[u8]) -> Result<(), SaveError> {
+        if save.len() != eeprom::SAVE_SIZE {
+            return Err(SaveError::WrongSize {
+                expected: eeprom::SAVE_SIZE,
+                actual: save.len(),
+            });
+        }
+        self.eeprom.load(save);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::rom::tests::rom;
+
+    #[test]
+    fn test_accelerometer() {
+        let mut mbc = Mbc7::new(Cartridge::from_bytes(rom(0x22, 0x00, 0x00)).unwrap());
+        let mut tilt = vec![(0.5, -1.0), (0.0, 0.0)].into_iter();
+        mbc.set_accelerometer(Box::new(move || tilt.next().unwrap()));
+
+        // The registers can't be accessed until both enable registers have been written.
+        assert_eq!(0xFF, mbc.read_u8(0xA020));
+        mbc.write_u8(0x0000, 0x0A);
+        mbc.write_u8(0x4000, 0x40);
+        assert_eq!(0x00, mbc.read_u8(0xA020));
+        assert_eq!(0x80, mbc.read_u8(0xA030));
+
+        // Latching only works after erasing.
+        mbc.write_u8(0xA010, 0xAA);
+        assert_eq!(0x8000, axis(&mbc, 0xA020));
+        mbc.write_u8(0xA000, 0x55);
+        mbc.write_u8(0xA010, 0xAA);
+        assert_eq!(CENTER + 56, axis(&mbc, 0xA020));
+        assert_eq!(CENTER - 112, axis(&mbc, 0xA040));
+
+        mbc.write_u8(0xA000, 0x55);
+        mbc.write_u8(0xA010, 0xAA);
+        assert_eq!(CENTER, axis(&mbc, 0xA020));
+        assert_eq!(CENTER, axis(&mbc, 0xA040));
+    }
+
+    /// Reads an axis, where the high byte is in the register after the low byte.
+    fn axis(mbc: &Mbc7, addr: u16) -> u16 {
+        u16::from(mbc.read_u8(addr)) | u16::from(mbc.read_u8(addr + 0x10)) << 8
+    }
+}
@@ -0,0 +1,176 @@
+use super::{load_ram, read_rom, Mbc, SaveError};
+use crate::gameboy::mem::Bus;
+use crate::gameboy::rom::{Cartridge, ROM_BANK_SIZE};
+
+const RAM_BANK_SIZE: usize = 0x2000;
+
+/// The MMM01, a multicart mapper which boots into a menu in the last 32 kB of the ROM.
+///
+/// Until the menu maps in a game, the registers also set the upper ROM and RAM bank bits, which
+/// select the game, and masks for which of the lower bits the game can't change. After that it
+/// behaves like an MBC1 confined to the game's part of the ROM and RAM.
+#[derive(Debug)]
+pub(crate) struct Mmm01 {
+    rom: Box<[u8]>,
+    ram: Box<[u8]>,
+    /// Whether the menu has mapped in a game, which locks the upper bits and the masks.
+    mapped: bool,
+    ram_enabled: bool,
+    /// The 9 bit ROM bank, where the lower five bits are the MBC1's bank register.
+    rom_bank: u16,
+    /// The lower ROM bank bits which the game can't change.
+    rom_mask: u16,
+    /// The 4 bit RAM bank, where the lower two bits are the MBC1's secondary register.
+    ram_bank: u8,
+    /// The lower RAM bank bits which the game can't change.
+    ram_mask: u8,
+    advanced_banking: bool,
+    /// Whether the game is prevented from changing the banking mode.
+    mode_locked: bool,
+}
+
+impl Mmm01 {
+    pub(crate) fn new(cartridge: Cartridge) -> Mmm01 {
+        Mmm01 {
+            ram: vec![0u8; cartridge.header.ram_size.min(16 * RAM_BANK_SIZE)].into_boxed_slice(),
+            rom: cartridge.rom,
+            mapped: false,
+            ram_enabled: false,
+            rom_bank: 0,
+            rom_mask: 0,
+            ram_bank: 0,
+            ram_mask: 0,
+            advanced_banking: false,
+            mode_locked: false,
+        }
+    }
+
+    fn rom_bank_0(&self) -> usize {
+        // The game's first bank, i.e. its share of the lower bits is zero.
+        usize::from(self.rom_bank & !(0x1F & !self.rom_mask))
+    }
+
+    fn rom_bank_n(&self) -> usize {
+        let mut bank = self.rom_bank;
+        if bank & 0x1F & !self.rom_mask == 0 {
+            bank |= 1;
+        }
+        usize::from(bank)
+    }
+
+    fn ram_offset(&self, addr: u16) -> usize {
+        let bank = if self.advanced_banking {
+            self.ram_bank
+        } else {
+            self.ram_bank & !(0x03 & !self.ram_mask)
+        };
+        (usize::from(bank) * RAM_BANK_SIZE + usize::from(addr - 0xA000)) % self.ram.len()
+    }
+}
+
+impl Bus for Mmm01 {
+    fn read_u8(&self, addr: u16) -> u8 {
+        match addr {
+            // The menu is in the last two banks.
+            0x0000..=0x7FFF if !self.mapped => {
+                let banks = self.rom.len() / ROM_BANK_SIZE;
+                read_rom(
+                    &self.rom,
+                    banks.saturating_sub(2) + usize::from(addr >> 14),
+                    addr,
+                )
+            }
+            0x0000..=0x3FFF => read_rom(&self.rom, self.rom_bank_0(), addr),
+            0x4000..=0x7FFF => read_rom(&self.rom, self.rom_bank_n(), addr),
+            0xA000..=0xBFFF if self.ram_enabled && !self.ram.is_empty() => {
+                self.ram[self.ram_offset(addr)]
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = value & 0x0F == 0x0A;
+                if !self.mapped {
+                    self.ram_mask = value >> 4 & 0x03;
+                    self.mapped = value & 0x40 != 0;
+                }
+            }
+            0x2000..=0x3FFF => {
+                let writable = if self.mapped {
+                    0x1F & !self.rom_mask
+                } else {
+                    0x7F
+                };
+                self.rom_bank = self.rom_bank & !writable | u16::from(value) & writable;
+            }
+            0x4000..=0x5FFF => {
+                let writable = if self.mapped {
+                    0x03 & !self.ram_mask
+                } else {
+                    self.rom_bank = self.rom_bank & 0x7F | u16::from(value >> 4 & 0x03) << 7;
+                    self.mode_locked = value & 0x40 != 0;
+                    0x0F
+                };
+                self.ram_bank = self.ram_bank & !writable | value & writable;
+            }
+            0x6000..=0x7FFF => {
+                if !self.mapped {
+                    self.rom_mask = u16::from(value & 0x3C) >> 1;
+                }
+                if !self.mapped || !self.mode_locked {
+                    self.advanced_banking = value & 0x01 != 0;
+                }
+            }
+            0xA000..=0xBFFF if self.ram_enabled && !self.ram.is_empty() => {
+                let offset = self.ram_offset(addr);
+                self.ram[offset] = value;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Mbc for Mmm01 {
+    fn save(&self) -> Vec<u8> {
+        self.ram.to_vec()
+    }
+
+    fn load_save(&mut self, save: &[u8]) -> Result<(), SaveError> {
+        load_ram(&mut self.ram, save)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::rom::tests::rom;
+
+    #[test]
+    fn test_mmm01() {
+        let mut rom = rom(0x0D, 0x04, 0x03);
+        for (bank, chunk) in rom.chunks_mut(ROM_BANK_SIZE).enumerate() {
+            chunk[0] = bank as u8;
+        }
+        let mut mbc = Mmm01::new(Cartridge::from_bytes(rom).unwrap());
+        // The menu starts out in the last two banks.
+        assert_eq!(0x1E, mbc.read_u8(0x0000));
+        assert_eq!(0x1F, mbc.read_u8(0x4000));
+
+        // Map in a game of four banks starting at bank 0x08, by masking bits 2-4.
+        mbc.write_u8(0x2000, 0x08);
+        mbc.write_u8(0x6000, 0x38);
+        mbc.write_u8(0x0000, 0x40);
+        assert_eq!(0x08, mbc.read_u8(0x0000));
+        assert_eq!(0x09, mbc.read_u8(0x4000));
+
+        // The game can only switch between its own banks.
+        mbc.write_u8(0x2000, 0x03);
+        assert_eq!(0x0B, mbc.read_u8(0x4000));
+        mbc.write_u8(0x2000, 0x1F);
+        assert_eq!(0x0B, mbc.read_u8(0x4000));
+        assert_eq!(0x08, mbc.read_u8(0x0000));
+    }
+}
@@ -0,0 +1,153 @@
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use failure::Fail;
+
+use super::mem::Bus;
+use super::rom::{Cartridge, Mapper, RomError, ROM_BANK_SIZE};
+
+mod eeprom;
+mod huc1;
+mod huc3;
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+mod mbc7;
+mod mmm01;
+mod rom_only;
+mod rtc;
+
+use self::huc1::HuC1;
+use self::huc3::HuC3;
+use self::mbc1::Mbc1;
+use self::mbc2::Mbc2;
+use self::mbc3::Mbc3;
+use self::mbc5::Mbc5;
+pub use self::mbc7::Accelerometer;
+use self::mbc7::Mbc7;
+use self::mmm01::Mmm01;
+use self::rom_only::RomOnly;
+pub use self::rtc::RtcClock;
+
+#[derive(Debug, PartialEq)]
+pub enum SaveError {
+    WrongSize {
+        expected: usize,
+        actual: usize,
+    },
+    /// The cartridge has no battery, so nothing on it survives being switched off.
+    NoBattery,
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::WrongSize { expected, actual } => write!(
+                f,
+                "save is {} bytes, but the cartridge expects {}",
+                actual, expected
+            ),
+            SaveError::NoBattery => write!(f, "the cartridge has no battery to save with"),
+        }
+    }
+}
+
+impl Fail for SaveError {}
+
+/// The memory bank controller of a cartridge, or whatever else is mapped into the cartridge's
+/// part of the address space.
+pub(crate) trait Mbc: Bus {
+    /// Advances whatever on the cartridge runs off the system clock by the given dots, which
+    /// keep their pace in double speed mode.
+    fn tick(&mut self, _dots: u8) {}
+
+    fn set_rtc_clock(&mut self, _clock: RtcClock) {}
+
+    /// Replaces what tilt sensors on the cartridge read.
+    fn set_accelerometer(&mut self, _accelerometer: Box<dyn Accelerometer>) {}
+
+    /// Whether the rumble motor is running.
+    fn rumble(&self) -> bool {
+        false
+    }
+
+    /// Whether the infrared LED is lit.
+    fn infrared_led(&self) -> bool {
+        false
+    }
+
+    /// The state which survives power cycles, in the format of a save file. For most cartridges
+    /// that's just the RAM.
+    fn save(&self) -> Vec<u8>;
+
+    /// Restores the state from a save file made by `save`.
+    fn load_save(&mut self, save: &[u8]) -> Result<(), SaveError>;
+}
+
+/// Reads from a ROM bank. Banks beyond the end of the ROM wrap around, since the upper bank bits
+/// aren't wired up.
+fn read_rom(rom: &[u8], bank: usize, addr: u16) -> u8 {
+    let banks = (rom.len() / ROM_BANK_SIZE).max(1);
+    let offset = (bank % banks) * ROM_BANK_SIZE + usize::from(addr) % ROM_BANK_SIZE;
+    rom.get(offset).cloned().unwrap_or(0xFF)
+}
+
+/// Restores the RAM from a save file with nothing else in it.
+fn load_ram(ram: &mut [u8], save: &[u8]) -> Result<(), SaveError> {
+    if save.len() != ram.len() {
+        return Err(SaveError::WrongSize {
+            expected: ram.len(),
+            actual: save.len(),
+        });
+    }
+    ram.copy_from_slice(save);
+    Ok(())
+}
+
+/// Wraps the cartridge in the memory bank controller given by its header, ready to be mapped
+/// into the cartridge's part of the address space.
+pub(crate) fn load(cartridge: Cartridge) -> Result<Rc<RefCell<dyn Mbc>>, RomError> {
+    Ok(match cartridge.header.cartridge_type.mapper {
+        Mapper::RomOnly => Rc::new(RefCell::new(RomOnly::new(cartridge))),
+        Mapper::Mbc1 => Rc::new(RefCell::new(Mbc1::new(cartridge))),
+        Mapper::Mbc2 => Rc::new(RefCell::new(Mbc2::new(cartridge))),
+        Mapper::Mbc3 => Rc::new(RefCell::new(Mbc3::new(cartridge))),
+        Mapper::Mbc5 => Rc::new(RefCell::new(Mbc5::new(cartridge))),
+        Mapper::Mbc7 => Rc::new(RefCell::new(Mbc7::new(cartridge))),
+        Mapper::Mmm01 => Rc::new(RefCell::new(Mmm01::new(cartridge))),
+        Mapper::HuC1 => Rc::new(RefCell::new(HuC1::new(cartridge))),
+        Mapper::HuC3 => Rc::new(RefCell::new(HuC3::new(cartridge))),
+        mapper => return Err(RomError::UnsupportedMapper(mapper)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::rom::tests::rom;
+
+    #[test]
+    fn test_rom_only() {
+        let mut rom = rom(0x08, 0x00, 0x02);
+        rom[0x0000] = 0x12;
+        rom[0x7FFF] = 0x34;
+        let cartridge = load(Cartridge::from_bytes(rom).unwrap()).unwrap();
+        let mut cartridge = cartridge.borrow_mut();
+        assert_eq!(0x12, cartridge.read_u8(0x0000));
+        assert_eq!(0x34, cartridge.read_u8(0x7FFF));
+
+        cartridge.write_u8(0x0000, 0x56);
+        assert_eq!(0x12, cartridge.read_u8(0x0000));
+        cartridge.write_u8(0xA000, 0x56);
+        assert_eq!(0x56, cartridge.read_u8(0xA000));
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let cartridge = Cartridge::from_bytes(rom(0x20, 0x00, 0x00)).unwrap();
+        assert_eq!(
+            RomError::UnsupportedMapper(Mapper::Mbc6),
+            load(cartridge).unwrap_err()
+        );
+    }
+}
@@ -0,0 +1,51 @@
+use super::{load_ram, Mbc, SaveError};
+use crate::gameboy::mem::Bus;
+use crate::gameboy::rom::Cartridge;
+
+/// A cartridge without a memory bank controller, i.e. 32 kB of ROM mapped straight into the
+/// address space, optionally along with up to 8 kB of RAM.
+#[derive(Debug)]
+pub(crate) struct RomOnly {
+    rom: Box<[u8]>,
+    ram: Box<[u8]>,
+}
+
+impl RomOnly {
+    pub(crate) fn new(cartridge: Cartridge) -> RomOnly {
+        RomOnly {
+            ram: vec![0u8; cartridge.header.ram_size.min(0x2000)].into_boxed_slice(),
+            rom: cartridge.rom,
+        }
+    }
+}
+
+impl Bus for RomOnly {
+    fn read_u8(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x7FFF => self.rom.get(usize::from(addr)).cloned().unwrap_or(0xFF),
+            0xA000..=0xBFFF if !self.ram.is_empty() => {
+                self.ram[usize::from(addr - 0xA000) % self.ram.len()]
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        if let 0xA000..=0xBFFF = addr {
+            if !self.ram.is_empty() {
+                let len = self.ram.len();
+                self.ram[usize::from(addr - 0xA000) % len] = value;
+            }
+        }
+    }
+}
+
+impl Mbc for RomOnly {
+    fn save(&self) -> Vec<u8> {
+        self.ram.to_vec()
+    }
+
+    fn load_save(&mut self, save: &[u8]) -> Result<(), SaveError> {
+        load_ram(&mut self.ram, save)
+    }
+}
@@ -0,0 +1,411 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The number of dots per second, which keep their pace regardless of the CPU's speed.
+const DOTS_PER_SECOND: u32 = 1 << 22;
+
+/// The size of the RTC footer appended to the RAM in save files, as written by VBA-M, BGB and
+/// others. Older versions wrote a 32 bit timestamp, which makes for a 44 byte footer.
+pub(crate) const FOOTER_SIZE: usize = 48;
+pub(crate) const LEGACY_FOOTER_SIZE: usize = 44;
+
+/// What drives the cartridge's real-time clock.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum RtcClock {
+    /// The clock advances with the emulated time, which makes runs reproducible.
+    Emulated,
+    /// The clock follows the host's wall clock, including the time between sessions.
+    #[default]
+    Live,
+}
+
+/// The clock registers, which are selected by writing 0x08-0x0C to 0x4000-0x5FFF.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+struct Registers {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    /// The 9 bit day counter.
+    days: u16,
+    halted: bool,
+    /// Set when the day counter overflows, and only cleared by writing to it.
+    carry: bool,
+}
+
+impl Registers {
+    fn read(&self, select: u8) -> u8 {
+        match select {
+            0x08 => self.seconds & 0x3F,
+            0x09 => self.minutes & 0x3F,
+            0x0A => self.hours & 0x1F,
+            0x0B => self.days as u8,
+            0x0C => (self.days >> 8) as u8 | (self.halted as u8) << 6 | (self.carry as u8) << 7,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, select: u8, value: u8) {
+        match select {
+            0x08 => self.seconds = value & 0x3F,
+            0x09 => self.minutes = value & 0x3F,
+            0x0A => self.hours = value & 0x1F,
+            0x0B => self.days = self.days & 0x100 | u16::from(value),
+            0x0C => {
+                self.days = self.days & 0xFF | u16::from(value & 0x01) << 8;
+                self.halted = value & 0x40 != 0;
+                self.carry = value & 0x80 != 0;
+            }
+            _ => {}
+        }
+    }
+
+    /// Advances the clock by a second. Registers which have been written with out of range
+    /// values keep counting until they overflow their bits, without carrying into the next.
+    fn tick(&mut self) {
+        self.seconds = (self.seconds + 1) & 0x3F;
+        if self.seconds != 60 {
+            return;
+        }
+        self.seconds = 0;
+        self.minutes = (self.minutes + 1) & 0x3F;
+        if self.minutes != 60 {
+            return;
+        }
+        self.minutes = 0;
+        self.hours = (self.hours + 1) & 0x1F;
+        if self.hours != 24 {
+            return;
+        }
+        self.hours = 0;
+        self.days = (self.days + 1) & 0x1FF;
+        if self.days == 0 {
+            self.carry = true;
+        }
+    }
+
+    fn advance(&mut self, mut seconds: u64) {
+        // Tick until the registers are in range, after which the rest can be done in one go.
+        while seconds > 0 && (self.seconds >= 60 || self.minutes >= 60 || self.hours >= 24) {
+            self.tick();
+            seconds -= 1;
+        }
+        let total = seconds
+            + u64::from(self.seconds)
+            + 60 * u64::from(self.minutes)
+            + 3600 * u64::from(self.hours);
+        self.seconds = (total % 60) as u8;
+        self.minutes = (total / 60 % 60) as u8;
+        self.hours = (total / 3600 % 24) as u8;
+        let days = total / 86400 + u64::from(self.days);
+        self.days = (days & 0x1FF) as u16;
+        self.carry |= days > 0x1FF;
+    }
+
+    fn to_footer(self, footer: &mut Vec<u8>) {
+        for select in 0x08..=0x0C {
+            footer.extend_from_slice(&u32::from(self.read(select)).to_le_bytes());
+        }
+    }
+
+    fn from_footer(footer: &[u8]) -> Registers {
+        let mut registers = Registers::default();
+        for (select, value) in (0x08..=0x0C).zip(footer.chunks(4)) {
+            registers.write(select, value[0]);
+        }
+        registers
+    }
+}
+
+/// Counts the seconds passing for a cartridge's clock, in either emulated or wall clock time.
+#[derive(Debug)]
+pub(crate) struct Timebase {
+    clock: RtcClock,
+    /// The dots since the last second, for the emulated clock.
+    dots: u32,
+    /// When the seconds were last counted, for the live clock.
+    synced: SystemTime,
+}
+
+impl Timebase {
+    pub(crate) fn new() -> Timebase {
+        Timebase {
+            clock: Default::default(),
+            dots: 0,
+            synced: SystemTime::now(),
+        }
+    }
+
+    /// Switches clocks, which starts counting anew. Callers should `sync` before switching, so
+    /// that the time passed on the old clock isn't lost.
+    pub(crate) fn set_clock(&mut self, clock: RtcClock) {
+        self.clock = clock;
+        self.reset();
+    }
+
+    /// Counts the dots towards the next second, and returns whether it has passed. Only the
+    /// emulated clock counts dots.
+    pub(crate) fn tick(&mut self, dots: u8) -> bool {
+        if self.clock != RtcClock::Emulated {
+            return false;
+        }
+        self.dots += u32::from(dots);
+        if self.dots < DOTS_PER_SECOND {
+            return false;
+        }
+        self.dots -= DOTS_PER_SECOND;
+        true
+    }
+
+    /// The whole seconds that have passed on the wall clock since they were last counted, for the
+    /// live clock.
+    pub(crate) fn elapsed(&self, now: SystemTime) -> u64 {
+        if self.clock != RtcClock::Live {
+            return 0;
+        }
+        // The wall clock may have been turned back, in which case the time is lost.
+        now.duration_since(self.synced)
+            .unwrap_or_default()
+            .as_secs()
+    }
+
+    /// Counts the seconds that have passed on the wall clock, like `elapsed`, but also marks them
+    /// as counted.
+    pub(crate) fn sync(&mut self) -> u64 {
+        let seconds = self.elapsed(SystemTime::now());
+        self.synced += Duration::from_secs(seconds);
+        seconds
+    }
+
+    /// Restarts the count towards the next second.
+    pub(crate) fn reset(&mut self) {
+        self.dots = 0;
+        self.synced = SystemTime::now();
+    }
+
+    /// Continues counting from a save made at the given UNIX time, which the live clock catches
+    /// up with on the next `sync`.
+    pub(crate) fn resume(&mut self, timestamp: u64) {
+        self.reset();
+        if self.clock == RtcClock::Live {
+            self.synced = UNIX_EPOCH + Duration::from_secs(timestamp);
+        }
+    }
+}
+
+/// The current time as a UNIX timestamp, for save files.
+pub(crate) fn timestamp(now: SystemTime) -> u64 {
+    now.duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0)
+}
+
+/// The MBC3's real-time clock.
+///
+/// The registers count continuously, but the CPU only sees a copy of them, which is updated by
+/// writing 0x00 and then 0x01 to 0x6000-0x7FFF.
+#[derive(Debug)]
+pub(crate) struct Rtc {
+    timebase: Timebase,
+    registers: Registers,
+    latched: Registers,
+    /// Whether 0x00 was the last value written to the latch register.
+    latch_armed: bool,
+}
+
+impl Rtc {
+    pub(crate) fn new() -> Rtc {
+        Rtc {
+            timebase: Timebase::new(),
+            registers: Default::default(),
+            latched: Default::default(),
+            latch_armed: false,
+        }
+    }
+
+    pub(crate) fn set_clock(&mut self, clock: RtcClock) {
+        self.sync();
+        self.timebase.set_clock(clock);
+    }
+
+    pub(crate) fn tick(&mut self, dots: u8) {
+        if !self.registers.halted && self.timebase.tick(dots) {
+            self.registers.tick();
+        }
+    }
+
+    /// The registers as they are right now, which for the live clock means catching up with the
+    /// wall clock.
+    fn current(&self, now: SystemTime) -> Registers {
+        let mut registers = self.registers;
+        if !registers.halted {
+            registers.advance(self.timebase.elapsed(now));
+        }
+        registers
+    }
+
+    fn sync(&mut self) {
+        let seconds = self.timebase.sync();
+        if !self.registers.halted {
+            self.registers.advance(seconds);
+        }
+    }
+
+    pub(crate) fn read(&self, select: u8) -> u8 {
+        self.latched.read(select)
+    }
+
+    pub(crate) fn write(&mut self, select: u8, value: u8) {
+        self.sync();
+        self.registers.write(select, value);
+        self.latched.write(select, value);
+        // Writing the seconds resets the divider that counts towards the next second.
+        if select == 0x08 {
+            self.timebase.reset();
+        }
+    }
+
+    pub(crate) fn write_latch(&mut self, value: u8) {
+        if self.latch_armed && value == 0x01 {
+            self.sync();
+            self.latched = self.registers;
+        }
+        self.latch_armed = value == 0x00;
+    }
+
+    /// Appends the clock in the format of the save file footer: the registers and the latched
+    /// registers as 32 bit values, followed by a 64 bit UNIX timestamp.
+    pub(crate) fn save(&self, footer: &mut Vec<u8>) {
+        let now = SystemTime::now();
+        self.current(now).to_footer(footer);
+        self.latched.to_footer(footer);
+        footer.extend_from_slice(&timestamp(now).to_le_bytes());
+    }
+
+    /// Restores the clock from a save file footer. The live clock also catches up with the time
+    /// that has passed since the save was made.
+    pub(crate) fn load(&mut self, footer: &[u8]) {
+        self.registers = Registers::from_footer(&footer[0..20]);
+        self.latched = Registers::from_footer(&footer[20..40]);
+        let mut timestamp = [0u8; 8];
+        timestamp[..footer.len() - 40].copy_from_slice(&footer[40..]);
+        self.timebase.resume(u64::from_le_bytes(timestamp));
+        self.sync();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emulated_clock() {
+        let mut rtc = Rtc::new();
+        rtc.set_clock(RtcClock::Emulated);
+        rtc.write(0x08, 59);
+        rtc.write(0x09, 59);
+        rtc.write(0x0A, 23);
+        rtc.write(0x0B, 0xFF);
+        rtc.write(0x0C, 0x01);
+        for _ in 0..DOTS_PER_SECOND / 16 {
+            rtc.tick(16);
+        }
+        // Nothing changes until the clock is latched.
+        assert_eq!(59, rtc.read(0x08));
+        rtc.write_latch(0x00);
+        rtc.write_latch(0x01);
+        assert_eq!(0, rtc.read(0x08));
+        assert_eq!(0, rtc.read(0x09));
+        assert_eq!(0, rtc.read(0x0A));
+        assert_eq!(0, rtc.read(0x0B));
+        assert_eq!(0x80, rtc.read(0x0C));
+    }
+
+    #[test]
+    fn test_halt() {
+        let mut rtc = Rtc::new();
+        rtc.set_clock(RtcClock::Emulated);
+        rtc.write(0x0C, 0x40);
+        for _ in 0..DOTS_PER_SECOND / 16 {
+            rtc.tick(16);
+        }
+        rtc.write_latch(0x00);
+        rtc.write_latch(0x01);
+        assert_eq!(0, rtc.read(0x08));
+        assert_eq!(0x40, rtc.read(0x0C));
+    }
+
+    #[test]
+    fn test_latch() {
+        let mut rtc = Rtc::new();
+        rtc.set_clock(RtcClock::Emulated);
+        rtc.registers.seconds = 12;
+        // Only a write of 0x01 directly after 0x00 latches.
+        rtc.write_latch(0x01);
+        assert_eq!(0, rtc.read(0x08));
+        rtc.write_latch(0x00);
+        rtc.write_latch(0x02);
+        rtc.write_latch(0x01);
+        assert_eq!(0, rtc.read(0x08));
+        rtc.write_latch(0x00);
+        rtc.write_latch(0x01);
+        assert_eq!(12, rtc.read(0x08));
+    }
+
+    #[test]
+    fn test_advance() {
+        let mut registers = Registers::default();
+        registers.advance(2 * 86400 + 3 * 3600 + 4 * 60 + 5);
+        assert_eq!((5, 4, 3, 2), registers_tuple(&registers));
+
+        registers.advance(510 * 86400);
+        assert_eq!((5, 4, 3, 0), registers_tuple(&registers));
+        assert!(registers.carry);
+
+        // Out of range values count up to the limit of their bits before wrapping.
+        let mut registers = Registers {
+            seconds: 62,
+            ..Default::default()
+        };
+        registers.advance(3);
+        assert_eq!((1, 0, 0, 0), registers_tuple(&registers));
+    }
+
+    fn registers_tuple(registers: &Registers) -> (u8, u8, u8, u16) {
+        (
+            registers.seconds,
+            registers.minutes,
+            registers.hours,
+            registers.days,
+        )
+    }
+
+    #[test]
+    fn test_footer() {
+        let mut rtc = Rtc::new();
+        rtc.set_clock(RtcClock::Emulated);
+        rtc.write(0x0A, 13);
+        rtc.write(0x0C, 0x81);
+        let mut footer = Vec::new();
+        rtc.save(&mut footer);
+        assert_eq!(FOOTER_SIZE, footer.len());
+        assert_eq!(&[13, 0, 0, 0], &footer[8..12]);
+        assert_eq!(&[0x81, 0, 0, 0], &footer[16..20]);
+
+        let mut restored = Rtc::new();
+        restored.set_clock(RtcClock::Emulated);
+        restored.load(&footer);
+        assert_eq!(rtc.registers, restored.registers);
+        assert_eq!(rtc.latched, restored.latched);
+
+        // The live clock catches up with the time since the save.
+        let mut footer = footer[..LEGACY_FOOTER_SIZE].to_vec();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            - 3600;
+        footer[40..].copy_from_slice(&(timestamp as u32).to_le_bytes());
+        let mut restored = Rtc::new();
+        restored.load(&footer);
+        assert_eq!(14, restored.registers.hours);
+    }
+}
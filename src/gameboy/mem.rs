@@ -1,35 +1,428 @@
+use std::{cell::RefCell, fmt, ops::RangeInclusive, rc::Rc};
+
+use super::apu::Apu;
+use super::boot::{BootRom, Model};
+use super::dma::Dma;
+use super::interrupt::Interrupt;
+use super::ppu::{FrameClock, Ppu};
+use super::timer::Timer;
+
+const INTERRUPT_FLAG: u16 = 0xFF0F;
+const BOOT_ROM_DISABLE: u16 = 0xFF50;
+const SPEED_SWITCH: u16 = 0xFF4D;
+const DMA: u16 = 0xFF46;
+/// 0xFF40-0xFF4B, the PPU's registers, apart from DMA at 0xFF46.
+const LCD_REGISTERS_START: u16 = 0xFF40;
+const LCD_REGISTERS_END: u16 = 0xFF4B;
+/// 0xFF04-0xFF07, DIV, TIMA, TMA and TAC.
+const TIMER_REGISTERS_START: u16 = 0xFF04;
+const TIMER_REGISTERS_END: u16 = 0xFF07;
+/// 0xFF10-0xFF3F, the APU's registers and wave RAM.
+const APU_REGISTERS_START: u16 = 0xFF10;
+const APU_REGISTERS_END: u16 = 0xFF3F;
+
+/// `Bus` is implemented by everything that can be read from and written to through an address,
+/// e.g. the MMU itself, but also the cartridge and the peripherals mapped into its address space.
+///
+/// Addresses are always absolute, i.e. a handler mapped to 0x8000-0x9FFF gets 0x8000 for the
+/// first byte of its range.
+pub(crate) trait Bus: fmt::Debug {
+    fn read_u8(&self, addr: u16) -> u8;
+    fn write_u8(&mut self, addr: u16, value: u8);
+}
+
+/// 16 bit accesses, for tests. The CPU accesses the bus a byte per M-cycle, so it never makes
+/// these.
+#[cfg(test)]
+pub(crate) trait BusExt: Bus {
+    /// Reads a little-endian value, i.e. the low byte is stored at `addr`.
+    fn read_u16(&self, addr: u16) -> u16 {
+        u16::from(self.read_u8(addr)) | u16::from(self.read_u8(addr.wrapping_add(1))) << 8
+    }
+
+    /// Writes a little-endian value, i.e. the low byte is stored at `addr`.
+    fn write_u16(&mut self, addr: u16, value: u16) {
+        self.write_u8(addr, (value & 0xFF) as u8);
+        self.write_u8(addr.wrapping_add(1), (value >> 8) as u8);
+    }
+}
+
+#[cfg(test)]
+impl<T: Bus + ?Sized> BusExt for T {}
+
+/// A shared handler for a range of addresses. It's shared so that whoever mapped it can keep a
+/// handle to it, e.g. to advance a peripheral's state between memory accesses.
+pub(crate) type Handler = Rc<RefCell<dyn Bus>>;
+
+/// The regions of the 16 bit address space, as seen by the CPU.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) enum Region {
+    /// 0x0000-0x3FFF, the fixed first bank of the cartridge ROM.
+    RomBank0,
+    /// 0x4000-0x7FFF, the switchable ROM bank.
+    RomBankN,
+    /// 0x8000-0x9FFF
+    VideoRam,
+    /// 0xA000-0xBFFF, RAM on the cartridge, if any.
+    ExternalRam,
+    /// 0xC000-0xDFFF
+    WorkRam,
+    /// 0xE000-0xFDFF, a mirror of 0xC000-0xDDFF.
+    EchoRam,
+    /// 0xFE00-0xFE9F, the sprite attribute table.
+    Oam,
+    /// 0xFEA0-0xFEFF
+    Unusable,
+    /// 0xFF00-0xFF7F
+    Io,
+    /// 0xFF80-0xFFFE
+    HighRam,
+    /// 0xFFFF
+    InterruptEnable,
+}
+
+impl Region {
+    pub(crate) fn of(addr: u16) -> Region {
+        use self::Region::*;
+        match addr {
+            0x0000..=0x3FFF => RomBank0,
+            0x4000..=0x7FFF => RomBankN,
+            0x8000..=0x9FFF => VideoRam,
+            0xA000..=0xBFFF => ExternalRam,
+            0xC000..=0xDFFF => WorkRam,
+            0xE000..=0xFDFF => EchoRam,
+            0xFE00..=0xFE9F => Oam,
+            0xFEA0..=0xFEFF => Unusable,
+            0xFF00..=0xFF7F => Io,
+            0xFF80..=0xFFFE => HighRam,
+            0xFFFF => InterruptEnable,
+        }
+    }
+}
+
+/// The two buses that OAM DMA can read from, which the CPU can't use while it does.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum DmaBus {
+    /// The cartridge and work RAM.
+    External,
+    Video,
+}
+
+impl DmaBus {
+    fn of(addr: u16) -> Option<DmaBus> {
+        match addr {
+            0x0000..=0x7FFF | 0xA000..=0xFDFF => Some(DmaBus::External),
+            0x8000..=0x9FFF => Some(DmaBus::Video),
+            _ => None,
+        }
+    }
+}
+
+/// A plain block of memory, mapped from `base` and onwards.
+#[derive(Debug)]
+pub(crate) struct Memory {
+    base: u16,
+    data: Box<[u8]>,
+}
+
+impl Memory {
+    pub fn new(base: u16, size: usize) -> Memory {
+        Memory {
+            base,
+            data: vec![0u8; size].into_boxed_slice(),
+        }
+    }
+}
+
+impl Bus for Memory {
+    fn read_u8(&self, addr: u16) -> u8 {
+        self.data[usize::from(addr - self.base)]
+    }
+
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        self.data[usize::from(addr - self.base)] = value;
+    }
+}
+
+#[derive(Debug)]
+struct Mapping {
+    range: RangeInclusive<u16>,
+    handler: Handler,
+}
+
 #[derive(Debug)]
 pub(crate) struct MMU {
-    mem: Box<[u8]>,
+    /// The model being emulated, which decides e.g. which of the CGB registers exist.
+    model: Model,
+    /// Handlers registered for parts of the address space, which take precedence over the
+    /// MMU's own memory. The most recently mapped handler comes first.
+    mappings: Vec<Mapping>,
+    /// Overlaid on everything else, until it's disabled through 0xFF50.
+    boot_rom: Option<BootRom>,
+    ppu: Ppu,
+    /// Stands in for the PPU once it's been disconnected.
+    frame_clock: Option<FrameClock>,
+    apu: Apu,
+    dma: Dma,
+    timer: Timer,
+    work_ram: Memory,
+    high_ram: Memory,
+    interrupt_flag: u8,
+    interrupt_enable: u8,
+    double_speed: bool,
+    speed_switch_armed: bool,
+    /// STOP has stopped the system clock, which the timer runs off.
+    stopped: bool,
+    /// Set by writes to the cartridge RAM, which is how changes to save data are noticed.
+    external_ram_written: bool,
 }
 
 impl MMU {
     pub fn new() -> MMU {
-        MMU {
-            // Memory is 16 kB (simplified)
-            mem: vec![0u8; 1 << 14].into_boxed_slice(),
+        let mut mmu = MMU {
+            model: Model::default(),
+            mappings: Vec::new(),
+            boot_rom: None,
+            ppu: Ppu::new(),
+            frame_clock: None,
+            apu: Apu::new(),
+            dma: Dma::default(),
+            timer: Timer::default(),
+            work_ram: Memory::new(0xC000, 0x2000),
+            high_ram: Memory::new(0xFF80, 0x7F),
+            interrupt_flag: 0,
+            interrupt_enable: 0,
+            double_speed: false,
+            speed_switch_armed: false,
+            stopped: false,
+            external_ram_written: false,
+        };
+        // Without a cartridge, its address space is backed by plain memory.
+        mmu.map(
+            0x0000..=0x7FFF,
+            Rc::new(RefCell::new(Memory::new(0x0000, 0x8000))),
+        );
+        mmu.map(
+            0xA000..=0xBFFF,
+            Rc::new(RefCell::new(Memory::new(0xA000, 0x2000))),
+        );
+        mmu
+    }
+
+    /// Routes all accesses within `range` to `handler`, shadowing whatever was there before.
+    pub fn map(&mut self, range: RangeInclusive<u16>, handler: Handler) {
+        self.mappings.insert(0, Mapping { range, handler });
+    }
+
+    /// Maps the cartridge into its parts of the address space, i.e. the ROM banks and the
+    /// external RAM.
+    pub fn insert_cartridge(&mut self, cartridge: Handler) {
+        self.map(0x0000..=0x7FFF, cartridge.clone());
+        self.map(0xA000..=0xBFFF, cartridge);
+    }
+
+    /// Overlays the boot ROM on the cartridge, until the boot ROM disables it by writing to 0xFF50.
+    pub fn map_boot_rom(&mut self, boot_rom: BootRom) {
+        self.boot_rom = Some(boot_rom);
+    }
+
+    fn handler(&self, addr: u16) -> Option<&Handler> {
+        self.mappings
+            .iter()
+            .find(|mapping| mapping.range.contains(&addr))
+            .map(|mapping| &mapping.handler)
+    }
+
+    /// Sets the interrupt's bit in IF, which will have it serviced once it's enabled.
+    pub fn request_interrupt(&mut self, interrupt: Interrupt) {
+        self.interrupt_flag |= interrupt.mask();
+    }
+
+    /// Clears the interrupt's bit in IF, which happens when the CPU services it.
+    pub fn acknowledge_interrupt(&mut self, interrupt: Interrupt) {
+        self.interrupt_flag &= !interrupt.mask();
+    }
+
+    /// Advances the peripherals by the given number of M-cycles, and requests the interrupts
+    /// they raised.
+    pub fn tick(&mut self, cycles: u8) {
+        for _ in 0..cycles {
+            if let Some((source, index)) = self.dma.tick() {
+                let value = self.read_unblocked(source);
+                self.ppu.write_oam(index, value);
+            }
+            if !self.stopped && self.timer.tick() {
+                self.request_interrupt(Interrupt::Timer);
+            }
+            self.apu.tick(if self.double_speed { 2 } else { 4 });
+        }
+        // The peripherals keep their pace in double speed mode, so they see half the dots.
+        let dots = u16::from(cycles) * if self.double_speed { 2 } else { 4 };
+        self.interrupt_flag |= match &mut self.frame_clock {
+            Some(frame_clock) => frame_clock.tick(dots),
+            None => self.ppu.tick(dots),
+        };
+    }
+
+    /// Stops ticking the PPU, for running without a screen. VRAM, OAM and the LCD registers stay
+    /// accessible, but nothing is drawn, and VBlank is requested once a frame regardless of LCDC.
+    pub fn disconnect_ppu(&mut self) {
+        self.frame_clock = Some(FrameClock::default());
+    }
+
+    /// Whether a frame has been completed since the last call, by the PPU or in its place.
+    pub fn take_frame_completed(&mut self) -> bool {
+        match &mut self.frame_clock {
+            Some(frame_clock) => frame_clock.take_frame_completed(),
+            None => self.ppu.take_frame_completed(),
         }
     }
 
-    pub fn write_u8(&mut self, addr: u16, value: u8) {
-        self.mem[addr as usize] = value;
+    pub fn ppu(&self) -> &Ppu {
+        &self.ppu
+    }
+
+    pub fn ppu_mut(&mut self) -> &mut Ppu {
+        &mut self.ppu
+    }
+
+    pub fn apu_mut(&mut self) -> &mut Apu {
+        &mut self.apu
+    }
+
+    /// Whether a CGB speed switch has been requested through KEY1, to be performed by STOP.
+    pub fn speed_switch_armed(&self) -> bool {
+        self.speed_switch_armed
+    }
+
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    pub fn set_model(&mut self, model: Model) {
+        self.model = model;
+    }
+
+    pub fn double_speed(&self) -> bool {
+        self.double_speed
+    }
+
+    /// Toggles between normal and double speed, and disarms the request in KEY1.
+    pub fn switch_speed(&mut self) {
+        self.double_speed = !self.double_speed;
+        self.speed_switch_armed = false;
+    }
+
+    /// Resets DIV and stops the timer, as STOP does, until `resume`.
+    pub fn stop(&mut self) {
+        self.timer.reset_div();
+        self.stopped = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.stopped = false;
     }
 
-    pub fn read_u8(&self, addr: u16) -> u8 {
-        self.mem[addr as usize]
+    /// Whether the cartridge RAM has been written since the last call.
+    pub fn take_external_ram_written(&mut self) -> bool {
+        std::mem::replace(&mut self.external_ram_written, false)
     }
 
-    pub fn read_u8_mut(&mut self, addr: u16) -> &mut u8 {
-        &mut self.mem[addr as usize]
+    /// The interrupts which are both requested and enabled.
+    pub fn pending_interrupts(&self) -> u8 {
+        self.interrupt_flag & self.interrupt_enable & 0x1F
     }
+}
 
-    pub fn read_u16(&self, addr: u16) -> u16 {
-        u16::from(self.read_u8(addr + 1)) | u16::from(self.read_u8(addr)) << 8
+impl MMU {
+    /// What the CPU sees at `addr` while OAM DMA is running, unless it can access it. Only HRAM
+    /// and the I/O registers are left alone. Reading from the bus that the transfer uses gets
+    /// the byte being copied, and everything else reads as 0xFF.
+    fn dma_conflict(&self, addr: u16) -> Option<u8> {
+        let source = self.dma.source()?;
+        match Region::of(addr) {
+            Region::HighRam | Region::Io | Region::InterruptEnable => None,
+            _ if DmaBus::of(addr) == DmaBus::of(source) => Some(self.read_unblocked(source)),
+            _ => Some(0xFF),
+        }
     }
 
-    pub fn write_u16(&mut self, addr: u16, value: u16) {
-        self.mem[addr as usize] = (value >> 8) as u8;
-        self.mem[addr as usize + 1] = (value & 0xFF) as u8;
+    /// Reads `addr` regardless of OAM DMA, which is how the transfer itself reads.
+    fn read_unblocked(&self, addr: u16) -> u8 {
+        if let Some(value) = self
+            .boot_rom
+            .as_ref()
+            .and_then(|boot_rom| boot_rom.read(addr))
+        {
+            return value;
+        }
+        if let Some(handler) = self.handler(addr) {
+            return handler.borrow().read_u8(addr);
+        }
+        match Region::of(addr) {
+            // Nothing drives the bus, so it reads as all ones.
+            Region::RomBank0 | Region::RomBankN | Region::ExternalRam => 0xFF,
+            Region::VideoRam | Region::Oam => self.ppu.read_u8(addr),
+            Region::WorkRam => self.work_ram.read_u8(addr),
+            Region::EchoRam => self.work_ram.read_u8(addr - 0x2000),
+            Region::Unusable => 0x00,
+            Region::Io => match addr {
+                TIMER_REGISTERS_START..=TIMER_REGISTERS_END => self.timer.read_u8(addr),
+                APU_REGISTERS_START..=APU_REGISTERS_END => self.apu.read_u8(addr),
+                DMA => self.dma.register(),
+                LCD_REGISTERS_START..=LCD_REGISTERS_END => self.ppu.read_u8(addr),
+                // The upper three bits of IF are unused and always read as set.
+                INTERRUPT_FLAG => self.interrupt_flag | 0xE0,
+                // Only the CGB has KEY1, and with it the double speed mode.
+                SPEED_SWITCH if self.model == Model::Cgb => {
+                    0x7E | (self.double_speed as u8) << 7 | self.speed_switch_armed as u8
+                }
+                _ => 0xFF,
+            },
+            Region::HighRam => self.high_ram.read_u8(addr),
+            Region::InterruptEnable => self.interrupt_enable,
+        }
+    }
+}
+
+impl Bus for MMU {
+    fn read_u8(&self, addr: u16) -> u8 {
+        self.dma_conflict(addr)
+            .unwrap_or_else(|| self.read_unblocked(addr))
+    }
+
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        if self.dma_conflict(addr).is_some() {
+            return;
+        }
+        if let Region::ExternalRam = Region::of(addr) {
+            self.external_ram_written = true;
+        }
+        if let Some(handler) = self.handler(addr) {
+            handler.borrow_mut().write_u8(addr, value);
+            return;
+        }
+        match Region::of(addr) {
+            Region::RomBank0 | Region::RomBankN | Region::ExternalRam | Region::Unusable => {}
+            Region::VideoRam | Region::Oam => self.ppu.write_u8(addr, value),
+            Region::WorkRam => self.work_ram.write_u8(addr, value),
+            Region::EchoRam => self.work_ram.write_u8(addr - 0x2000, value),
+            Region::Io => match addr {
+                TIMER_REGISTERS_START..=TIMER_REGISTERS_END => self.timer.write_u8(addr, value),
+                APU_REGISTERS_START..=APU_REGISTERS_END => self.apu.write_u8(addr, value),
+                DMA => self.dma.write_register(value),
+                LCD_REGISTERS_START..=LCD_REGISTERS_END => self.ppu.write_u8(addr, value),
+                INTERRUPT_FLAG => self.interrupt_flag = value & 0x1F,
+                SPEED_SWITCH if self.model == Model::Cgb => {
+                    self.speed_switch_armed = value & 0x01 != 0
+                }
+                // Once disabled, the boot ROM can't be mapped in again.
+                BOOT_ROM_DISABLE if value != 0 => self.boot_rom = None,
+                _ => {}
+            },
+            Region::HighRam => self.high_ram.write_u8(addr, value),
+            Region::InterruptEnable => self.interrupt_enable = value,
+        }
     }
 }
 
@@ -41,22 +434,101 @@ impl Default for MMU {
 
 #[test]
 fn test_read_u16() {
-    let mmu = MMU {
-        mem: vec![0x12, 0x34].into_boxed_slice(),
-    };
-    assert_eq!(0x12, mmu.read_u8(0));
-    assert_eq!(0x34, mmu.read_u8(1));
-    assert_eq!(0x1234, mmu.read_u16(0));
+    let mut mmu = MMU::new();
+    mmu.write_u8(0xC000, 0x34);
+    mmu.write_u8(0xC001, 0x12);
+    assert_eq!(0x34, mmu.read_u8(0xC000));
+    assert_eq!(0x12, mmu.read_u8(0xC001));
+    assert_eq!(0x1234, mmu.read_u16(0xC000));
 }
 
 #[test]
 fn test_write_u16() {
-    let mut mmu = MMU {
-        mem: vec![0, 0].into_boxed_slice(),
-    };
-    mmu.write_u16(0, 0x1234);
-    assert_eq!(0x12, mmu.read_u8(0));
-    assert_eq!(0x34, mmu.read_u8(1));
+    let mut mmu = MMU::new();
+    mmu.write_u16(0xC000, 0x1234);
+    assert_eq!(0x34, mmu.read_u8(0xC000));
+    assert_eq!(0x12, mmu.read_u8(0xC001));
+}
+
+#[test]
+fn test_regions() {
+    assert_eq!(Region::RomBank0, Region::of(0x0000));
+    assert_eq!(Region::RomBankN, Region::of(0x7FFF));
+    assert_eq!(Region::VideoRam, Region::of(0x8000));
+    assert_eq!(Region::ExternalRam, Region::of(0xBFFF));
+    assert_eq!(Region::WorkRam, Region::of(0xC000));
+    assert_eq!(Region::EchoRam, Region::of(0xFDFF));
+    assert_eq!(Region::Oam, Region::of(0xFE9F));
+    assert_eq!(Region::Unusable, Region::of(0xFEA0));
+    assert_eq!(Region::Io, Region::of(0xFF7F));
+    assert_eq!(Region::HighRam, Region::of(0xFF80));
+    assert_eq!(Region::InterruptEnable, Region::of(0xFFFF));
+}
+
+#[test]
+fn test_echo_ram() {
+    let mut mmu = MMU::new();
+    mmu.write_u8(0xC123, 0x42);
+    assert_eq!(0x42, mmu.read_u8(0xE123));
+    mmu.write_u8(0xFDFF, 0x24);
+    assert_eq!(0x24, mmu.read_u8(0xDDFF));
+}
+
+#[test]
+fn test_unmapped() {
+    let mut mmu = MMU::new();
+    mmu.write_u8(0xFEA0, 0x42);
+    assert_eq!(0x00, mmu.read_u8(0xFEA0));
+    mmu.write_u8(0xFF7F, 0x42);
+    assert_eq!(0xFF, mmu.read_u8(0xFF7F));
+}
+
+#[test]
+fn test_map_handler() {
+    let mut mmu = MMU::new();
+    let handler = Rc::new(RefCell::new(Memory::new(0xFF10, 0x30)));
+    mmu.map(0xFF10..=0xFF3F, handler.clone());
+    mmu.write_u8(0xFF10, 0x42);
+    mmu.write_u8(0xFF3F, 0x24);
+    assert_eq!(0x42, handler.borrow().read_u8(0xFF10));
+    assert_eq!(0x24, mmu.read_u8(0xFF3F));
+    // LCDC, which is the PPU's.
+    assert_eq!(0x00, mmu.read_u8(0xFF40));
+}
+
+#[test]
+fn test_oam_dma() {
+    let mut mmu = MMU::new();
+    for i in 0..0xA0 {
+        mmu.write_u8(0xC100 + i, i as u8);
+    }
+    mmu.write_u8(0xFF80, 0x42);
+    mmu.write_u8(0xFF46, 0xC1);
+    // OAM is still accessible until the transfer has started.
+    assert_eq!(0x00, mmu.read_u8(0xFE00));
+    mmu.tick(2);
+    // Reading from the same bus gets the byte being copied.
+    assert_eq!(0x01, mmu.read_u8(0xD000));
+    assert_eq!(0xFF, mmu.read_u8(0x8000));
+    assert_eq!(0xFF, mmu.read_u8(0xFE00));
+    assert_eq!(0x42, mmu.read_u8(0xFF80));
+    assert_eq!(0xC1, mmu.read_u8(0xFF46));
+    mmu.write_u8(0xC000, 0x24);
+    mmu.tick(0x9F);
+    assert_eq!(0x00, mmu.read_u8(0xC000));
+    assert_eq!(0x00, mmu.read_u8(0xFE00));
+    assert_eq!(0x9F, mmu.read_u8(0xFE9F));
+}
+
+#[test]
+fn test_timer_interrupt() {
+    let mut mmu = MMU::new();
+    mmu.write_u8(0xFF07, 0x05);
+    mmu.write_u8(0xFF05, 0xFF);
+    mmu.tick(4);
+    assert_eq!(0xE0, mmu.read_u8(INTERRUPT_FLAG));
+    mmu.tick(1);
+    assert_eq!(0xE0 | Interrupt::Timer.mask(), mmu.read_u8(INTERRUPT_FLAG));
 }
 
 pub(crate) trait Read {
@@ -1,34 +1,574 @@
+use std::{cell::RefCell, rc::Rc};
+
+use failure::Error;
+
+pub(crate) mod apu;
+pub(crate) mod boot;
 mod cpu;
+mod dma;
+pub(crate) mod event;
+pub(crate) mod gbs;
 mod instr;
+mod interrupt;
+pub(crate) mod mbc;
 mod mem;
+pub(crate) mod ppu;
+pub(crate) mod rom;
+pub(crate) mod save;
+mod timer;
+pub(crate) mod vgm;
+pub(crate) mod wav;
+
+use self::apu::{Channel, RegisterLog};
+use self::boot::{BootRom, Model};
+use self::cpu::Mode;
+use self::event::{Event, EventQueue};
+use self::gbs::{GbsFile, GbsRom};
+use self::interrupt::Interrupt;
+use self::mbc::{Accelerometer, Mbc, RtcClock, SaveError};
+use self::mem::Bus;
+use self::ppu::Renderer;
+use self::rom::Cartridge;
+
+/// The number of M-cycles the PPU takes for a frame, i.e. 154 lines of 456 dots.
+const CYCLES_PER_FRAME: u32 = 154 * 456 / 4;
 
 #[derive(Debug, Default)]
-pub(crate) struct GameBoy {
+pub struct GameBoy {
     cpu: cpu::CPU,
     mmu: mem::MMU,
+    cartridge: Option<Rc<RefCell<dyn Mbc>>>,
+    /// Whether the cartridge has a battery, which keeps its save data when switched off.
+    battery: bool,
+    rumble: bool,
+    infrared_led: bool,
+    events: EventQueue,
+    /// The M-cycles of the current step that the rest of the system has already been ticked for,
+    /// as the CPU accessed the bus.
+    cycles_ticked: u8,
 }
 
 impl GameBoy {
-    pub fn run(&mut self) {
+    /// Creates a DMG with the cartridge inserted, in the state that the boot ROM leaves it.
+    pub fn new(cartridge: Cartridge) -> Result<GameBoy, Error> {
+        GameBoy::with_model(cartridge, Model::Dmg, None)
+    }
+
+    /// Creates a Game Boy of the given model with the cartridge inserted. With a boot ROM, it
+    /// starts out running the boot ROM from 0x0000. Without one, it starts out in the state that
+    /// the model's boot ROM leaves it.
+    pub fn with_model(
+        cartridge: Cartridge,
+        model: Model,
+        boot_rom: Option<Vec<u8>>,
+    ) -> Result<GameBoy, Error> {
+        let mut gb = GameBoy {
+            battery: cartridge.header.cartridge_type.battery,
+            ..Default::default()
+        };
+        let header_checksum = cartridge.header.header_checksum;
+        let cartridge = mbc::load(cartridge)?;
+        gb.mmu.set_model(model);
+        gb.mmu.insert_cartridge(cartridge.clone());
+        gb.cartridge = Some(cartridge);
+        match boot_rom {
+            Some(boot_rom) => gb.mmu.map_boot_rom(BootRom::new(model, boot_rom)?),
+            None => gb.skip_boot(model, header_checksum),
+        }
+        Ok(gb)
+    }
+
+    /// Creates a Game Boy that plays a song of the GBS file, counting from 1, with no screen.
+    ///
+    /// The driver in the ROM calls init with the song number and then waits for the interrupts
+    /// that call play. The samples are taken as usual, e.g. frame by frame with `run_frame`.
+    pub fn with_gbs(gbs: &GbsFile, song: u8) -> Result<GameBoy, Error> {
+        gbs.check_song(song)?;
+        let header = &gbs.header;
+        let mut gb = GameBoy::default();
+        gb.mmu.disconnect_ppu();
+        gb.mmu
+            .insert_cartridge(Rc::new(RefCell::new(GbsRom::new(gbs))));
+        // The APU starts out on, at full volume, with every channel going to both sides.
+        gb.mmu.write_u8(0xFF26, 0x80);
+        gb.mmu.write_u8(0xFF25, 0xFF);
+        gb.mmu.write_u8(0xFF24, 0x77);
+        gb.mmu.write_u8(0xFF05, header.timer_modulo);
+        gb.mmu.write_u8(0xFF06, header.timer_modulo);
+        gb.mmu.write_u8(0xFF07, header.timer_control & 0x07);
+        let interrupt = if header.timer_interrupt() {
+            Interrupt::Timer
+        } else {
+            Interrupt::VBlank
+        };
+        gb.mmu.write_u8(0xFFFF, interrupt.mask());
+        if header.double_speed() {
+            // Only the CGB has a double speed mode.
+            gb.mmu.set_model(Model::Cgb);
+            gb.mmu.switch_speed();
+        }
+        let register = &mut gb.cpu.register;
+        *register.af = u16::from(song - 1) << 8;
+        *register.sp = header.stack_pointer;
+        *register.pc = gbs::DRIVER;
+        Ok(gb)
+    }
+
+    pub fn model(&self) -> Model {
+        self.mmu.model()
+    }
+
+    /// Selects what drives the real-time clock of cartridges that have one.
+    pub fn set_rtc_clock(&mut self, clock: RtcClock) {
+        if let Some(cartridge) = &self.cartridge {
+            cartridge.borrow_mut().set_rtc_clock(clock);
+        }
+    }
+
+    /// Replaces what the tilt sensor of MBC7 cartridges reads, which is level by default.
+    pub fn set_accelerometer(&mut self, accelerometer: Box<dyn Accelerometer>) {
+        if let Some(cartridge) = &self.cartridge {
+            cartridge.borrow_mut().set_accelerometer(accelerometer);
+        }
+    }
+
+    /// Whether the cartridge has a battery, and so has save data.
+    pub fn has_battery(&self) -> bool {
+        self.battery
+    }
+
+    /// The raw contents of a save file for the cartridge, i.e. its RAM followed by e.g. the state
+    /// of its real-time clock. Cartridges without a battery have none.
+    pub fn save_data(&self) -> Option<Vec<u8>> {
+        if !self.battery {
+            return None;
+        }
+        self.cartridge
+            .as_ref()
+            .map(|cartridge| cartridge.borrow().save())
+    }
+
+    /// Restores the cartridge from the raw contents of a save file.
+    pub fn load_save_data(&mut self, save: &[u8]) -> Result<(), Error> {
+        match &self.cartridge {
+            Some(cartridge) if self.battery => cartridge.borrow_mut().load_save(save)?,
+            _ => return Err(SaveError::NoBattery.into()),
+        }
+        Ok(())
+    }
+
+    /// Whether the save data may have changed since the last call, i.e. whether the cartridge RAM
+    /// has been written.
+    pub fn save_data_changed(&mut self) -> bool {
+        self.mmu.take_external_ram_written() && self.battery
+    }
+
+    /// Runs until an error occurs, e.g. an illegal opcode is executed.
+    pub fn run(&mut self) -> Result<(), Error> {
         loop {
-            let opcode = self.fetch();
+            self.step()?;
+        }
+    }
+
+    /// Sets up the registers the way the model's boot ROM leaves them when it hands over control
+    /// to the cartridge at 0x0100.
+    fn skip_boot(&mut self, model: Model, header_checksum: u8) {
+        let [af, bc, de, hl] = model.post_boot_registers(header_checksum);
+        let register = &mut self.cpu.register;
+        *register.af = af;
+        *register.bc = bc;
+        *register.de = de;
+        *register.hl = hl;
+        *register.sp = 0xFFFE;
+        *register.pc = 0x0100;
+        // The boot ROM leaves the LCD on, with the background showing the logo it scrolled in.
+        self.mmu.write_u8(0xFF40, 0x91);
+        self.mmu.write_u8(0xFF47, 0xFC);
+        // It also leaves the APU on, after playing the chime on channel 1.
+        self.mmu.write_u8(0xFF26, 0x80);
+        self.mmu.write_u8(0xFF11, 0x80);
+        self.mmu.write_u8(0xFF12, 0xF3);
+        self.mmu.write_u8(0xFF24, 0x77);
+        self.mmu.write_u8(0xFF25, 0xF3);
+    }
+
+    /// Runs the CPU for a single step, and then lets the rest of the system catch up with it.
+    /// Returns the number of M-cycles that passed.
+    pub fn step(&mut self) -> Result<u8, Error> {
+        self.cycles_ticked = 0;
+        let cycles = self.step_cpu()?;
+        // Cycles without a memory access, like the internal ones of `ADD HL,BC`, are caught up
+        // with at the end.
+        self.mmu.tick(cycles.saturating_sub(self.cycles_ticked));
+        if let Some(cartridge) = &self.cartridge {
+            let mut cartridge = cartridge.borrow_mut();
+            // Like the peripherals, the cartridge keeps its pace in double speed mode.
+            cartridge.tick(cycles * if self.mmu.double_speed() { 2 } else { 4 });
+            if cartridge.rumble() != self.rumble {
+                self.rumble = !self.rumble;
+                self.events.push(Event::Rumble(self.rumble));
+            }
+            if cartridge.infrared_led() != self.infrared_led {
+                self.infrared_led = !self.infrared_led;
+                self.events.push(Event::InfraredLed(self.infrared_led));
+            }
+        }
+        Ok(cycles)
+    }
+
+    /// Selects how the PPU draws the picture, which takes effect from the next line on.
+    pub fn set_renderer(&mut self, renderer: Renderer) {
+        self.mmu.ppu_mut().set_renderer(renderer);
+    }
+
+    /// The most recently drawn frame, as `SCREEN_WIDTH` by `SCREEN_HEIGHT` shades from 0 (lightest)
+    /// to 3 (darkest), row by row.
+    pub fn frame(&self) -> &[u8] {
+        self.mmu.ppu().frame()
+    }
+
+    /// Runs until the PPU has drawn a whole frame and entered VBlank. While the LCD is off, it
+    /// runs for as long as a frame would have taken instead.
+    pub fn run_frame(&mut self) -> Result<(), Error> {
+        self.mmu.take_frame_completed();
+        // The CPU gets through twice as many cycles per frame in double speed mode.
+        let frame_cycles = CYCLES_PER_FRAME << self.mmu.double_speed() as u8;
+        let mut cycles = 0;
+        while cycles < frame_cycles {
+            cycles += u32::from(self.step()?);
+            if self.mmu.take_frame_completed() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the rate in Hz at which the APU's output is sampled, where 0 (the default) turns
+    /// sampling off.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.mmu.apu_mut().set_sample_rate(sample_rate);
+    }
+
+    /// Takes the audio samples produced since the last call, as left and right pairs.
+    pub fn take_samples(&mut self) -> Vec<[i16; 2]> {
+        self.mmu.apu_mut().take_samples()
+    }
+
+    /// Leaves the channel out of the samples, or puts it back in.
+    pub fn set_muted(&mut self, channel: Channel, muted: bool) {
+        self.mmu.apu_mut().set_muted(channel, muted);
+    }
+
+    /// Limits the samples to the channels that are soloed, as long as there are any.
+    pub fn set_solo(&mut self, channel: Channel, solo: bool) {
+        self.mmu.apu_mut().set_solo(channel, solo);
+    }
+
+    /// Sets whether each channel is also sampled on its own, for `take_channel_samples`.
+    pub fn set_channel_sampling(&mut self, enabled: bool) {
+        self.mmu.apu_mut().set_channel_sampling(enabled);
+    }
+
+    /// Takes the samples of the channel on its own produced since the last call. Muting and
+    /// soloing don't affect these.
+    pub fn take_channel_samples(&mut self, channel: Channel) -> Vec<[i16; 2]> {
+        self.mmu.apu_mut().take_channel_samples(channel)
+    }
+
+    /// Starts logging the writes to the APU's registers, e.g. for `VgmWriter`, or stops it. The log
+    /// starts out with writes that recreate the APU's current state.
+    pub fn set_register_logging(&mut self, enabled: bool) {
+        self.mmu.apu_mut().set_register_logging(enabled);
+    }
+
+    /// Takes the writes to the APU's registers logged since the last call.
+    pub fn take_register_log(&mut self) -> RegisterLog {
+        self.mmu.apu_mut().take_register_log()
+    }
+
+    /// Signals that a button has been pressed, which requests the joypad interrupt and wakes the
+    /// CPU if STOP has put it to sleep.
+    pub fn joypad_input(&mut self) {
+        self.mmu.request_interrupt(Interrupt::Joypad);
+        if self.cpu.mode == Mode::Stopped {
+            self.cpu.mode = Mode::Running;
+            self.mmu.resume();
+        }
+    }
+
+    /// Takes the oldest event that hasn't been handled yet. Of each kind, only the latest event is
+    /// kept until it's taken.
+    pub fn poll_event(&mut self) -> Option<Event> {
+        self.events.pop()
+    }
+
+    /// Fetches and executes a single instruction, returning the number of cycles it took. If an
+    /// interrupt is serviced instead, the cycles of the dispatch are returned.
+    fn step_cpu(&mut self) -> Result<u8, Error> {
+        match self.cpu.mode {
+            Mode::Running => {}
+            // A pending interrupt wakes the CPU regardless of IME, but is only serviced with IME.
+            Mode::Halted if self.mmu.pending_interrupts() != 0 => self.cpu.mode = Mode::Running,
+            Mode::Halted | Mode::Stopped => return Ok(1),
+        }
+        if let Some(cycles) = self.service_interrupt() {
+            return Ok(cycles);
+        }
+        let opcode = self.fetch();
+        if self.cpu.halt_bug {
+            self.cpu.halt_bug = false;
+        } else {
             self.advance_pc(1);
-            self.execute(opcode).unwrap_or_else(|e| panic!(e));
         }
+        let cycles = self.execute(opcode)?;
+        if self.cpu.ime_delay > 0 {
+            self.cpu.ime_delay -= 1;
+            if self.cpu.ime_delay == 0 {
+                self.cpu.ime = true;
+            }
+        }
+        Ok(cycles)
     }
 
-    fn fetch(&self) -> u8 {
-        self.mmu.read_u8(*self.cpu.register.pc)
+    /// Jumps to the vector of the highest priority interrupt that's both enabled and requested,
+    /// provided that IME is set.
+    fn service_interrupt(&mut self) -> Option<u8> {
+        if !self.cpu.ime {
+            return None;
+        }
+        let interrupt = Interrupt::highest_priority(self.mmu.pending_interrupts())?;
+        self.cpu.ime = false;
+        self.mmu.acknowledge_interrupt(interrupt);
+        let mut pc = *self.cpu.register.pc;
+        if self.cpu.halt_bug {
+            // The HALT that triggered the bug is returned to, so that it's executed again
+            // instead of the handler's first opcode being read twice.
+            self.cpu.halt_bug = false;
+            pc = pc.wrapping_sub(1);
+        }
+        self.push_u16(pc);
+        *self.cpu.register.pc = interrupt.vector();
+        Some(5)
+    }
+
+    fn fetch(&mut self) -> u8 {
+        self.read_cycle(*self.cpu.register.pc)
+    }
+
+    /// Reads from the bus at the end of an M-cycle, after the rest of the system has been ticked
+    /// for it, so that e.g. a timer read sees the value of the cycle that it's made on.
+    fn read_cycle(&mut self, addr: u16) -> u8 {
+        self.tick_cycle();
+        self.mmu.read_u8(addr)
+    }
+
+    /// Writes to the bus at the end of an M-cycle, like `read_cycle`.
+    fn write_cycle(&mut self, addr: u16, value: u8) {
+        self.tick_cycle();
+        self.mmu.write_u8(addr, value);
+    }
+
+    fn tick_cycle(&mut self) {
+        self.mmu.tick(1);
+        self.cycles_ticked += 1;
     }
 
     fn advance_pc(&mut self, steps: u8) {
         let pc: &mut u16 = &mut self.cpu.register.pc;
-        *pc += u16::from(steps);
+        *pc = pc.wrapping_add(u16::from(steps));
     }
 
-    fn execute(&mut self, opcode: u8) -> Result<(), failure::Error> {
-        let steps = instr::execute(opcode, self)?;
-        self.advance_pc(steps);
-        Ok(())
+    /// Pushes `value` onto the stack, which grows downwards. The high byte ends up on top, which
+    /// makes the value little-endian in memory.
+    fn push_u16(&mut self, value: u16) {
+        let sp = self.cpu.register.sp.wrapping_sub(1);
+        self.write_cycle(sp, (value >> 8) as u8);
+        let sp = sp.wrapping_sub(1);
+        self.write_cycle(sp, value as u8);
+        *self.cpu.register.sp = sp;
+    }
+
+    /// Pops a value pushed by `push_u16` off the stack.
+    fn pop_u16(&mut self) -> u16 {
+        let sp = *self.cpu.register.sp;
+        let lo = self.read_cycle(sp);
+        let hi = self.read_cycle(sp.wrapping_add(1));
+        *self.cpu.register.sp = sp.wrapping_add(2);
+        u16::from(hi) << 8 | u16::from(lo)
+    }
+
+    fn execute(&mut self, opcode: u8) -> Result<u8, Error> {
+        instr::execute(opcode, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::ppu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+    use crate::gameboy::rom::tests::rom;
+
+    #[test]
+    fn test_post_boot_state() {
+        let mut rom = rom(0x00, 0x00, 0x00);
+        rom[0x0100] = 0x42;
+        let gb = GameBoy::new(Cartridge::from_bytes(rom).unwrap()).unwrap();
+        let register = &gb.cpu.register;
+        assert_eq!(0x01B0, *register.af);
+        assert_eq!(0x0013, *register.bc);
+        assert_eq!(0x00D8, *register.de);
+        assert_eq!(0x014D, *register.hl);
+        assert_eq!(0xFFFE, *register.sp);
+        assert_eq!(0x0100, *register.pc);
+        assert_eq!(0x42, gb.mmu.read_u8(0x0100));
+    }
+
+    #[test]
+    fn test_rumble_event() {
+        let mut rom = rom(0x1C, 0x00, 0x00);
+        // LD A, 0x08; LD (0x4000), A
+        rom[0x0100..0x0105].copy_from_slice(&[0x3E, 0x08, 0xEA, 0x00, 0x40]);
+        let mut gb = GameBoy::new(Cartridge::from_bytes(rom).unwrap()).unwrap();
+        gb.step().unwrap();
+        assert_eq!(None, gb.poll_event());
+        gb.step().unwrap();
+        assert_eq!(Some(Event::Rumble(true)), gb.poll_event());
+        assert_eq!(None, gb.poll_event());
+    }
+
+    #[test]
+    fn test_events_coalesce() {
+        let mut rom = rom(0x1C, 0x00, 0x00);
+        // LD A, 0x08; LD (0x4000), A; XOR A; LD (0x4000), A
+        rom[0x0100..0x0109]
+            .copy_from_slice(&[0x3E, 0x08, 0xEA, 0x00, 0x40, 0xAF, 0xEA, 0x00, 0x40]);
+        let mut gb = GameBoy::new(Cartridge::from_bytes(rom).unwrap()).unwrap();
+        for _ in 0..4 {
+            gb.step().unwrap();
+        }
+        assert_eq!(Some(Event::Rumble(false)), gb.poll_event());
+        assert_eq!(None, gb.poll_event());
+    }
+
+    #[test]
+    fn test_rtc_double_speed() {
+        let mut rom = rom(0x0F, 0x00, 0x00);
+        // JR -2
+        rom[0x0100..0x0102].copy_from_slice(&[0x18, 0xFE]);
+        let cartridge = Cartridge::from_bytes(rom).unwrap();
+        let mut gb = GameBoy::with_model(cartridge, Model::Cgb, None).unwrap();
+        gb.set_rtc_clock(RtcClock::Emulated);
+        gb.mmu.switch_speed();
+        // Just over a second, even though the CPU got through two seconds' worth of cycles.
+        for _ in 0..60 {
+            gb.run_frame().unwrap();
+        }
+        gb.mmu.write_u8(0x0000, 0x0A);
+        gb.mmu.write_u8(0x4000, 0x08);
+        gb.mmu.write_u8(0x6000, 0x00);
+        gb.mmu.write_u8(0x6000, 0x01);
+        assert_eq!(1, gb.mmu.read_u8(0xA000));
+    }
+
+    #[test]
+    fn test_boot_rom() {
+        let mut rom = rom(0x00, 0x00, 0x00);
+        rom[0x0000] = 0x42;
+        // LD A, 0x01; LDH (0x50), A
+        let mut boot_rom = vec![0; 0x100];
+        boot_rom[0x00..0x04].copy_from_slice(&[0x3E, 0x01, 0xE0, 0x50]);
+        let cartridge = Cartridge::from_bytes(rom).unwrap();
+        let mut gb = GameBoy::with_model(cartridge, Model::Dmg, Some(boot_rom)).unwrap();
+        assert_eq!(0x0000, *gb.cpu.register.pc);
+        assert_eq!(0x3E, gb.mmu.read_u8(0x0000));
+        gb.step().unwrap();
+        gb.step().unwrap();
+        assert_eq!(0x42, gb.mmu.read_u8(0x0000));
+    }
+
+    #[test]
+    fn test_post_boot_models() {
+        let cartridge = Cartridge::from_bytes(rom(0x00, 0x00, 0x00)).unwrap();
+        let gb = GameBoy::with_model(cartridge, Model::Cgb, None).unwrap();
+        assert_eq!(Model::Cgb, gb.model());
+        assert_eq!(0x1180, *gb.cpu.register.af);
+        assert_eq!(0xFF56, *gb.cpu.register.de);
+        assert_eq!(0x0100, *gb.cpu.register.pc);
+    }
+
+    #[test]
+    fn test_run_frame() {
+        let mut rom = rom(0x00, 0x00, 0x00);
+        // JR -2
+        rom[0x0100..0x0102].copy_from_slice(&[0x18, 0xFE]);
+        let mut gb = GameBoy::new(Cartridge::from_bytes(rom).unwrap()).unwrap();
+        gb.run_frame().unwrap();
+        assert_eq!(144, gb.mmu.read_u8(0xFF44));
+        assert_eq!(0xE0 | Interrupt::VBlank.mask(), gb.mmu.read_u8(0xFF0F));
+        assert_eq!(SCREEN_WIDTH * SCREEN_HEIGHT, gb.frame().len());
+    }
+
+    #[test]
+    fn test_timer_read_timing() {
+        let mut rom = rom(0x00, 0x00, 0x00);
+        // XOR A; LDH (0x04), A; LD A, 0x05; LDH (0x07), A; LDH A, (0x05)
+        rom[0x0100..0x0109]
+            .copy_from_slice(&[0xAF, 0xE0, 0x04, 0x3E, 0x05, 0xE0, 0x07, 0xF0, 0x05]);
+        let mut gb = GameBoy::new(Cartridge::from_bytes(rom).unwrap()).unwrap();
+        for _ in 0..5 {
+            gb.step().unwrap();
+        }
+        // TIMA is read on the last cycle of LDH, 8 cycles after DIV was reset, by which time it
+        // has been incremented once.
+        assert_eq!(0x01, *gb.cpu.register.af >> 8);
+    }
+
+    #[test]
+    fn test_gbs_vblank() {
+        let gbs = GbsFile::from_bytes(gbs::tests::gbs(0, 0)).unwrap();
+        let mut gb = GameBoy::with_gbs(&gbs, 2).unwrap();
+        for _ in 0..10 {
+            gb.run_frame().unwrap();
+        }
+        // Init got the song counting from 0, and play has been called once a frame. The last
+        // frame's VBlank has only just been requested.
+        assert_eq!(0x01, gb.mmu.read_u8(0xC000));
+        assert_eq!(9, gb.mmu.read_u8(0xC001));
+        assert!(GameBoy::with_gbs(&gbs, 4).is_err());
+    }
+
+    #[test]
+    fn test_gbs_timer() {
+        // The timer overflows every 0x80 ticks at 4096 Hz, i.e. 32 times a second, which double
+        // speed doubles along with the rest of the CPU.
+        let gbs = GbsFile::from_bytes(gbs::tests::gbs(0x80, 0x84)).unwrap();
+        let mut gb = GameBoy::with_gbs(&gbs, 1).unwrap();
+        for _ in 0..60 {
+            gb.run_frame().unwrap();
+        }
+        assert_eq!(64, gb.mmu.read_u8(0xC001));
+    }
+
+    #[test]
+    fn test_gbs_interrupt_pending_at_halt() {
+        // The timer overflows every 16 cycles, so an interrupt is already pending by the time
+        // init returns to the driver's EI; HALT.
+        let gbs = GbsFile::from_bytes(gbs::tests::gbs(0xFF, 0x05)).unwrap();
+        let mut gb = GameBoy::with_gbs(&gbs, 1).unwrap();
+        let mut serviced = false;
+        let mut returned = None;
+        for _ in 0..100 {
+            gb.step().unwrap();
+            let pc = *gb.cpu.register.pc;
+            if pc == Interrupt::Timer.vector() {
+                serviced = true;
+            } else if serviced && (gbs::DRIVER..0x0400).contains(&pc) {
+                returned = Some(pc);
+                break;
+            }
+        }
+        // After play, the driver is back at its HALT, rather than just past it.
+        assert_eq!(Some(gbs::DRIVER + 4), returned);
+        assert_eq!(1, gb.mmu.read_u8(0xC001));
     }
 }
@@ -0,0 +1,326 @@
+use std::collections::VecDeque;
+
+use super::*;
+
+/// How many dots the fetcher spends on the first tile of a line, which it fetches twice.
+const STARTUP_DOTS: u8 = 6;
+/// How many dots fetching a sprite's tile takes, once the background fetcher has made way.
+const SPRITE_FETCH_DOTS: u8 = 6;
+
+/// The steps of the background fetcher, which take two dots each. Pushing is retried every dot
+/// until the background FIFO is empty.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Step {
+    Tile,
+    DataLow,
+    DataHigh,
+    Push,
+}
+
+/// The state of the pixel FIFO renderer for the current line.
+#[derive(Debug)]
+pub(super) struct PixelFifo {
+    /// The colors of the background or window pixels waiting to be pushed to the LCD.
+    background: VecDeque<u8>,
+    /// The sprite pixels to be mixed with the background pixels, where color 0 is transparent.
+    sprites: VecDeque<(u8, Sprite)>,
+    step: Step,
+    /// The dots spent on the current step.
+    step_dots: u8,
+    /// The column of the tile to fetch next, counted in tiles from the left of the screen or the
+    /// window.
+    fetcher_x: u8,
+    tile: u8,
+    low: u8,
+    high: u8,
+    /// Dots left before the fetcher starts.
+    startup: u8,
+    /// Pixels left to throw away, to scroll the background by less than a tile.
+    discard: u8,
+    /// The number of pixels pushed to the LCD on this line.
+    x: u8,
+    /// Whether the fetcher has switched over to the window on this line.
+    window: bool,
+    /// The index of the next sprite to fetch in the line's sprites.
+    next_sprite: usize,
+    /// Dots left of the sprite fetch in progress, if any.
+    sprite_fetch: u8,
+}
+
+impl PixelFifo {
+    pub(super) fn new() -> PixelFifo {
+        PixelFifo {
+            background: VecDeque::with_capacity(16),
+            sprites: VecDeque::with_capacity(8),
+            step: Step::Tile,
+            step_dots: 0,
+            fetcher_x: 0,
+            tile: 0,
+            low: 0,
+            high: 0,
+            startup: 0,
+            discard: 0,
+            x: 0,
+            window: false,
+            next_sprite: 0,
+            sprite_fetch: 0,
+        }
+    }
+
+    /// Points the fetcher at the first tile of the background or the window.
+    fn restart_fetcher(&mut self) {
+        self.background.clear();
+        self.step = Step::Tile;
+        self.step_dots = 0;
+        self.fetcher_x = 0;
+    }
+}
+
+impl Ppu {
+    pub(super) fn start_fifo(&mut self) {
+        let fifo = &mut self.fifo;
+        fifo.restart_fetcher();
+        fifo.sprites.clear();
+        fifo.startup = STARTUP_DOTS;
+        fifo.discard = self.scx % 8;
+        fifo.x = 0;
+        fifo.window = false;
+        fifo.next_sprite = 0;
+        fifo.sprite_fetch = 0;
+    }
+
+    /// Advances the fetchers and pushes a pixel to the LCD if there's one, returning whether the
+    /// line is done.
+    pub(super) fn step_fifo(&mut self) -> bool {
+        if self.fifo.startup > 0 {
+            self.fifo.startup -= 1;
+            return false;
+        }
+
+        // A sprite stops the pixels until it has been fetched, which first waits for the
+        // background fetcher to finish the tile it's on.
+        if self.sprite_due() {
+            if self.fifo.sprite_fetch == 0 {
+                let fetching = self.fifo.step != Step::Push
+                    && (self.fifo.step != Step::Tile || self.fifo.step_dots != 0);
+                if fetching || self.fifo.background.is_empty() {
+                    self.step_fetcher();
+                    return false;
+                }
+                self.fifo.sprite_fetch = SPRITE_FETCH_DOTS;
+            }
+            self.fifo.sprite_fetch -= 1;
+            if self.fifo.sprite_fetch == 0 {
+                self.fetch_sprite();
+            }
+            return false;
+        }
+
+        if !self.fifo.window
+            && self.window_visible()
+            && u16::from(self.fifo.x) + 7 >= u16::from(self.wx)
+        {
+            self.fifo.window = true;
+            self.fifo.restart_fetcher();
+            // The window is shifted out of the screen to the left with WX below 7.
+            self.fifo.discard = 7u8.saturating_sub(self.wx);
+            return false;
+        }
+
+        self.step_fetcher();
+        let color = match self.fifo.background.pop_front() {
+            Some(color) => color,
+            None => return false,
+        };
+        if self.fifo.discard > 0 {
+            self.fifo.discard -= 1;
+            return false;
+        }
+        let sprite = self
+            .fifo
+            .sprites
+            .pop_front()
+            .filter(|&(color, _)| color != 0 && self.lcdc & SPRITE_ENABLE != 0);
+        // On the DMG, disabling the background disables the window as well.
+        let background = if self.lcdc & BG_ENABLE != 0 { color } else { 0 };
+        let offset = usize::from(self.ly) * SCREEN_WIDTH + usize::from(self.fifo.x);
+        self.frame[offset] = self.mix(background, sprite);
+
+        self.fifo.x += 1;
+        if usize::from(self.fifo.x) < SCREEN_WIDTH {
+            return false;
+        }
+        if self.fifo.window {
+            self.window_line += 1;
+        }
+        true
+    }
+
+    /// Whether the next sprite starts at the pixel about to be pushed, or further to the left.
+    fn sprite_due(&self) -> bool {
+        self.lcdc & SPRITE_ENABLE != 0
+            && self
+                .sprites
+                .get(self.fifo.next_sprite)
+                .is_some_and(|sprite| sprite.x <= self.fifo.x + 8)
+    }
+
+    fn step_fetcher(&mut self) {
+        if self.fifo.step == Step::Push {
+            if self.fifo.background.is_empty() {
+                let row = (self.fifo.low, self.fifo.high);
+                self.fifo.background.extend((0..8).map(|x| color(row, x)));
+                self.fifo.fetcher_x = self.fifo.fetcher_x.wrapping_add(1);
+                self.fifo.step = Step::Tile;
+            }
+            return;
+        }
+        self.fifo.step_dots += 1;
+        if self.fifo.step_dots < 2 {
+            return;
+        }
+        self.fifo.step_dots = 0;
+        // The registers are read as the fetcher gets to them, so that changes in the middle of a
+        // line show from the next tile on.
+        let (map, column, y) = if self.fifo.window {
+            (
+                self.tile_map(WINDOW_MAP),
+                self.fifo.fetcher_x,
+                self.window_line,
+            )
+        } else {
+            let column = self.scx / 8 + self.fifo.fetcher_x;
+            (
+                self.tile_map(BG_MAP),
+                column,
+                self.scy.wrapping_add(self.ly),
+            )
+        };
+        match self.fifo.step {
+            Step::Tile => {
+                let index = map + usize::from(y / 8) * 32 + usize::from(column % 32);
+                self.fifo.tile = self.video_ram[index];
+                self.fifo.step = Step::DataLow;
+            }
+            Step::DataLow => {
+                self.fifo.low = self.tile_row(self.background_tile(self.fifo.tile), y % 8).0;
+                self.fifo.step = Step::DataHigh;
+            }
+            Step::DataHigh => {
+                self.fifo.high = self.tile_row(self.background_tile(self.fifo.tile), y % 8).1;
+                self.fifo.step = Step::Push;
+            }
+            Step::Push => unreachable!(),
+        }
+    }
+
+    /// Mixes the next sprite into the sprite FIFO, where it only shows through the transparent
+    /// pixels of the sprites fetched before it.
+    fn fetch_sprite(&mut self) {
+        let sprite = self.sprites[self.fifo.next_sprite];
+        self.fifo.next_sprite += 1;
+        let row = self.sprite_row(sprite);
+        // Pixels left of the one about to be pushed have been missed, which only happens to
+        // sprites that are partially off the screen to the left.
+        let skip = self.fifo.x + 8 - sprite.x;
+        while self.fifo.sprites.len() < 8 {
+            self.fifo.sprites.push_back((0, sprite));
+        }
+        for x in skip..8 {
+            let column = if sprite.x_flip() { 7 - x } else { x };
+            let color = color(row, column);
+            let pixel = &mut self.fifo.sprites[usize::from(x - skip)];
+            if pixel.0 == 0 {
+                *pixel = (color, sprite);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Enables the LCD with the given LCDC bits, and returns how long mode 3 takes on line 0.
+    fn drawing_dots(ppu: &mut Ppu, lcdc: u8) -> u16 {
+        ppu.set_renderer(Renderer::PixelFifo);
+        ppu.write_u8(LCDC, LCD_ENABLE | lcdc);
+        ppu.tick(OAM_SCAN_DOTS);
+        let mut dots = 0;
+        while ppu.mode == Mode::Drawing {
+            ppu.tick(1);
+            dots += 1;
+        }
+        dots
+    }
+
+    #[test]
+    fn test_drawing_dots() {
+        let mut ppu = Ppu::new();
+        assert_eq!(DRAWING_DOTS, drawing_dots(&mut ppu, BG_ENABLE));
+
+        let mut ppu = Ppu::new();
+        ppu.write_u8(SCX, 3);
+        assert_eq!(DRAWING_DOTS + 3, drawing_dots(&mut ppu, BG_ENABLE));
+
+        let mut ppu = Ppu::new();
+        ppu.write_u8(0xFE00, 16);
+        ppu.write_u8(0xFE01, 8);
+        let dots = drawing_dots(&mut ppu, BG_ENABLE | SPRITE_ENABLE);
+        assert!((DRAWING_DOTS + 6..=DRAWING_DOTS + 11).contains(&dots));
+    }
+
+    /// Draws a frame with both renderers, which should agree as long as nothing changes in the
+    /// middle of a line.
+    #[test]
+    fn test_same_as_scanline() {
+        let mut ppus = [Ppu::new(), Ppu::new()];
+        ppus[1].set_renderer(Renderer::PixelFifo);
+        for ppu in &mut ppus {
+            for i in 0..0x1800 {
+                ppu.write_u8(0x8000 + i, (i * 7 + i / 13) as u8);
+            }
+            for i in 0..0x800 {
+                ppu.write_u8(0x9800 + i, (i * 5) as u8);
+            }
+            for i in 0..40 {
+                let sprite = [16 + 4 * i as u8, (8 * i) as u8, i as u8, (i as u8) << 4];
+                for (j, &byte) in sprite.iter().enumerate() {
+                    ppu.write_u8(0xFE00 + 4 * i + j as u16, byte);
+                }
+            }
+            ppu.write_u8(SCX, 13);
+            ppu.write_u8(SCY, 200);
+            ppu.write_u8(WX, 90);
+            ppu.write_u8(WY, 60);
+            ppu.write_u8(BGP, 0xE4);
+            ppu.write_u8(OBP0, 0xD2);
+            ppu.write_u8(OBP1, 0x1B);
+            ppu.write_u8(LCDC, 0xF7);
+            while !ppu.take_frame_completed() {
+                ppu.tick(1);
+            }
+        }
+        assert!(ppus[0].frame() == ppus[1].frame());
+    }
+
+    #[test]
+    fn test_palette_mid_line() {
+        let mut ppu = Ppu::new();
+        ppu.set_renderer(Renderer::PixelFifo);
+        for i in 0..16 {
+            ppu.write_u8(0x8000 + i, 0xFF);
+        }
+        ppu.write_u8(BGP, 0x00);
+        ppu.write_u8(LCDC, LCD_ENABLE | UNSIGNED_TILE_DATA | BG_ENABLE);
+        // Halfway through drawing the line, the pixels so far have the old palette.
+        ppu.tick(OAM_SCAN_DOTS + u16::from(STARTUP_DOTS) + 6 + 80);
+        ppu.write_u8(BGP, 0x40);
+        while !ppu.take_frame_completed() {
+            ppu.tick(1);
+        }
+        assert_eq!(0, ppu.frame()[79]);
+        assert_eq!(1, ppu.frame()[80]);
+        assert_eq!(1, ppu.frame()[SCREEN_WIDTH]);
+    }
+}
@@ -0,0 +1,584 @@
+use std::mem;
+
+use super::interrupt::Interrupt;
+use super::mem::Bus;
+
+use self::fifo::PixelFifo;
+
+mod fifo;
+mod scanline;
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+
+const DOTS_PER_LINE: u16 = 456;
+const LINES_PER_FRAME: u8 = 154;
+const OAM_SCAN_DOTS: u16 = 80;
+/// How long drawing takes without any of the penalties for fine scrolling, window and sprites.
+const DRAWING_DOTS: u16 = 172;
+const MAX_SPRITES_PER_LINE: usize = 10;
+
+const LCDC: u16 = 0xFF40;
+const STAT: u16 = 0xFF41;
+const SCY: u16 = 0xFF42;
+const SCX: u16 = 0xFF43;
+const LY: u16 = 0xFF44;
+const LYC: u16 = 0xFF45;
+const BGP: u16 = 0xFF47;
+const OBP0: u16 = 0xFF48;
+const OBP1: u16 = 0xFF49;
+const WY: u16 = 0xFF4A;
+const WX: u16 = 0xFF4B;
+
+// The bits of LCDC.
+const BG_ENABLE: u8 = 0x01;
+const SPRITE_ENABLE: u8 = 0x02;
+const TALL_SPRITES: u8 = 0x04;
+const BG_MAP: u8 = 0x08;
+const UNSIGNED_TILE_DATA: u8 = 0x10;
+const WINDOW_ENABLE: u8 = 0x20;
+const WINDOW_MAP: u8 = 0x40;
+const LCD_ENABLE: u8 = 0x80;
+
+// The interrupt source bits of STAT.
+const HBLANK_INTERRUPT: u8 = 0x08;
+const VBLANK_INTERRUPT: u8 = 0x10;
+const OAM_SCAN_INTERRUPT: u8 = 0x20;
+const LYC_INTERRUPT: u8 = 0x40;
+
+/// Keeps the PPU's time in place of the PPU, for running without a screen. It requests VBlank
+/// once a frame, as the PPU does with the LCD on, but draws nothing.
+#[derive(Debug, Default)]
+pub(crate) struct FrameClock {
+    /// The dot within the current frame.
+    dot: u32,
+    frame_completed: bool,
+}
+
+impl FrameClock {
+    /// Whether a frame has been completed since the last call.
+    pub(crate) fn take_frame_completed(&mut self) -> bool {
+        mem::replace(&mut self.frame_completed, false)
+    }
+
+    /// Advances by the given number of dots, returning the interrupts requested like `Ppu::tick`.
+    pub(crate) fn tick(&mut self, dots: u16) -> u8 {
+        let dots_per_frame = u32::from(DOTS_PER_LINE) * u32::from(LINES_PER_FRAME);
+        self.dot += u32::from(dots);
+        if self.dot < dots_per_frame {
+            return 0;
+        }
+        self.dot -= dots_per_frame;
+        self.frame_completed = true;
+        Interrupt::VBlank.mask()
+    }
+}
+
+/// How the PPU draws the picture.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Renderer {
+    /// Draws each line in one go at the end of mode 3. It's fast, but changes to the registers in
+    /// the middle of a line only show from the next line on.
+    #[default]
+    Scanline,
+    /// Models the fetchers and pixel FIFOs of the hardware dot by dot, so that changes in the
+    /// middle of a line show where they would on the hardware.
+    PixelFifo,
+}
+
+/// What the PPU is doing, as reported in the lower two bits of STAT.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Mode {
+    HBlank = 0,
+    VBlank = 1,
+    /// Looking for the sprites on the line, during which the CPU can't access OAM.
+    OamScan = 2,
+    /// Sending pixels to the LCD, during which the CPU can access neither VRAM nor OAM.
+    Drawing = 3,
+}
+
+/// An entry of the sprite attribute table in OAM.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Sprite {
+    /// The vertical position plus 16, so that sprites can be partially hidden above the screen.
+    y: u8,
+    /// The horizontal position plus 8.
+    x: u8,
+    tile: u8,
+    attributes: u8,
+}
+
+impl Sprite {
+    fn behind_background(self) -> bool {
+        self.attributes & 0x80 != 0
+    }
+
+    fn y_flip(self) -> bool {
+        self.attributes & 0x40 != 0
+    }
+
+    fn x_flip(self) -> bool {
+        self.attributes & 0x20 != 0
+    }
+
+    /// Whether the sprite uses OBP1 rather than OBP0.
+    fn second_palette(self) -> bool {
+        self.attributes & 0x10 != 0
+    }
+}
+
+/// The picture processing unit, which owns VRAM, OAM and the LCD registers at 0xFF40-0xFF4B.
+///
+/// It walks through the modes of each line dot by dot, while one of the renderers draws the
+/// lines into the frame during mode 3.
+#[derive(Debug)]
+pub(crate) struct Ppu {
+    video_ram: Box<[u8]>,
+    oam: Box<[u8]>,
+    lcdc: u8,
+    /// The interrupt sources of STAT, the rest of it is derived from the PPU's state.
+    stat: u8,
+    scy: u8,
+    scx: u8,
+    ly: u8,
+    lyc: u8,
+    bgp: u8,
+    obp0: u8,
+    obp1: u8,
+    wy: u8,
+    wx: u8,
+    mode: Mode,
+    renderer: Renderer,
+    /// The renderer drawing the current line, so that switching takes effect from the next one.
+    line_renderer: Renderer,
+    /// The dot within the current line.
+    dot: u16,
+    /// The dot at which mode 3 ends on the current line, for the scanline renderer.
+    drawing_end: u16,
+    fifo: PixelFifo,
+    /// The sprites found on the current line, ordered by priority.
+    sprites: Vec<Sprite>,
+    /// Whether LY has matched WY during this frame, which is when the window starts to show.
+    window_triggered: bool,
+    /// The line of the window to draw next, which only advances on lines that show the window.
+    window_line: u8,
+    /// The OR of the enabled STAT interrupt sources, which requests the interrupt when it rises.
+    stat_line: bool,
+    interrupts: u8,
+    frame: Box<[u8]>,
+    frame_completed: bool,
+}
+
+impl Ppu {
+    pub(crate) fn new() -> Ppu {
+        Ppu {
+            video_ram: vec![0u8; 0x2000].into_boxed_slice(),
+            oam: vec![0u8; 0xA0].into_boxed_slice(),
+            lcdc: 0,
+            stat: 0,
+            scy: 0,
+            scx: 0,
+            ly: 0,
+            lyc: 0,
+            bgp: 0,
+            obp0: 0,
+            obp1: 0,
+            wy: 0,
+            wx: 0,
+            mode: Mode::HBlank,
+            renderer: Renderer::Scanline,
+            line_renderer: Renderer::Scanline,
+            dot: 0,
+            drawing_end: 0,
+            fifo: PixelFifo::new(),
+            sprites: Vec::with_capacity(MAX_SPRITES_PER_LINE),
+            window_triggered: false,
+            window_line: 0,
+            stat_line: false,
+            interrupts: 0,
+            frame: vec![0u8; SCREEN_WIDTH * SCREEN_HEIGHT].into_boxed_slice(),
+            frame_completed: false,
+        }
+    }
+
+    /// The most recently drawn frame, as shades from 0 (lightest) to 3 (darkest), row by row.
+    pub(crate) fn frame(&self) -> &[u8] {
+        &self.frame
+    }
+
+    /// Writes OAM for OAM DMA, which isn't held up by the PPU using it.
+    pub(crate) fn write_oam(&mut self, index: u8, value: u8) {
+        self.oam[usize::from(index)] = value;
+    }
+
+    pub(crate) fn set_renderer(&mut self, renderer: Renderer) {
+        self.renderer = renderer;
+    }
+
+    /// Whether a frame has been completed since the last call.
+    pub(crate) fn take_frame_completed(&mut self) -> bool {
+        mem::replace(&mut self.frame_completed, false)
+    }
+
+    /// Advances the PPU by the given number of dots, returning the interrupts it requested as
+    /// their bits in IF.
+    pub(crate) fn tick(&mut self, dots: u16) -> u8 {
+        if self.lcd_enabled() {
+            for _ in 0..dots {
+                self.tick_dot();
+            }
+        }
+        mem::replace(&mut self.interrupts, 0)
+    }
+
+    fn tick_dot(&mut self) {
+        self.dot += 1;
+        match self.mode {
+            Mode::OamScan if self.dot == OAM_SCAN_DOTS => self.start_drawing(),
+            Mode::Drawing => self.step_drawing(),
+            _ if self.dot == DOTS_PER_LINE => self.next_line(),
+            _ => {}
+        }
+    }
+
+    fn next_line(&mut self) {
+        self.dot = 0;
+        self.ly = (self.ly + 1) % LINES_PER_FRAME;
+        match self.ly {
+            0 => {
+                self.window_triggered = false;
+                self.window_line = 0;
+                self.start_line();
+            }
+            1..=143 => self.start_line(),
+            144 => {
+                self.mode = Mode::VBlank;
+                self.interrupts |= Interrupt::VBlank.mask();
+                self.frame_completed = true;
+            }
+            _ => {}
+        }
+        self.update_stat_line();
+    }
+
+    /// Starts a visible line with the OAM scan.
+    fn start_line(&mut self) {
+        if self.ly == self.wy {
+            self.window_triggered = true;
+        }
+        self.mode = Mode::OamScan;
+    }
+
+    /// Picks the sprites on the line, which determines how long drawing takes.
+    fn start_drawing(&mut self) {
+        let height = self.sprite_height();
+        let line = self.ly + 16;
+        self.sprites.clear();
+        self.sprites.extend(
+            self.oam
+                .chunks(4)
+                .map(|entry| Sprite {
+                    y: entry[0],
+                    x: entry[1],
+                    tile: entry[2],
+                    attributes: entry[3],
+                })
+                .filter(|sprite| line >= sprite.y && line < sprite.y.saturating_add(height))
+                .take(MAX_SPRITES_PER_LINE),
+        );
+        // On the DMG, the sprite furthest to the left wins, and after that the first in OAM.
+        self.sprites.sort_by_key(|sprite| sprite.x);
+
+        self.line_renderer = self.renderer;
+        match self.line_renderer {
+            Renderer::Scanline => {
+                // An approximation of the time the pixel FIFO takes.
+                let mut penalty = u16::from(self.scx % 8) + 6 * self.sprites.len() as u16;
+                if self.window_visible() {
+                    penalty += 6;
+                }
+                self.drawing_end = OAM_SCAN_DOTS + DRAWING_DOTS + penalty;
+            }
+            Renderer::PixelFifo => self.start_fifo(),
+        }
+        self.mode = Mode::Drawing;
+        self.update_stat_line();
+    }
+
+    /// Advances drawing by a dot, and moves on to HBlank once the line is done.
+    fn step_drawing(&mut self) {
+        let done = match self.line_renderer {
+            Renderer::Scanline if self.dot == self.drawing_end => {
+                self.render_scanline();
+                true
+            }
+            Renderer::Scanline => false,
+            Renderer::PixelFifo => self.step_fifo(),
+        };
+        if done {
+            self.mode = Mode::HBlank;
+            self.update_stat_line();
+        }
+    }
+
+    fn lcd_enabled(&self) -> bool {
+        self.lcdc & LCD_ENABLE != 0
+    }
+
+    fn sprite_height(&self) -> u8 {
+        if self.lcdc & TALL_SPRITES != 0 {
+            16
+        } else {
+            8
+        }
+    }
+
+    /// The tile map selected by one of the LCDC bits, as an offset into VRAM.
+    fn tile_map(&self, bit: u8) -> usize {
+        if self.lcdc & bit != 0 {
+            0x1C00
+        } else {
+            0x1800
+        }
+    }
+
+    /// The index from 0x8000 of a background or window tile. It's either one of tiles 0-255 from
+    /// 0x8000, or one of tiles -128-127 from 0x9000.
+    fn background_tile(&self, index: u8) -> usize {
+        if self.lcdc & UNSIGNED_TILE_DATA != 0 {
+            usize::from(index)
+        } else {
+            (0x100 + i16::from(index as i8)) as usize
+        }
+    }
+
+    /// The two bytes making up a row of a tile, by its index from 0x8000. Rows past the 8th
+    /// continue into the following tile.
+    fn tile_row(&self, tile: usize, row: u8) -> (u8, u8) {
+        let addr = tile * 16 + usize::from(row) * 2;
+        (self.video_ram[addr], self.video_ram[addr + 1])
+    }
+
+    /// The row of a sprite's tile which is drawn on the current line, taking flips and tall
+    /// sprites into account.
+    fn sprite_row(&self, sprite: Sprite) -> (u8, u8) {
+        let height = self.sprite_height();
+        let mut row = self.ly + 16 - sprite.y;
+        if sprite.y_flip() {
+            row = height - 1 - row;
+        }
+        // Tall sprites are made up of an even tile and the odd one after it.
+        let tile = if height == 16 {
+            sprite.tile & 0xFE
+        } else {
+            sprite.tile
+        };
+        self.tile_row(usize::from(tile), row)
+    }
+
+    /// Combines a background and a sprite pixel into the shade that ends up on the LCD.
+    fn mix(&self, background: u8, sprite: Option<(u8, Sprite)>) -> u8 {
+        match sprite {
+            Some((color, sprite)) if !sprite.behind_background() || background == 0 => {
+                let palette = if sprite.second_palette() {
+                    self.obp1
+                } else {
+                    self.obp0
+                };
+                shade(palette, color)
+            }
+            _ => shade(self.bgp, background),
+        }
+    }
+
+    /// Whether the window shows on the current line.
+    fn window_visible(&self) -> bool {
+        self.lcdc & WINDOW_ENABLE != 0 && self.window_triggered && self.wx <= 166
+    }
+
+    /// Requests the STAT interrupt if any of its enabled sources has just become active. As the
+    /// sources share a line, one becoming active while another already is doesn't request it.
+    fn update_stat_line(&mut self) {
+        let source = match self.mode {
+            Mode::HBlank => HBLANK_INTERRUPT,
+            Mode::VBlank => VBLANK_INTERRUPT,
+            Mode::OamScan => OAM_SCAN_INTERRUPT,
+            Mode::Drawing => 0,
+        };
+        let coincidence = if self.ly == self.lyc {
+            LYC_INTERRUPT
+        } else {
+            0
+        };
+        let line = self.lcd_enabled() && self.stat & (source | coincidence) != 0;
+        if line && !self.stat_line {
+            self.interrupts |= Interrupt::LcdStat.mask();
+        }
+        self.stat_line = line;
+    }
+
+    fn write_lcdc(&mut self, value: u8) {
+        let was_enabled = self.lcd_enabled();
+        self.lcdc = value;
+        match (was_enabled, self.lcd_enabled()) {
+            // The LCD goes blank, and the PPU starts over from the first line once enabled again.
+            (true, false) => {
+                self.ly = 0;
+                self.dot = 0;
+                self.mode = Mode::HBlank;
+                self.stat_line = false;
+                self.frame.iter_mut().for_each(|shade| *shade = 0);
+            }
+            (false, true) => {
+                self.window_triggered = false;
+                self.window_line = 0;
+                self.start_line();
+                self.update_stat_line();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The color of a pixel in a row of a tile, where the pixel at 0 is the leftmost.
+fn color(row: (u8, u8), x: u8) -> u8 {
+    let (low, high) = row;
+    let bit = 7 - x;
+    (high >> bit & 1) << 1 | low >> bit & 1
+}
+
+/// Maps a color to its shade through one of the palette registers.
+fn shade(palette: u8, color: u8) -> u8 {
+    palette >> (2 * color) & 0x03
+}
+
+impl Bus for Ppu {
+    fn read_u8(&self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0x9FFF if self.mode == Mode::Drawing => 0xFF,
+            0x8000..=0x9FFF => self.video_ram[usize::from(addr - 0x8000)],
+            0xFE00..=0xFE9F if self.mode == Mode::OamScan || self.mode == Mode::Drawing => 0xFF,
+            0xFE00..=0xFE9F => self.oam[usize::from(addr - 0xFE00)],
+            LCDC => self.lcdc,
+            STAT => {
+                let coincidence = if self.ly == self.lyc { 0x04 } else { 0 };
+                0x80 | self.stat | coincidence | self.mode as u8
+            }
+            SCY => self.scy,
+            SCX => self.scx,
+            LY => self.ly,
+            LYC => self.lyc,
+            BGP => self.bgp,
+            OBP0 => self.obp0,
+            OBP1 => self.obp1,
+            WY => self.wy,
+            WX => self.wx,
+            _ => 0xFF,
+        }
+    }
+
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x8000..=0x9FFF if self.mode == Mode::Drawing => {}
+            0x8000..=0x9FFF => self.video_ram[usize::from(addr - 0x8000)] = value,
+            0xFE00..=0xFE9F if self.mode == Mode::OamScan || self.mode == Mode::Drawing => {}
+            0xFE00..=0xFE9F => self.oam[usize::from(addr - 0xFE00)] = value,
+            LCDC => self.write_lcdc(value),
+            STAT => {
+                self.stat = value & 0x78;
+                self.update_stat_line();
+            }
+            SCY => self.scy = value,
+            SCX => self.scx = value,
+            // LY is read only.
+            LY => {}
+            LYC => {
+                self.lyc = value;
+                self.update_stat_line();
+            }
+            BGP => self.bgp = value,
+            OBP0 => self.obp0 = value,
+            OBP1 => self.obp1 = value,
+            WY => self.wy = value,
+            WX => self.wx = value,
+            _ => {}
+        }
+    }
+}
+
+impl Default for Ppu {
+    fn default() -> Ppu {
+        Ppu::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs the PPU until LY reaches `ly` at the start of a line, collecting the interrupts.
+    fn run_to_line(ppu: &mut Ppu, ly: u8) -> u8 {
+        let mut interrupts = 0;
+        while ppu.ly != ly || ppu.dot != 0 {
+            interrupts |= ppu.tick(1);
+        }
+        interrupts
+    }
+
+    #[test]
+    fn test_modes() {
+        let mut ppu = Ppu::new();
+        ppu.write_u8(LCDC, LCD_ENABLE);
+        assert_eq!(0x82, ppu.read_u8(STAT) & !0x04);
+        ppu.tick(OAM_SCAN_DOTS);
+        assert_eq!(0x83, ppu.read_u8(STAT) & !0x04);
+        ppu.tick(DRAWING_DOTS);
+        assert_eq!(0x80, ppu.read_u8(STAT) & !0x04);
+        ppu.tick(DOTS_PER_LINE - OAM_SCAN_DOTS - DRAWING_DOTS);
+        assert_eq!(1, ppu.read_u8(LY));
+
+        assert_eq!(Interrupt::VBlank.mask(), run_to_line(&mut ppu, 144));
+        assert_eq!(0x81, ppu.read_u8(STAT) & !0x04);
+        assert!(ppu.take_frame_completed());
+        assert!(!ppu.take_frame_completed());
+        run_to_line(&mut ppu, 0);
+        assert_eq!(0x82, ppu.read_u8(STAT) & !0x04);
+
+        ppu.write_u8(LCDC, 0);
+        assert_eq!(0x84, ppu.read_u8(STAT));
+        assert_eq!(0, ppu.tick(DOTS_PER_LINE));
+        assert_eq!(0, ppu.read_u8(LY));
+    }
+
+    #[test]
+    fn test_access_blocked() {
+        let mut ppu = Ppu::new();
+        ppu.write_u8(0x8000, 0x42);
+        ppu.write_u8(0xFE00, 0x24);
+        ppu.write_u8(LCDC, LCD_ENABLE);
+        assert_eq!(0x42, ppu.read_u8(0x8000));
+        assert_eq!(0xFF, ppu.read_u8(0xFE00));
+        ppu.tick(OAM_SCAN_DOTS);
+        assert_eq!(0xFF, ppu.read_u8(0x8000));
+        ppu.write_u8(0x8000, 0x00);
+        ppu.tick(DRAWING_DOTS);
+        assert_eq!(0x42, ppu.read_u8(0x8000));
+        assert_eq!(0x24, ppu.read_u8(0xFE00));
+    }
+
+    #[test]
+    fn test_stat_interrupt() {
+        let mut ppu = Ppu::new();
+        ppu.write_u8(LYC, 2);
+        ppu.write_u8(STAT, LYC_INTERRUPT | HBLANK_INTERRUPT);
+        ppu.write_u8(LCDC, LCD_ENABLE);
+        // HBlank requests it on every line, except on line 2 where the coincidence already keeps
+        // the line high.
+        let stat = Interrupt::LcdStat.mask();
+        assert_eq!(stat, run_to_line(&mut ppu, 1));
+        assert_eq!(stat, run_to_line(&mut ppu, 2));
+        assert_eq!(0x04, ppu.read_u8(STAT) & 0x04);
+        assert_eq!(0, run_to_line(&mut ppu, 3));
+        assert_eq!(stat, run_to_line(&mut ppu, 4));
+    }
+}
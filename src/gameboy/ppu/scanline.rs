@@ -0,0 +1,184 @@
+use super::*;
+
+impl Ppu {
+    /// Draws the current line into the frame in one go, with the registers as they are at the end
+    /// of mode 3.
+    pub(super) fn render_scanline(&mut self) {
+        let window = self.window_visible();
+        let offset = usize::from(self.ly) * SCREEN_WIDTH;
+        for x in 0..SCREEN_WIDTH as u8 {
+            // On the DMG, disabling the background disables the window as well.
+            let background = if self.lcdc & BG_ENABLE == 0 {
+                0
+            } else if window && x + 7 >= self.wx {
+                self.window_pixel(x + 7 - self.wx)
+            } else {
+                self.background_pixel(x)
+            };
+            self.frame[offset + usize::from(x)] = self.mix(background, self.sprite_pixel(x));
+        }
+        if window {
+            self.window_line += 1;
+        }
+    }
+
+    fn background_pixel(&self, x: u8) -> u8 {
+        let map = self.tile_map(BG_MAP);
+        self.map_pixel(
+            map,
+            self.scx.wrapping_add(x),
+            self.scy.wrapping_add(self.ly),
+        )
+    }
+
+    fn window_pixel(&self, x: u8) -> u8 {
+        self.map_pixel(self.tile_map(WINDOW_MAP), x, self.window_line)
+    }
+
+    /// The color of a pixel of the 256x256 picture made up by the tile map at `map` in VRAM.
+    fn map_pixel(&self, map: usize, x: u8, y: u8) -> u8 {
+        let index = self.video_ram[map + usize::from(y / 8) * 32 + usize::from(x / 8)];
+        color(self.tile_row(self.background_tile(index), y % 8), x % 8)
+    }
+
+    /// The color of the highest priority sprite pixel that isn't transparent, if any.
+    fn sprite_pixel(&self, x: u8) -> Option<(u8, Sprite)> {
+        if self.lcdc & SPRITE_ENABLE == 0 {
+            return None;
+        }
+        self.sprites
+            .iter()
+            .filter(|sprite| x + 8 >= sprite.x && x < sprite.x)
+            .map(|&sprite| {
+                let mut column = x + 8 - sprite.x;
+                if sprite.x_flip() {
+                    column = 7 - column;
+                }
+                (color(self.sprite_row(sprite), column), sprite)
+            })
+            .find(|&(color, _)| color != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fills a tile with a single color.
+    fn fill_tile(ppu: &mut Ppu, addr: u16, color: u8) {
+        for row in 0..8 {
+            ppu.write_u8(addr + 2 * row, if color & 1 != 0 { 0xFF } else { 0x00 });
+            ppu.write_u8(addr + 2 * row + 1, if color & 2 != 0 { 0xFF } else { 0x00 });
+        }
+    }
+
+    /// Enables the LCD with the given LCDC bits, and runs it to VBlank.
+    fn render(ppu: &mut Ppu, lcdc: u8) {
+        ppu.write_u8(BGP, 0xE4);
+        ppu.write_u8(OBP0, 0xE4);
+        ppu.write_u8(OBP1, 0x1B);
+        ppu.write_u8(LCDC, LCD_ENABLE | lcdc);
+        while !ppu.take_frame_completed() {
+            ppu.tick(1);
+        }
+    }
+
+    fn pixel(ppu: &Ppu, x: usize, y: usize) -> u8 {
+        ppu.frame()[y * SCREEN_WIDTH + x]
+    }
+
+    #[test]
+    fn test_background() {
+        let mut ppu = Ppu::new();
+        // Tile 1 at 0x9010 with signed addressing, and tile -1 at 0x8FF0.
+        fill_tile(&mut ppu, 0x9010, 1);
+        fill_tile(&mut ppu, 0x8FF0, 2);
+        ppu.write_u8(0x9800, 0x01);
+        ppu.write_u8(0x9821, 0xFF);
+        ppu.write_u8(SCX, 4);
+        ppu.write_u8(SCY, 0xFC);
+        render(&mut ppu, BG_ENABLE);
+        assert_eq!(0, pixel(&ppu, 0, 3));
+        assert_eq!(1, pixel(&ppu, 0, 4));
+        assert_eq!(1, pixel(&ppu, 3, 11));
+        assert_eq!(0, pixel(&ppu, 4, 11));
+        assert_eq!(2, pixel(&ppu, 4, 12));
+        // The background wraps around.
+        assert_eq!(0, pixel(&ppu, 0, 0));
+    }
+
+    #[test]
+    fn test_window() {
+        let mut ppu = Ppu::new();
+        fill_tile(&mut ppu, 0x8010, 3);
+        ppu.write_u8(0x9C00, 0x01);
+        ppu.write_u8(WY, 10);
+        ppu.write_u8(WX, 17);
+        render(
+            &mut ppu,
+            BG_ENABLE | UNSIGNED_TILE_DATA | WINDOW_ENABLE | WINDOW_MAP,
+        );
+        assert_eq!(0, pixel(&ppu, 10, 9));
+        assert_eq!(0, pixel(&ppu, 9, 10));
+        assert_eq!(3, pixel(&ppu, 10, 10));
+        assert_eq!(3, pixel(&ppu, 17, 17));
+        assert_eq!(0, pixel(&ppu, 18, 17));
+        assert_eq!(0, pixel(&ppu, 10, 18));
+    }
+
+    #[test]
+    fn test_sprites() {
+        let mut ppu = Ppu::new();
+        fill_tile(&mut ppu, 0x8010, 1);
+        // A tile with only its top left pixel set.
+        ppu.write_u8(0x8020, 0x80);
+        ppu.write_u8(0x8021, 0x80);
+        fill_tile(&mut ppu, 0x8030, 2);
+        ppu.write_u8(0x9800, 0x01);
+        ppu.write_u8(0x9802, 0x01);
+        let sprites = [
+            // Flipped both ways, so its only pixel is at the bottom right.
+            [16, 8, 0x02, 0x60],
+            // Overlaps the first one from the right, and shows where the first is transparent.
+            [16, 12, 0x01, 0x10],
+            // Behind the background, so it only shows where the background has color 0.
+            [20, 24, 0x03, 0x80],
+            [16, 40, 0x03, 0x00],
+        ];
+        for (i, sprite) in sprites.iter().enumerate() {
+            for (j, &byte) in sprite.iter().enumerate() {
+                ppu.write_u8(0xFE00 + 4 * i as u16 + j as u16, byte);
+            }
+        }
+        render(&mut ppu, BG_ENABLE | UNSIGNED_TILE_DATA | SPRITE_ENABLE);
+        assert_eq!(1, pixel(&ppu, 0, 0));
+        assert_eq!(3, pixel(&ppu, 7, 7));
+        assert_eq!(2, pixel(&ppu, 6, 7));
+        assert_eq!(2, pixel(&ppu, 11, 0));
+        assert_eq!(1, pixel(&ppu, 16, 4));
+        assert_eq!(2, pixel(&ppu, 16, 8));
+
+        // Tall sprites are made up of the even tile and the odd one after it.
+        render(
+            &mut ppu,
+            BG_ENABLE | UNSIGNED_TILE_DATA | SPRITE_ENABLE | TALL_SPRITES,
+        );
+        assert_eq!(3, pixel(&ppu, 32, 0));
+        assert_eq!(0, pixel(&ppu, 33, 0));
+        assert_eq!(2, pixel(&ppu, 32, 8));
+    }
+
+    #[test]
+    fn test_sprite_limit() {
+        let mut ppu = Ppu::new();
+        fill_tile(&mut ppu, 0x8010, 3);
+        for i in 0..11 {
+            ppu.write_u8(0xFE00 + 4 * i, 16);
+            ppu.write_u8(0xFE01 + 4 * i, 8 + 8 * i as u8);
+            ppu.write_u8(0xFE02 + 4 * i, 0x01);
+        }
+        render(&mut ppu, SPRITE_ENABLE);
+        assert_eq!(3, pixel(&ppu, 79, 0));
+        assert_eq!(0, pixel(&ppu, 80, 0));
+    }
+}
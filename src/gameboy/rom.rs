@@ -0,0 +1,566 @@
+use std::{fmt, fs, path::Path};
+
+use failure::{Error, Fail, ResultExt};
+
+/// The Nintendo logo, which the boot ROM compares against the cartridge's copy at 0x0104 before
+/// handing over control to the cartridge.
+pub(crate) const LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+/// The size of a ROM bank, which is also the smallest possible ROM.
+pub(crate) const ROM_BANK_SIZE: usize = 0x4000;
+
+const LOGO_START: usize = 0x0104;
+const TITLE_START: usize = 0x0134;
+const MANUFACTURER_START: usize = 0x013F;
+const CGB_FLAG: usize = 0x0143;
+const NEW_LICENSEE_START: usize = 0x0144;
+const SGB_FLAG: usize = 0x0146;
+const CARTRIDGE_TYPE: usize = 0x0147;
+const ROM_SIZE: usize = 0x0148;
+const RAM_SIZE: usize = 0x0149;
+const DESTINATION: usize = 0x014A;
+const OLD_LICENSEE: usize = 0x014B;
+const VERSION: usize = 0x014C;
+const HEADER_CHECKSUM: usize = 0x014D;
+const GLOBAL_CHECKSUM: usize = 0x014E;
+const HEADER_END: usize = 0x0150;
+
+/// An old licensee code of 0x33 means that the new licensee code should be used instead.
+const USE_NEW_LICENSEE: u8 = 0x33;
+
+#[derive(Debug, PartialEq)]
+pub enum RomError {
+    MissingHeader(usize),
+    Truncated { expected: usize, actual: usize },
+    UnknownCartridgeType(u8),
+    UnknownRomSize(u8),
+    UnknownRamSize(u8),
+    UnknownDestination(u8),
+    BadLogo,
+    HeaderChecksum { expected: u8, actual: u8 },
+    GlobalChecksum { expected: u16, actual: u16 },
+    UnsupportedMapper(Mapper),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::RomError::*;
+        match self {
+            MissingHeader(len) => write!(
+                f,
+                "the file is too short to contain a header ({} bytes)",
+                len
+            ),
+            Truncated { expected, actual } => write!(
+                f,
+                "the header declares {} bytes of ROM, but the file is {} bytes",
+                expected, actual
+            ),
+            UnknownCartridgeType(code) => write!(f, "unknown cartridge type 0x{:02X}", code),
+            UnknownRomSize(code) => write!(f, "unknown ROM size code 0x{:02X}", code),
+            UnknownRamSize(code) => write!(f, "unknown RAM size code 0x{:02X}", code),
+            UnknownDestination(code) => write!(f, "unknown destination code 0x{:02X}", code),
+            BadLogo => write!(f, "the Nintendo logo doesn't match"),
+            HeaderChecksum { expected, actual } => write!(
+                f,
+                "header checksum is 0x{:02X}, but the header sums to 0x{:02X}",
+                expected, actual
+            ),
+            GlobalChecksum { expected, actual } => write!(
+                f,
+                "global checksum is 0x{:04X}, but the ROM sums to 0x{:04X}",
+                expected, actual
+            ),
+            UnsupportedMapper(mapper) => write!(f, "the {:?} mapper isn't supported", mapper),
+        }
+    }
+}
+
+impl Fail for RomError {}
+
+/// The memory bank controller, or other mapper, of a cartridge.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Mapper {
+    RomOnly,
+    Mbc1,
+    Mbc2,
+    Mbc3,
+    Mbc5,
+    Mbc6,
+    Mbc7,
+    Mmm01,
+    PocketCamera,
+    Tama5,
+    HuC1,
+    HuC3,
+}
+
+/// The cartridge type byte, decoded into the mapper and the extra hardware on the cartridge.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CartridgeType {
+    pub mapper: Mapper,
+    pub ram: bool,
+    pub battery: bool,
+    pub timer: bool,
+    pub rumble: bool,
+    pub sensor: bool,
+}
+
+impl CartridgeType {
+    fn new(mapper: Mapper) -> CartridgeType {
+        CartridgeType {
+            mapper,
+            ram: false,
+            battery: false,
+            timer: false,
+            rumble: false,
+            sensor: false,
+        }
+    }
+
+    fn ram(self) -> CartridgeType {
+        CartridgeType { ram: true, ..self }
+    }
+
+    fn battery(self) -> CartridgeType {
+        CartridgeType {
+            battery: true,
+            ..self
+        }
+    }
+
+    fn timer(self) -> CartridgeType {
+        CartridgeType {
+            timer: true,
+            ..self
+        }
+    }
+
+    fn rumble(self) -> CartridgeType {
+        CartridgeType {
+            rumble: true,
+            ..self
+        }
+    }
+
+    fn sensor(self) -> CartridgeType {
+        CartridgeType {
+            sensor: true,
+            ..self
+        }
+    }
+
+    pub fn from_code(code: u8) -> Result<CartridgeType, RomError> {
+        use self::Mapper::*;
+        let new = CartridgeType::new;
+        Ok(match code {
+            0x00 => new(RomOnly),
+            0x01 => new(Mbc1),
+            0x02 => new(Mbc1).ram(),
+            0x03 => new(Mbc1).ram().battery(),
+            0x05 => new(Mbc2),
+            0x06 => new(Mbc2).battery(),
+            0x08 => new(RomOnly).ram(),
+            0x09 => new(RomOnly).ram().battery(),
+            0x0B => new(Mmm01),
+            0x0C => new(Mmm01).ram(),
+            0x0D => new(Mmm01).ram().battery(),
+            0x0F => new(Mbc3).timer().battery(),
+            0x10 => new(Mbc3).timer().ram().battery(),
+            0x11 => new(Mbc3),
+            0x12 => new(Mbc3).ram(),
+            0x13 => new(Mbc3).ram().battery(),
+            0x19 => new(Mbc5),
+            0x1A => new(Mbc5).ram(),
+            0x1B => new(Mbc5).ram().battery(),
+            0x1C => new(Mbc5).rumble(),
+            0x1D => new(Mbc5).rumble().ram(),
+            0x1E => new(Mbc5).rumble().ram().battery(),
+            0x20 => new(Mbc6),
+            0x22 => new(Mbc7).sensor().rumble().ram().battery(),
+            0xFC => new(PocketCamera),
+            0xFD => new(Tama5),
+            0xFE => new(HuC3).timer().ram().battery(),
+            0xFF => new(HuC1).ram().battery(),
+            _ => return Err(RomError::UnknownCartridgeType(code)),
+        })
+    }
+}
+
+/// Whether, and how, the cartridge supports the Game Boy Color.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CgbSupport {
+    None,
+    /// Works on both DMG and CGB, but with CGB enhancements.
+    Compatible,
+    Only,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Destination {
+    Japan,
+    Overseas,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Licensee {
+    /// A one byte licensee code, as used by the older cartridges.
+    Old(u8),
+    /// A two character licensee code, as used by cartridges released after the SGB.
+    New([u8; 2]),
+}
+
+impl fmt::Display for Licensee {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Licensee::Old(code) => write!(f, "{:02X}", code),
+            Licensee::New(code) => write!(f, "{}", String::from_utf8_lossy(code)),
+        }
+    }
+}
+
+/// The cartridge header, located at 0x0100-0x014F.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    pub title: String,
+    pub manufacturer: Option<String>,
+    pub cgb: CgbSupport,
+    pub sgb: bool,
+    pub licensee: Licensee,
+    pub cartridge_type: CartridgeType,
+    /// The size of the ROM in bytes.
+    pub rom_size: usize,
+    /// The size of the external RAM in bytes, not counting RAM built into the mapper.
+    pub ram_size: usize,
+    pub destination: Destination,
+    pub version: u8,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+}
+
+impl Header {
+    pub fn parse(rom: &[u8]) -> Result<Header, RomError> {
+        if rom.len() < HEADER_END {
+            return Err(RomError::MissingHeader(rom.len()));
+        }
+
+        let cgb = match rom[CGB_FLAG] {
+            0xC0 => CgbSupport::Only,
+            flag if flag & 0x80 != 0 => CgbSupport::Compatible,
+            _ => CgbSupport::None,
+        };
+        // Cartridges made for the CGB have a shorter title, followed by a manufacturer code.
+        // Older cartridges use the full 16 bytes for the title.
+        let (title, manufacturer) = if cgb == CgbSupport::None {
+            (ascii(&rom[TITLE_START..NEW_LICENSEE_START]), None)
+        } else {
+            let manufacturer = &rom[MANUFACTURER_START..CGB_FLAG];
+            let manufacturer = if manufacturer.iter().all(u8::is_ascii_alphanumeric) {
+                Some(ascii(manufacturer))
+            } else {
+                None
+            };
+            (ascii(&rom[TITLE_START..MANUFACTURER_START]), manufacturer)
+        };
+
+        let licensee = match rom[OLD_LICENSEE] {
+            USE_NEW_LICENSEE => {
+                Licensee::New([rom[NEW_LICENSEE_START], rom[NEW_LICENSEE_START + 1]])
+            }
+            code => Licensee::Old(code),
+        };
+
+        let rom_size = match rom[ROM_SIZE] {
+            code @ 0x00..=0x08 => (2 * ROM_BANK_SIZE) << code,
+            0x52 => 72 * ROM_BANK_SIZE,
+            0x53 => 80 * ROM_BANK_SIZE,
+            0x54 => 96 * ROM_BANK_SIZE,
+            code => return Err(RomError::UnknownRomSize(code)),
+        };
+        let ram_size = match rom[RAM_SIZE] {
+            0x00 => 0,
+            0x01 => 0x800,
+            0x02 => 0x2000,
+            0x03 => 0x8000,
+            0x04 => 0x20000,
+            0x05 => 0x10000,
+            code => return Err(RomError::UnknownRamSize(code)),
+        };
+        let destination = match rom[DESTINATION] {
+            0x00 => Destination::Japan,
+            0x01 => Destination::Overseas,
+            code => return Err(RomError::UnknownDestination(code)),
+        };
+
+        Ok(Header {
+            title,
+            manufacturer,
+            cgb,
+            sgb: rom[SGB_FLAG] == 0x03,
+            licensee,
+            cartridge_type: CartridgeType::from_code(rom[CARTRIDGE_TYPE])?,
+            rom_size,
+            ram_size,
+            destination,
+            version: rom[VERSION],
+            header_checksum: rom[HEADER_CHECKSUM],
+            global_checksum: u16::from(rom[GLOBAL_CHECKSUM]) << 8
+                | u16::from(rom[GLOBAL_CHECKSUM + 1]),
+        })
+    }
+}
+
+/// Reads a zero padded ASCII string.
+pub(crate) fn ascii(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .take_while(|&&b| b != 0)
+        .map(|&b| if b.is_ascii_graphic() { b as char } else { ' ' })
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+/// The checksum over 0x0134-0x014C that the boot ROM verifies.
+fn header_checksum(rom: &[u8]) -> u8 {
+    rom[TITLE_START..HEADER_CHECKSUM]
+        .iter()
+        .fold(0u8, |x, &b| x.wrapping_sub(b).wrapping_sub(1))
+}
+
+/// Whether there's a copy of the Nintendo logo in the header starting at `base`, which is where
+/// the games of a multicart have their headers.
+pub(crate) fn has_logo(rom: &[u8], base: usize) -> bool {
+    let start = base + LOGO_START;
+    rom.get(start..start + LOGO.len()) == Some(&LOGO[..])
+}
+
+/// Where the menu of an MMM01 multicart starts, if the ROM is one. The menu is in the last 32 kB,
+/// and its header is the one that describes the cartridge, while the header at the start of the
+/// ROM belongs to the first game.
+fn mmm01_menu(rom: &[u8]) -> Option<usize> {
+    let menu = rom.len().checked_sub(2 * ROM_BANK_SIZE)?;
+    if menu == 0 || !has_logo(rom, menu) {
+        return None;
+    }
+    match rom[menu + CARTRIDGE_TYPE] {
+        0x0B..=0x0D => Some(menu),
+        _ => None,
+    }
+}
+
+/// The sum of all bytes in the ROM, except the two bytes of the global checksum itself, in the
+/// header starting at `header`.
+fn global_checksum(rom: &[u8], header: usize) -> u16 {
+    let checksum = header + GLOBAL_CHECKSUM;
+    rom.iter()
+        .enumerate()
+        .filter(|&(i, _)| i != checksum && i != checksum + 1)
+        .fold(0u16, |sum, (_, &b)| sum.wrapping_add(u16::from(b)))
+}
+
+/// A cartridge ROM along with its parsed header.
+#[derive(Debug)]
+pub struct Cartridge {
+    pub header: Header,
+    pub(crate) rom: Box<[u8]>,
+}
+
+impl Cartridge {
+    pub fn from_bytes(rom: Vec<u8>) -> Result<Cartridge, RomError> {
+        let header = match mmm01_menu(&rom) {
+            Some(menu) => Header::parse(&rom[menu..])?,
+            None => Header::parse(&rom)?,
+        };
+        if rom.len() < header.rom_size {
+            return Err(RomError::Truncated {
+                expected: header.rom_size,
+                actual: rom.len(),
+            });
+        }
+        Ok(Cartridge {
+            header,
+            rom: rom.into_boxed_slice(),
+        })
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Cartridge, Error> {
+        let path = path.as_ref();
+        let rom = fs::read(path).with_context(|_| format!("couldn't read {}", path.display()))?;
+        let cartridge = Cartridge::from_bytes(rom)
+            .with_context(|_| format!("couldn't load {}", path.display()))?;
+        Ok(cartridge)
+    }
+
+    /// Verifies the Nintendo logo and the header checksum, which the boot ROM refuses to run the
+    /// cartridge without, as well as the global checksum, which nothing verifies in practice.
+    pub fn verify(&self) -> Result<(), RomError> {
+        let header = mmm01_menu(&self.rom).unwrap_or(0);
+        if !has_logo(&self.rom, header) {
+            return Err(RomError::BadLogo);
+        }
+        let actual = header_checksum(&self.rom[header..]);
+        if actual != self.header.header_checksum {
+            return Err(RomError::HeaderChecksum {
+                expected: self.header.header_checksum,
+                actual,
+            });
+        }
+        let actual = global_checksum(&self.rom, header);
+        if actual != self.header.global_checksum {
+            return Err(RomError::GlobalChecksum {
+                expected: self.header.global_checksum,
+                actual,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Builds an empty ROM with a valid header, from the raw cartridge type and size codes.
+    pub(crate) fn rom(cartridge_type: u8, rom_size: u8, ram_size: u8) -> Vec<u8> {
+        let mut rom = vec![0u8; (2 * ROM_BANK_SIZE) << rom_size];
+        rom[LOGO_START..LOGO_START + LOGO.len()].copy_from_slice(&LOGO);
+        rom[TITLE_START..TITLE_START + 7].copy_from_slice(b"RUSTBOI");
+        rom[OLD_LICENSEE] = 0x01;
+        rom[CARTRIDGE_TYPE] = cartridge_type;
+        rom[ROM_SIZE] = rom_size;
+        rom[RAM_SIZE] = ram_size;
+        rom[DESTINATION] = 0x01;
+        fix_checksums(&mut rom);
+        rom
+    }
+
+    pub(crate) fn fix_checksums(rom: &mut [u8]) {
+        rom[HEADER_CHECKSUM] = header_checksum(rom);
+        let sum = global_checksum(rom, 0);
+        rom[GLOBAL_CHECKSUM] = (sum >> 8) as u8;
+        rom[GLOBAL_CHECKSUM + 1] = sum as u8;
+    }
+
+    #[test]
+    fn test_parse() {
+        let cartridge = Cartridge::from_bytes(rom(0x13, 0x02, 0x03)).unwrap();
+        let header = &cartridge.header;
+        assert_eq!("RUSTBOI", header.title);
+        assert_eq!(None, header.manufacturer);
+        assert_eq!(CgbSupport::None, header.cgb);
+        assert!(!header.sgb);
+        assert_eq!(Licensee::Old(0x01), header.licensee);
+        assert_eq!(Mapper::Mbc3, header.cartridge_type.mapper);
+        assert!(header.cartridge_type.ram);
+        assert!(header.cartridge_type.battery);
+        assert!(!header.cartridge_type.timer);
+        assert_eq!(0x20000, header.rom_size);
+        assert_eq!(0x8000, header.ram_size);
+        assert_eq!(Destination::Overseas, header.destination);
+        assert_eq!(Ok(()), cartridge.verify());
+    }
+
+    #[test]
+    fn test_parse_cgb() {
+        let mut rom = rom(0x00, 0x00, 0x00);
+        rom[TITLE_START..MANUFACTURER_START].copy_from_slice(b"POKEMON GLD");
+        rom[MANUFACTURER_START..CGB_FLAG].copy_from_slice(b"AAUE");
+        rom[CGB_FLAG] = 0x80;
+        rom[NEW_LICENSEE_START..SGB_FLAG].copy_from_slice(b"01");
+        rom[SGB_FLAG] = 0x03;
+        rom[OLD_LICENSEE] = USE_NEW_LICENSEE;
+        let header = Header::parse(&rom).unwrap();
+        assert_eq!("POKEMON GLD", header.title);
+        assert_eq!(Some("AAUE".to_string()), header.manufacturer);
+        assert_eq!(CgbSupport::Compatible, header.cgb);
+        assert!(header.sgb);
+        assert_eq!(Licensee::New(*b"01"), header.licensee);
+        assert_eq!("01", header.licensee.to_string());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Err(RomError::MissingHeader(0x100)),
+            Header::parse(&[0u8; 0x100])
+        );
+
+        let mut bad = rom(0x00, 0x00, 0x00);
+        bad[CARTRIDGE_TYPE] = 0x04;
+        assert_eq!(
+            Err(RomError::UnknownCartridgeType(0x04)),
+            Header::parse(&bad)
+        );
+
+        let mut bad = rom(0x00, 0x00, 0x00);
+        bad[ROM_SIZE] = 0x09;
+        assert_eq!(Err(RomError::UnknownRomSize(0x09)), Header::parse(&bad));
+
+        let mut bad = rom(0x00, 0x00, 0x00);
+        bad[RAM_SIZE] = 0x06;
+        assert_eq!(Err(RomError::UnknownRamSize(0x06)), Header::parse(&bad));
+
+        let mut bad = rom(0x00, 0x00, 0x00);
+        bad[DESTINATION] = 0x02;
+        assert_eq!(Err(RomError::UnknownDestination(0x02)), Header::parse(&bad));
+
+        let mut short = rom(0x01, 0x01, 0x00);
+        short.truncate(0x6000);
+        assert_eq!(
+            RomError::Truncated {
+                expected: 0x10000,
+                actual: 0x6000
+            },
+            Cartridge::from_bytes(short).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_verify() {
+        let mut bad = rom(0x00, 0x00, 0x00);
+        bad[LOGO_START] = 0;
+        let cartridge = Cartridge::from_bytes(bad).unwrap();
+        assert_eq!(Err(RomError::BadLogo), cartridge.verify());
+
+        let mut bad = rom(0x00, 0x00, 0x00);
+        bad[VERSION] = 1;
+        let expected = bad[HEADER_CHECKSUM];
+        let cartridge = Cartridge::from_bytes(bad).unwrap();
+        assert_eq!(
+            Err(RomError::HeaderChecksum {
+                expected,
+                actual: expected.wrapping_sub(1),
+            }),
+            cartridge.verify()
+        );
+
+        let mut bad = rom(0x00, 0x00, 0x00);
+        bad[0x7FFF] = 1;
+        let cartridge = Cartridge::from_bytes(bad).unwrap();
+        match cartridge.verify() {
+            Err(RomError::GlobalChecksum { expected, actual }) => assert_eq!(expected + 1, actual),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_mmm01_menu() {
+        // The first game's header comes first, and the menu with its header is at the end.
+        let mut rom = rom(0x01, 0x03, 0x00);
+        let menu = rom.len() - 2 * ROM_BANK_SIZE;
+        let header = self::rom(0x0D, 0x00, 0x03);
+        rom[menu..].copy_from_slice(&header);
+        let sum = global_checksum(&rom, menu);
+        rom[menu + GLOBAL_CHECKSUM] = (sum >> 8) as u8;
+        rom[menu + GLOBAL_CHECKSUM + 1] = sum as u8;
+        let cartridge = Cartridge::from_bytes(rom).unwrap();
+        assert_eq!(Mapper::Mmm01, cartridge.header.cartridge_type.mapper);
+        assert_eq!(0x8000, cartridge.header.ram_size);
+        assert_eq!(Ok(()), cartridge.verify());
+    }
+}
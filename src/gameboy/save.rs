@@ -0,0 +1,159 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use failure::{Error, Fail, ResultExt};
+
+use super::GameBoy;
+
+/// How long the save data has to stay unchanged before it's written, so that a game saving
+/// byte by byte results in a single write.
+const DEBOUNCE: Duration = Duration::from_secs(1);
+/// How long the save data can go unwritten while it keeps changing.
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// Keeps a `.sav` file next to the ROM in sync with the cartridge's battery-backed save data.
+#[derive(Debug)]
+pub struct SaveFile {
+    path: PathBuf,
+    /// When the unwritten changes were first and last noticed.
+    pending: Option<(Instant, Instant)>,
+}
+
+impl SaveFile {
+    /// The save file for the ROM at `rom`, i.e. the same path with the `.sav` extension.
+    pub fn for_rom<P: AsRef<Path>>(rom: P) -> SaveFile {
+        SaveFile {
+            path: rom.as_ref().with_extension("sav"),
+            pending: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Restores the save data from the file, if the cartridge has a battery and the file exists.
+    pub fn load(&self, gb: &mut GameBoy) -> Result<(), Error> {
+        if !gb.has_battery() {
+            return Ok(());
+        }
+        let save = match fs::read(&self.path) {
+            Ok(save) => save,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.context(self.describe("couldn't read")).into()),
+        };
+        gb.load_save_data(&save)
+            .with_context(|_| self.describe("couldn't load"))?;
+        Ok(())
+    }
+
+    /// Takes note of changes to the save data, and writes it once it has settled. Meant to be
+    /// called after every step or frame.
+    pub fn update(&mut self, gb: &mut GameBoy) -> Result<(), Error> {
+        let changed = gb.save_data_changed();
+        if changed || self.pending.is_some() {
+            self.update_at(gb, changed, Instant::now())?;
+        }
+        Ok(())
+    }
+
+    fn update_at(&mut self, gb: &GameBoy, changed: bool, now: Instant) -> Result<(), Error> {
+        if changed {
+            let first = self.pending.map_or(now, |(first, _)| first);
+            self.pending = Some((first, now));
+        }
+        match self.pending {
+            Some((first, last)) if now - last >= DEBOUNCE || now - first >= MAX_DELAY => {
+                self.flush(gb)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the save data, if the cartridge has a battery. The file is replaced in one go, so
+    /// that it's never left half written.
+    pub fn flush(&mut self, gb: &GameBoy) -> Result<(), Error> {
+        self.pending = None;
+        let save = match gb.save_data() {
+            Some(save) => save,
+            None => return Ok(()),
+        };
+        let temporary = self.path.with_extension("sav.tmp");
+        fs::write(&temporary, save)
+            .and_then(|_| fs::rename(&temporary, &self.path))
+            .with_context(|_| self.describe("couldn't write"))?;
+        Ok(())
+    }
+
+    fn describe(&self, what: &str) -> String {
+        format!("{} {}", what, self.path.display())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::rom::tests::rom;
+    use crate::gameboy::rom::Cartridge;
+
+    fn gameboy(cartridge_type: u8) -> GameBoy {
+        let rom = rom(cartridge_type, 0x00, 0x02);
+        GameBoy::new(Cartridge::from_bytes(rom).unwrap()).unwrap()
+    }
+
+    fn temporary_rom(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rustboi-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("game.gb")
+    }
+
+    #[test]
+    fn test_debounce() {
+        let rom = temporary_rom("debounce");
+        let mut save = SaveFile::for_rom(&rom);
+        assert_eq!(rom.with_extension("sav"), save.path());
+        let _ = fs::remove_file(save.path());
+
+        let mut gb = gameboy(0x1B);
+        let start = Instant::now();
+        save.update_at(&gb, true, start).unwrap();
+        save.update_at(&gb, false, start + DEBOUNCE / 2).unwrap();
+        assert!(!save.path().exists());
+        save.update_at(&gb, false, start + DEBOUNCE).unwrap();
+        assert!(save.path().exists());
+
+        // Changes that keep coming are still written every now and then.
+        fs::remove_file(save.path()).unwrap();
+        for i in 0..10 {
+            save.update_at(&gb, true, start + i * DEBOUNCE / 2).unwrap();
+        }
+        assert!(!save.path().exists());
+        save.update_at(&gb, true, start + MAX_DELAY).unwrap();
+        assert!(save.path().exists());
+
+        // The file is loaded into a fresh cartridge.
+        let mut data = gb.save_data().unwrap();
+        data[0] = 0x42;
+        gb.load_save_data(&data).unwrap();
+        save.flush(&gb).unwrap();
+        let mut restored = gameboy(0x1B);
+        save.load(&mut restored).unwrap();
+        assert_eq!(Some(0x42), restored.save_data().map(|data| data[0]));
+        fs::remove_dir_all(rom.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_no_battery() {
+        let rom = temporary_rom("no-battery");
+        let mut save = SaveFile::for_rom(&rom);
+        let mut gb = gameboy(0x1A);
+        assert_eq!(None, gb.save_data());
+        assert!(gb.load_save_data(&[0; 0x2000]).is_err());
+        save.flush(&gb).unwrap();
+        assert!(!save.path().exists());
+        fs::remove_dir_all(rom.parent().unwrap()).unwrap();
+    }
+}
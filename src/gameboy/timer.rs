@@ -0,0 +1,216 @@
+use super::mem::Bus;
+
+const DIV: u16 = 0xFF04;
+const TIMA: u16 = 0xFF05;
+const TMA: u16 = 0xFF06;
+const TAC: u16 = 0xFF07;
+
+const TAC_ENABLE: u8 = 0x04;
+
+/// The timer, which is built around a 16 bit counter incremented every T-cycle. DIV is its upper
+/// byte, and TIMA is incremented whenever the counter bit selected by TAC falls from 1 to 0.
+///
+/// Since it's the falling edge that counts, resetting DIV or changing TAC can increment TIMA
+/// when it pulls the selected bit down.
+#[derive(Debug, Default)]
+pub(crate) struct Timer {
+    counter: u16,
+    tima: u8,
+    tma: u8,
+    tac: u8,
+    /// TIMA has overflowed on this M-cycle, and reads as 0x00 until it's reloaded on the next one.
+    overflow: bool,
+    /// TIMA has been reloaded from TMA on this M-cycle, which makes it ignore writes, while writes
+    /// to TMA go through to TIMA as well.
+    reloading: bool,
+}
+
+impl Timer {
+    /// Advances by an M-cycle, returning whether the timer interrupt is requested.
+    pub(crate) fn tick(&mut self) -> bool {
+        self.reloading = false;
+        let reload = self.overflow;
+        if reload {
+            self.overflow = false;
+            self.tima = self.tma;
+            self.reloading = true;
+        }
+        let signal = self.signal();
+        self.counter = self.counter.wrapping_add(4);
+        self.detect_falling_edge(signal);
+        reload
+    }
+
+    /// Resets the counter, and with it DIV, as writing to DIV and STOP do.
+    pub(crate) fn reset_div(&mut self) {
+        let signal = self.signal();
+        self.counter = 0;
+        self.detect_falling_edge(signal);
+    }
+
+    /// The counter bit selected by TAC, ANDed with the enable bit.
+    fn signal(&self) -> bool {
+        let bit = match self.tac & 0x03 {
+            0b00 => 9,
+            0b01 => 3,
+            0b10 => 5,
+            _ => 7,
+        };
+        self.tac & TAC_ENABLE != 0 && self.counter & (1 << bit) != 0
+    }
+
+    fn detect_falling_edge(&mut self, before: bool) {
+        if !before || self.signal() {
+            return;
+        }
+        let (tima, overflow) = self.tima.overflowing_add(1);
+        self.tima = tima;
+        self.overflow = overflow;
+    }
+}
+
+impl Bus for Timer {
+    fn read_u8(&self, addr: u16) -> u8 {
+        match addr {
+            DIV => (self.counter >> 8) as u8,
+            TIMA => self.tima,
+            TMA => self.tma,
+            // The upper five bits of TAC are unused.
+            TAC => 0xF8 | self.tac,
+            _ => unreachable!("invalid timer register 0x{:04X}", addr),
+        }
+    }
+
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        match addr {
+            DIV => self.reset_div(),
+            // A write on the cycle that TIMA overflows cancels the reload, while a write on the
+            // cycle that it's reloaded is lost.
+            TIMA if self.reloading => {}
+            TIMA => {
+                self.tima = value;
+                self.overflow = false;
+            }
+            TMA => {
+                self.tma = value;
+                if self.reloading {
+                    self.tima = value;
+                }
+            }
+            TAC => {
+                let signal = self.signal();
+                self.tac = value & 0x07;
+                self.detect_falling_edge(signal);
+            }
+            _ => unreachable!("invalid timer register 0x{:04X}", addr),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A timer counting every 4 M-cycles, i.e. with TAC selecting bit 3 of the counter.
+    fn fast_timer() -> Timer {
+        let mut timer = Timer::default();
+        timer.write_u8(TAC, TAC_ENABLE | 0b01);
+        timer
+    }
+
+    #[test]
+    fn test_div() {
+        let mut timer = Timer::default();
+        for _ in 0..64 {
+            timer.tick();
+        }
+        assert_eq!(0x01, timer.read_u8(DIV));
+        timer.write_u8(DIV, 0x42);
+        assert_eq!(0x00, timer.read_u8(DIV));
+        assert_eq!(0xF8, timer.read_u8(TAC));
+    }
+
+    #[test]
+    fn test_tima_frequencies() {
+        for &(tac, cycles) in &[(0b00, 256), (0b01, 4), (0b10, 16), (0b11, 64)] {
+            let mut timer = Timer::default();
+            timer.write_u8(TAC, TAC_ENABLE | tac);
+            for _ in 0..cycles - 1 {
+                timer.tick();
+            }
+            assert_eq!(0, timer.read_u8(TIMA), "TAC {}", tac);
+            timer.tick();
+            assert_eq!(1, timer.read_u8(TIMA), "TAC {}", tac);
+        }
+    }
+
+    #[test]
+    fn test_overflow_reload() {
+        let mut timer = fast_timer();
+        timer.write_u8(TMA, 0x80);
+        timer.write_u8(TIMA, 0xFF);
+        for _ in 0..4 {
+            assert!(!timer.tick());
+        }
+        // TIMA reads as 0x00 for a cycle before it's reloaded and the interrupt is requested.
+        assert_eq!(0x00, timer.read_u8(TIMA));
+        assert!(timer.tick());
+        assert_eq!(0x80, timer.read_u8(TIMA));
+        assert!(!timer.tick());
+    }
+
+    #[test]
+    fn test_writes_around_reload() {
+        // Writing TIMA while it's 0x00 cancels the reload.
+        let mut timer = fast_timer();
+        timer.write_u8(TIMA, 0xFF);
+        for _ in 0..4 {
+            timer.tick();
+        }
+        timer.write_u8(TIMA, 0x12);
+        assert!(!timer.tick());
+        assert_eq!(0x12, timer.read_u8(TIMA));
+
+        // Writing TIMA on the reload cycle is ignored, but writing TMA goes through to TIMA.
+        let mut timer = fast_timer();
+        timer.write_u8(TIMA, 0xFF);
+        for _ in 0..5 {
+            timer.tick();
+        }
+        timer.write_u8(TIMA, 0x12);
+        assert_eq!(0x00, timer.read_u8(TIMA));
+        timer.write_u8(TMA, 0x34);
+        assert_eq!(0x34, timer.read_u8(TIMA));
+        timer.tick();
+        timer.write_u8(TIMA, 0x56);
+        assert_eq!(0x56, timer.read_u8(TIMA));
+    }
+
+    #[test]
+    fn test_div_reset_glitch() {
+        let mut timer = fast_timer();
+        // Bit 3 of the counter is set after two M-cycles, so resetting DIV pulls it down.
+        timer.tick();
+        timer.tick();
+        timer.write_u8(DIV, 0);
+        assert_eq!(1, timer.read_u8(TIMA));
+        // With the bit clear, it doesn't.
+        timer.tick();
+        timer.write_u8(DIV, 0);
+        assert_eq!(1, timer.read_u8(TIMA));
+    }
+
+    #[test]
+    fn test_tac_glitch() {
+        let mut timer = fast_timer();
+        timer.tick();
+        timer.tick();
+        // Disabling the timer while the selected bit is set increments TIMA.
+        timer.write_u8(TAC, 0b01);
+        assert_eq!(1, timer.read_u8(TIMA));
+        // So does selecting a bit that's clear while the old one is set, here bit 9.
+        timer.write_u8(TAC, TAC_ENABLE | 0b01);
+        timer.write_u8(TAC, TAC_ENABLE);
+        assert_eq!(2, timer.read_u8(TIMA));
+    }
+}
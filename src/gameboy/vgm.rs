@@ -0,0 +1,184 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use failure::{Error, ResultExt};
+
+use super::apu::{RegisterLog, CLOCK_RATE};
+
+/// The size of the header, after which the commands start.
+const HEADER_SIZE: u32 = 0x100;
+/// 1.61, the first version to support the Game Boy.
+const VERSION: u32 = 0x161;
+/// The rate that waits are counted at.
+const SAMPLE_RATE: u64 = 44_100;
+
+const EOF_OFFSET: u64 = 0x04;
+const TOTAL_SAMPLES: u64 = 0x18;
+const DATA_OFFSET: usize = 0x34;
+const DMG_CLOCK: usize = 0x80;
+
+/// Writes value dd to register aa, i.e. to 0xFF10 + aa.
+const WRITE_DMG: u8 = 0xB3;
+/// Waits for n samples, given as a little-endian 16 bit value.
+const WAIT: u8 = 0x61;
+/// Waits for a 60th of a second.
+const WAIT_NTSC_FRAME: u8 = 0x62;
+/// Waits for a 50th of a second.
+const WAIT_PAL_FRAME: u8 = 0x63;
+/// Waits for 1 to 16 samples, given by the low nibble plus 1.
+const WAIT_SHORT: u8 = 0x70;
+const END_OF_DATA: u8 = 0x66;
+
+/// Records the writes to the APU's registers, as logged by `GameBoy::take_register_log`, to a VGM
+/// file, which players replay on an emulated APU of their own.
+///
+/// The end of the data and the sizes in the header are kept up to date as logs are written, so
+/// that the file is complete even if the recording is cut short before `finish`.
+#[derive(Debug)]
+pub struct VgmWriter<W: Write + Seek> {
+    inner: W,
+    /// The T-cycles of the logs written so far.
+    cycles: u64,
+    /// The samples waited for so far, which lag behind `cycles` by less than a sample.
+    samples: u64,
+}
+
+impl VgmWriter<BufWriter<File>> {
+    /// Creates the file at `path`, replacing it if it exists.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file =
+            File::create(path).with_context(|_| format!("couldn't create {}", path.display()))?;
+        VgmWriter::new(BufWriter::new(file))
+    }
+}
+
+impl<W: Write + Seek> VgmWriter<W> {
+    pub fn new(mut inner: W) -> Result<Self, Error> {
+        let mut header = [0u8; HEADER_SIZE as usize];
+        header[..4].copy_from_slice(b"Vgm ");
+        header[0x08..0x0C].copy_from_slice(&VERSION.to_le_bytes());
+        // The offset is relative to where it's stored.
+        header[DATA_OFFSET..DATA_OFFSET + 4]
+            .copy_from_slice(&(HEADER_SIZE - DATA_OFFSET as u32).to_le_bytes());
+        header[DMG_CLOCK..DMG_CLOCK + 4].copy_from_slice(&CLOCK_RATE.to_le_bytes());
+        inner.write_all(&header)?;
+        let mut vgm = VgmWriter {
+            inner,
+            cycles: 0,
+            samples: 0,
+        };
+        vgm.write_end()?;
+        Ok(vgm)
+    }
+
+    /// Appends the log's writes, along with the waits before them and up to the end of the log.
+    pub fn write_log(&mut self, log: &RegisterLog) -> Result<(), Error> {
+        for write in &log.writes {
+            self.wait_until(self.cycles + write.cycle)?;
+            let register = (write.address - 0xFF10) as u8;
+            self.inner.write_all(&[WRITE_DMG, register, write.value])?;
+        }
+        self.cycles += log.cycles;
+        self.wait_until(self.cycles)?;
+        self.write_end()
+    }
+
+    /// Waits for as many samples as have passed by `cycle`, counting from the start.
+    fn wait_until(&mut self, cycle: u64) -> Result<(), Error> {
+        let target = cycle * SAMPLE_RATE / u64::from(CLOCK_RATE);
+        while self.samples < target {
+            let samples = (target - self.samples).min(u64::from(u16::MAX));
+            match samples {
+                1..=16 => self.inner.write_all(&[WAIT_SHORT + samples as u8 - 1])?,
+                735 => self.inner.write_all(&[WAIT_NTSC_FRAME])?,
+                882 => self.inner.write_all(&[WAIT_PAL_FRAME])?,
+                _ => {
+                    let [lo, hi] = (samples as u16).to_le_bytes();
+                    self.inner.write_all(&[WAIT, lo, hi])?;
+                }
+            }
+            self.samples += samples;
+        }
+        Ok(())
+    }
+
+    /// Ends the commands and fills in the sizes in the header. The end of the data is then
+    /// overwritten by the next commands.
+    fn write_end(&mut self) -> Result<(), Error> {
+        self.inner.write_all(&[END_OF_DATA])?;
+        let end = self.inner.stream_position()?;
+        self.inner.seek(SeekFrom::Start(EOF_OFFSET))?;
+        self.inner.write_all(&(end as u32 - 4).to_le_bytes())?;
+        self.inner.seek(SeekFrom::Start(TOTAL_SAMPLES))?;
+        self.inner.write_all(&(self.samples as u32).to_le_bytes())?;
+        self.inner.seek(SeekFrom::Start(end - 1))?;
+        Ok(())
+    }
+
+    /// Flushes the commands, and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.inner.seek(SeekFrom::End(0))?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::apu::RegisterWrite;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_vgm() {
+        let mut vgm = VgmWriter::new(Cursor::new(Vec::new())).unwrap();
+        let write = |cycle, address, value| RegisterWrite {
+            cycle,
+            address,
+            value,
+        };
+        // Just over 735 samples, which is a 60th of a second.
+        vgm.write_log(&RegisterLog {
+            writes: vec![write(0, 0xFF26, 0x80), write(952, 0xFF30, 0x12)],
+            cycles: 69_906,
+        })
+        .unwrap();
+        // Just under, but what was left over of the last sample adds up to it.
+        vgm.write_log(&RegisterLog {
+            writes: vec![],
+            cycles: 69_905,
+        })
+        .unwrap();
+        let bytes = vgm.finish().unwrap().into_inner();
+        assert_eq!(b"Vgm ", &bytes[..4]);
+        assert_eq!((bytes.len() as u32 - 4).to_le_bytes(), bytes[0x04..0x08]);
+        assert_eq!([0x61, 0x01, 0x00, 0x00], bytes[0x08..0x0C]);
+        assert_eq!(1470u32.to_le_bytes(), bytes[0x18..0x1C]);
+        assert_eq!([0xCC, 0x00, 0x00, 0x00], bytes[0x34..0x38]);
+        assert_eq!([0x00, 0x00, 0x40, 0x00], bytes[0x80..0x84]);
+        // Powering on, and 952 T-cycles later, which are 10 samples, writing the wave RAM.
+        assert_eq!([WRITE_DMG, 0x16, 0x80, 0x79], bytes[0x100..0x104]);
+        assert_eq!([WRITE_DMG, 0x20, 0x12], bytes[0x104..0x107]);
+        let end = [WAIT, 0xD5, 0x02, WAIT_NTSC_FRAME, END_OF_DATA];
+        assert_eq!(end, bytes[0x107..]);
+    }
+
+    #[test]
+    fn test_vgm_without_finish() {
+        let mut cursor = Cursor::new(Vec::new());
+        let mut vgm = VgmWriter::new(&mut cursor).unwrap();
+        vgm.write_log(&RegisterLog {
+            writes: vec![],
+            cycles: 952,
+        })
+        .unwrap();
+        let bytes = cursor.into_inner();
+        assert_eq!([0xFE, 0x00, 0x00, 0x00], bytes[0x04..0x08]);
+        assert_eq!(10u32.to_le_bytes(), bytes[0x18..0x1C]);
+        assert_eq!([WAIT_SHORT + 9, END_OF_DATA], bytes[0x100..]);
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use failure::{Error, ResultExt};
+
+/// The size of the header up to the samples, i.e. of the RIFF, fmt and data chunk headers.
+const HEADER_SIZE: u32 = 44;
+const CHANNELS: u16 = 2;
+const BYTES_PER_SAMPLE: u16 = 2;
+
+/// Records 16 bit stereo samples, as produced by `GameBoy::take_samples`, to a WAV file.
+///
+/// The sizes in the header are kept up to date as samples are written, so that the file is
+/// complete even if the recording is cut short before `finish`.
+#[derive(Debug)]
+pub struct WavWriter<W: Write + Seek> {
+    inner: W,
+    /// The number of bytes of samples written so far.
+    data_size: u32,
+}
+
+impl WavWriter<BufWriter<File>> {
+    /// Creates the file at `path`, replacing it if it exists.
+    pub fn create<P: AsRef<Path>>(path: P, sample_rate: u32) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file =
+            File::create(path).with_context(|_| format!("couldn't create {}", path.display()))?;
+        WavWriter::new(BufWriter::new(file), sample_rate)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut inner: W, sample_rate: u32) -> Result<Self, Error> {
+        let block_align = CHANNELS * BYTES_PER_SAMPLE;
+        inner.write_all(b"RIFF")?;
+        inner.write_all(&(HEADER_SIZE - 8).to_le_bytes())?;
+        inner.write_all(b"WAVEfmt ")?;
+        inner.write_all(&16u32.to_le_bytes())?;
+        // Uncompressed PCM.
+        inner.write_all(&1u16.to_le_bytes())?;
+        inner.write_all(&CHANNELS.to_le_bytes())?;
+        inner.write_all(&sample_rate.to_le_bytes())?;
+        inner.write_all(&(sample_rate * u32::from(block_align)).to_le_bytes())?;
+        inner.write_all(&block_align.to_le_bytes())?;
+        inner.write_all(&(8 * BYTES_PER_SAMPLE).to_le_bytes())?;
+        inner.write_all(b"data")?;
+        inner.write_all(&0u32.to_le_bytes())?;
+        Ok(WavWriter {
+            inner,
+            data_size: 0,
+        })
+    }
+
+    /// Appends the samples, as left and right pairs.
+    pub fn write_samples(&mut self, samples: &[[i16; 2]]) -> Result<(), Error> {
+        for &[left, right] in samples {
+            self.inner.write_all(&left.to_le_bytes())?;
+            self.inner.write_all(&right.to_le_bytes())?;
+        }
+        self.data_size += (samples.len() * usize::from(CHANNELS * BYTES_PER_SAMPLE)) as u32;
+        self.write_sizes()
+    }
+
+    /// Flushes the samples, and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    /// Fills in the sizes in the header, and goes back to the end for the next samples.
+    fn write_sizes(&mut self) -> Result<(), Error> {
+        self.inner.seek(SeekFrom::Start(4))?;
+        self.inner
+            .write_all(&(HEADER_SIZE - 8 + self.data_size).to_le_bytes())?;
+        self.inner
+            .seek(SeekFrom::Start(u64::from(HEADER_SIZE - 4)))?;
+        self.inner.write_all(&self.data_size.to_le_bytes())?;
+        self.inner.seek(SeekFrom::End(0))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_wav() {
+        let mut wav = WavWriter::new(Cursor::new(Vec::new()), 48_000).unwrap();
+        wav.write_samples(&[[1, -1], [0x1234, 0]]).unwrap();
+        let bytes = wav.finish().unwrap().into_inner();
+        assert_eq!(HEADER_SIZE as usize + 8, bytes.len());
+        assert_eq!(b"RIFF", &bytes[0..4]);
+        assert_eq!([44, 0, 0, 0], bytes[4..8]);
+        assert_eq!(b"WAVEfmt ", &bytes[8..16]);
+        // 2 channels at 48000 Hz, i.e. 192000 bytes per second in blocks of 4 bytes.
+        assert_eq!([2, 0, 0x80, 0xBB, 0, 0], bytes[22..28]);
+        assert_eq!([0x00, 0xEE, 0x02, 0x00, 4, 0, 16, 0], bytes[28..36]);
+        assert_eq!(b"data", &bytes[36..40]);
+        assert_eq!([8, 0, 0, 0], bytes[40..44]);
+        assert_eq!([1, 0, 0xFF, 0xFF, 0x34, 0x12, 0, 0], bytes[44..]);
+    }
+
+    #[test]
+    fn test_wav_without_finish() {
+        let mut cursor = Cursor::new(Vec::new());
+        let mut wav = WavWriter::new(&mut cursor, 48_000).unwrap();
+        wav.write_samples(&[[1, -1]]).unwrap();
+        let bytes = cursor.into_inner();
+        assert_eq!([40, 0, 0, 0], bytes[4..8]);
+        assert_eq!([4, 0, 0, 0], bytes[40..44]);
+        assert_eq!([1, 0, 0xFF, 0xFF], bytes[44..]);
+    }
+}
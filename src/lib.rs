@@ -0,0 +1,17 @@
+#![allow(clippy::upper_case_acronyms)]
+
+mod gameboy;
+
+pub use crate::gameboy::apu::{Channel, RegisterLog, RegisterWrite};
+pub use crate::gameboy::boot::{BootError, Model};
+pub use crate::gameboy::event::Event;
+pub use crate::gameboy::gbs::{GbsError, GbsFile, GbsHeader};
+pub use crate::gameboy::mbc::{Accelerometer, RtcClock, SaveError};
+pub use crate::gameboy::ppu::{Renderer, SCREEN_HEIGHT, SCREEN_WIDTH};
+pub use crate::gameboy::rom::{
+    Cartridge, CartridgeType, CgbSupport, Destination, Header, Licensee, Mapper, RomError,
+};
+pub use crate::gameboy::save::SaveFile;
+pub use crate::gameboy::vgm::VgmWriter;
+pub use crate::gameboy::wav::WavWriter;
+pub use crate::gameboy::GameBoy;
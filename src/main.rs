@@ -1,9 +1,341 @@
-use crate::gameboy::GameBoy;
+use std::{
+    env,
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
-mod gameboy;
+use failure::{format_err, Error, ResultExt};
+
+use rustboi::{Cartridge, Channel, GameBoy, GbsFile, Model, SaveFile, VgmWriter, WavWriter};
+
+const USAGE: &str = "usage: rustboi [--model dmg|mgb|sgb|cgb] [--boot-rom <file>] [--frames <n>] \
+                     [--record-audio <file>] [--record-channels <dir>] [--record-vgm <file>] \
+                     [--sample-rate <hz>] [--mute <channels>] [--solo <channels>] \
+                     [--songs <songs>] <rom|gbs>";
+const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+/// How long each song of a GBS file is rendered for without `--frames`, about two minutes.
+const DEFAULT_SONG_FRAMES: u64 = 2 * 60 * 60;
+
+/// Set by Ctrl-C, which stops the emulation the same way running out of frames does.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Default)]
+struct Options {
+    rom: String,
+    model: Model,
+    boot_rom: Option<String>,
+    /// Stops after this many frames, rather than running until something goes wrong.
+    frames: Option<u64>,
+    /// The WAV file to record the audio to.
+    record_audio: Option<String>,
+    /// The directory to record each channel to, as a WAV file of its own.
+    record_channels: Option<String>,
+    /// The VGM file to log the writes to the APU's registers to.
+    record_vgm: Option<String>,
+    sample_rate: u32,
+    muted: Vec<Channel>,
+    solo: Vec<Channel>,
+    /// The songs of a GBS file to render, counting from 1.
+    songs: Vec<u8>,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, Error> {
+        let mut options = Options {
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            ..Options::default()
+        };
+        let mut rom = None;
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format_err!("{} needs a value", arg))
+            };
+            match arg.as_str() {
+                "--model" => {
+                    options.model = match value()?.as_str() {
+                        "dmg" => Model::Dmg,
+                        "mgb" => Model::Mgb,
+                        "sgb" => Model::Sgb,
+                        "cgb" => Model::Cgb,
+                        model => return Err(format_err!("unknown model {}", model)),
+                    }
+                }
+                "--boot-rom" => options.boot_rom = Some(value()?),
+                "--frames" => {
+                    let frames = value()?;
+                    options.frames = Some(
+                        frames
+                            .parse()
+                            .map_err(|_| format_err!("invalid number of frames {}", frames))?,
+                    );
+                }
+                "--record-audio" => options.record_audio = Some(value()?),
+                "--record-channels" => options.record_channels = Some(value()?),
+                "--record-vgm" => options.record_vgm = Some(value()?),
+                "--mute" => options.muted.extend(parse_channels(&value()?)?),
+                "--solo" => options.solo.extend(parse_channels(&value()?)?),
+                "--songs" => {
+                    for song in value()?.split(',') {
+                        options.songs.push(
+                            song.trim()
+                                .parse()
+                                .map_err(|_| format_err!("invalid song {}", song))?,
+                        );
+                    }
+                }
+                "--sample-rate" => {
+                    let rate = value()?;
+                    options.sample_rate = match rate.parse() {
+                        Ok(rate) if rate > 0 => rate,
+                        _ => return Err(format_err!("invalid sample rate {}", rate)),
+                    };
+                }
+                _ if arg.starts_with("--") => return Err(format_err!("unknown option {}", arg)),
+                _ if rom.is_none() => rom = Some(arg),
+                _ => return Err(format_err!("unexpected argument {}", arg)),
+            }
+        }
+        options.rom = rom.ok_or_else(|| format_err!("no ROM given"))?;
+        Ok(options)
+    }
+}
+
+/// Parses a comma separated list of channel numbers, from 1 to 4.
+fn parse_channels(value: &str) -> Result<Vec<Channel>, Error> {
+    value
+        .split(',')
+        .map(|channel| match channel.trim() {
+            "1" => Ok(Channel::Square1),
+            "2" => Ok(Channel::Square2),
+            "3" => Ok(Channel::Wave),
+            "4" => Ok(Channel::Noise),
+            _ => Err(format_err!("invalid channel {}", channel)),
+        })
+        .collect()
+}
 
 fn main() {
-    let mut gb: GameBoy = Default::default();
-    println!("{:#?}", gb);
-    gb.run();
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = run(&options) {
+        let causes: Vec<String> = e.iter_chain().map(|cause| cause.to_string()).collect();
+        eprintln!("error: {}", causes.join(": "));
+        process::exit(1);
+    }
+}
+
+fn run(options: &Options) -> Result<(), Error> {
+    let gbs = Path::new(&options.rom)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("gbs"));
+    if gbs {
+        return render_gbs(options);
+    }
+    let cartridge = Cartridge::from_file(&options.rom)?;
+    // Emulators are more forgiving than the boot ROM, but it's still worth knowing about.
+    if let Err(e) = cartridge.verify() {
+        eprintln!("warning: {}", e);
+    }
+    let boot_rom = match &options.boot_rom {
+        Some(path) => Some(fs::read(path).with_context(|_| format!("couldn't read {}", path))?),
+        None => None,
+    };
+    let mut gb = GameBoy::with_model(cartridge, options.model, boot_rom)?;
+    let mut save = SaveFile::for_rom(&options.rom);
+    save.load(&mut gb)?;
+    set_channels(&mut gb, options);
+    let mut recording = Recording::start(
+        &mut gb,
+        options.record_audio.as_ref().map(PathBuf::from),
+        options.record_channels.as_ref().map(PathBuf::from),
+        options.record_vgm.as_ref().map(PathBuf::from),
+        options.sample_rate,
+    )?;
+    ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst))
+        .context("couldn't handle Ctrl-C")?;
+    let result = emulate(&mut gb, &mut save, options.frames, &mut recording);
+    // Whatever stopped the emulation, the save data and the recordings are still worth keeping.
+    save.flush(&gb)?;
+    recording.finish()?;
+    result
+}
+
+/// Renders the selected songs of a GBS file, or the first one, each to its own recording. With
+/// more than one song, the song number is added to the names of the recordings.
+fn render_gbs(options: &Options) -> Result<(), Error> {
+    if options.record_audio.is_none()
+        && options.record_channels.is_none()
+        && options.record_vgm.is_none()
+    {
+        return Err(format_err!(
+            "GBS files are only rendered, which needs --record-audio, --record-channels or \
+             --record-vgm"
+        ));
+    }
+    let gbs = GbsFile::from_file(&options.rom)?;
+    let header = &gbs.header;
+    eprintln!(
+        "{} - {} ({}), {} songs",
+        header.title, header.author, header.copyright, header.songs
+    );
+    let songs = if options.songs.is_empty() {
+        vec![header.first_song]
+    } else {
+        options.songs.clone()
+    };
+    let frames = options.frames.unwrap_or(DEFAULT_SONG_FRAMES);
+    for &song in &songs {
+        let mut gb = GameBoy::with_gbs(&gbs, song)?;
+        set_channels(&mut gb, options);
+        let name = |path: &String| {
+            let path = PathBuf::from(path);
+            if songs.len() == 1 {
+                return path;
+            }
+            let mut name = path.file_stem().unwrap_or_default().to_os_string();
+            name.push(format!("-{}", song));
+            if let Some(extension) = path.extension() {
+                name.push(".");
+                name.push(extension);
+            }
+            path.with_file_name(name)
+        };
+        let mut recording = Recording::start(
+            &mut gb,
+            options.record_audio.as_ref().map(name),
+            options.record_channels.as_ref().map(name),
+            options.record_vgm.as_ref().map(name),
+            options.sample_rate,
+        )?;
+        let mut result = Ok(());
+        for _ in 0..frames {
+            result = gb.run_frame().and_then(|()| recording.write(&mut gb));
+            if result.is_err() {
+                break;
+            }
+        }
+        recording.finish()?;
+        result.with_context(|_| format!("song {} stopped", song))?;
+    }
+    Ok(())
+}
+
+/// Mutes and solos the channels as selected.
+fn set_channels(gb: &mut GameBoy, options: &Options) {
+    for &channel in &options.muted {
+        gb.set_muted(channel, true);
+    }
+    for &channel in &options.solo {
+        gb.set_solo(channel, true);
+    }
+}
+
+/// Runs frame by frame, for the given number of frames, until Ctrl-C is pressed or until
+/// something goes wrong.
+fn emulate(
+    gb: &mut GameBoy,
+    save: &mut SaveFile,
+    frames: Option<u64>,
+    recording: &mut Recording,
+) -> Result<(), Error> {
+    let mut frame = 0;
+    while frames.is_none_or(|frames| frame < frames) && !INTERRUPTED.load(Ordering::SeqCst) {
+        gb.run_frame()?;
+        save.update(gb)?;
+        recording.write(gb)?;
+        // There's no motor to rumble or LED to light up here, so the events are just dropped.
+        while gb.poll_event().is_some() {}
+        frame += 1;
+    }
+    Ok(())
+}
+
+/// The files being recorded to, i.e. WAV files of the mix and of each channel, and a VGM file of
+/// the register writes.
+#[derive(Debug, Default)]
+struct Recording {
+    mix: Option<WavWriter<BufWriter<File>>>,
+    channels: Vec<(Channel, WavWriter<BufWriter<File>>)>,
+    vgm: Option<VgmWriter<BufWriter<File>>>,
+}
+
+impl Recording {
+    /// Starts recording the mix to the file at `mix`, each channel to a file of its own in the
+    /// directory `channels`, and the register writes to the file at `vgm`.
+    fn start(
+        gb: &mut GameBoy,
+        mix: Option<PathBuf>,
+        channels: Option<PathBuf>,
+        vgm: Option<PathBuf>,
+        sample_rate: u32,
+    ) -> Result<Recording, Error> {
+        let mut recording = Recording::default();
+        if let Some(path) = mix {
+            recording.mix = Some(WavWriter::create(path, sample_rate)?);
+        }
+        if let Some(dir) = channels {
+            fs::create_dir_all(&dir)
+                .with_context(|_| format!("couldn't create {}", dir.display()))?;
+            for &channel in &Channel::ALL {
+                let path = dir.join(format!("{}.wav", channel_name(channel)));
+                let wav = WavWriter::create(path, sample_rate)?;
+                recording.channels.push((channel, wav));
+            }
+            gb.set_channel_sampling(true);
+        }
+        if let Some(path) = vgm {
+            recording.vgm = Some(VgmWriter::create(path)?);
+            gb.set_register_logging(true);
+        }
+        if recording.mix.is_some() || !recording.channels.is_empty() {
+            gb.set_sample_rate(sample_rate);
+        }
+        Ok(recording)
+    }
+
+    fn write(&mut self, gb: &mut GameBoy) -> Result<(), Error> {
+        // The mix is taken even if it isn't recorded, so that it doesn't pile up.
+        let samples = gb.take_samples();
+        if let Some(mix) = &mut self.mix {
+            mix.write_samples(&samples)?;
+        }
+        for (channel, wav) in &mut self.channels {
+            wav.write_samples(&gb.take_channel_samples(*channel))?;
+        }
+        if let Some(vgm) = &mut self.vgm {
+            vgm.write_log(&gb.take_register_log())?;
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        if let Some(mix) = self.mix {
+            mix.finish()?;
+        }
+        for (_, wav) in self.channels {
+            wav.finish()?;
+        }
+        if let Some(vgm) = self.vgm {
+            vgm.finish()?;
+        }
+        Ok(())
+    }
+}
+
+fn channel_name(channel: Channel) -> &'static str {
+    match channel {
+        Channel::Square1 => "square1",
+        Channel::Square2 => "square2",
+        Channel::Wave => "wave",
+        Channel::Noise => "noise",
+    }
 }